@@ -0,0 +1 @@
+ALTER TABLE subtitles DROP COLUMN speakers_json
@@ -0,0 +1 @@
+ALTER TABLE subtitles ADD COLUMN speakers_json text not null default '[]'
@@ -0,0 +1,9 @@
+CREATE TABLE videos(
+    id varchar(255) primary key not null,
+    title text not null,
+    channel_id varchar(255) not null,
+    channel_title text not null,
+    duration varchar(64) not null,
+    thumbnails_json text not null,
+    fetched_at datetime not null
+)
@@ -0,0 +1,2 @@
+ALTER TABLE videos DROP COLUMN source_id;
+ALTER TABLE videos DROP COLUMN source;
\ No newline at end of file
@@ -0,0 +1,3 @@
+ALTER TABLE videos ADD COLUMN source varchar(16) not null default 'youtube';
+ALTER TABLE videos ADD COLUMN source_id text not null default '';
+UPDATE videos SET source_id = id;
\ No newline at end of file
@@ -0,0 +1,4 @@
+DROP TABLE user_languages;
+DROP INDEX users_username;
+ALTER TABLE users DROP COLUMN bio;
+ALTER TABLE users DROP COLUMN display_name;
\ No newline at end of file
@@ -0,0 +1,16 @@
+ALTER TABLE users ADD COLUMN display_name varchar(255) not null default '';
+ALTER TABLE users ADD COLUMN bio text not null default '';
+
+-- Usernames used to be copied from token claims, so they may not be unique yet
+UPDATE users SET username = username || '-' || substr(id, 1, 8)
+WHERE rowid NOT IN (SELECT min(rowid) FROM users GROUP BY lower(username));
+CREATE UNIQUE INDEX users_username ON users(username COLLATE NOCASE);
+
+CREATE TABLE user_languages(
+    user_id varchar(255) not null references users(id) on delete cascade,
+    language varchar(10) not null,
+    can_caption boolean not null default 1,
+    can_translate boolean not null default 0,
+    primary key (user_id, language)
+);
+CREATE INDEX user_languages_language ON user_languages(language);
@@ -0,0 +1,3 @@
+DROP INDEX changes_author;
+ALTER TABLE changes DROP COLUMN language;
+ALTER TABLE changes DROP COLUMN video_id;
\ No newline at end of file
@@ -0,0 +1,4 @@
+-- Changes made before this don't record their track, they still count towards edits
+ALTER TABLE changes ADD COLUMN video_id varchar(255) not null default '';
+ALTER TABLE changes ADD COLUMN language varchar(10) not null default '';
+CREATE INDEX changes_author ON changes(author, timestamp);
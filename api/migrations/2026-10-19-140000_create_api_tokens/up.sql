@@ -0,0 +1,13 @@
+CREATE TABLE api_tokens(
+    id varchar(255) primary key not null,
+    user_id varchar(255) not null references users(id) on delete cascade,
+    name varchar(255) not null,
+    -- SHA-256 of the whole token, the token itself is never stored
+    token_hash varchar(64) not null,
+    scopes text not null,
+    created_at datetime not null,
+    expires_at datetime not null,
+    last_used_at datetime
+);
+CREATE UNIQUE INDEX api_tokens_hash ON api_tokens(token_hash);
+CREATE INDEX api_tokens_user ON api_tokens(user_id);
@@ -0,0 +1 @@
+DROP TABLE video_metadata;
\ No newline at end of file
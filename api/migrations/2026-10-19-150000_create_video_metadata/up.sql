@@ -0,0 +1,9 @@
+-- Curated metadata for the database video provider, filled in by operators
+CREATE TABLE video_metadata(
+    id varchar(255) primary key not null,
+    title text not null,
+    channel_id varchar(255) not null,
+    channel_title text not null,
+    duration varchar(64) not null default '',
+    thumbnails_json text not null default '{}'
+)
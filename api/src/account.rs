@@ -0,0 +1,90 @@
+//! Data export and account deletion.
+
+use crate::{db::models::Change, DbConnection, IntoStatus};
+use api_types::user::User;
+use diesel::{result::Error as DieselError, Connection, ExpressionMethods, QueryDsl, RunQueryDsl};
+use serde::Serialize;
+use std::io::{Cursor, Write};
+use tonic::Status;
+use zip::{result::ZipError, write::FileOptions, CompressionMethod, ZipWriter};
+
+/// Author of changes whose account was deleted. The edits stay so track history remains
+/// complete, but they can't be traced back to anyone.
+pub const DELETED_AUTHOR: &str = "[deleted]";
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ExportedChange<'a> {
+    timestamp: String,
+    video_id: &'a str,
+    language: &'a str,
+    changes: serde_json::Value
+}
+
+fn zip_error(err: ZipError) -> Status {
+    Status::internal(format!("Couldn't build archive: {}", err))
+}
+
+pub fn authored_changes(conn: &DbConnection, user_id: &str) -> Result<Vec<Change>, Status> {
+    use crate::db::schema::changes::dsl::*;
+
+    changes
+        .filter(author.eq(user_id))
+        .order(timestamp)
+        .select(Change::COLUMNS)
+        .load::<Change>(conn)
+        .into_status()
+}
+
+/// Builds a zip with `profile.json`, `changes.json` and the stored profile pictures, given as
+/// file names and contents.
+pub fn export_archive(
+    profile: &User,
+    changes: &[Change],
+    pictures: Vec<(String, Vec<u8>)>
+) -> Result<Vec<u8>, Status> {
+    let changes: Vec<_> = changes.iter()
+        .map(|change| ExportedChange {
+            timestamp: change.timestamp.format("%Y-%m-%dT%H:%M:%SZ").to_string(),
+            video_id: &change.video_id,
+            language: &change.language,
+            changes: serde_json::from_str(&change.changes_json).unwrap_or(serde_json::Value::Null)
+        })
+        .collect();
+
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    let json = FileOptions::default().compression_method(CompressionMethod::Deflated);
+    // Images are compressed already
+    let image = FileOptions::default().compression_method(CompressionMethod::Stored);
+
+    let write_error = |e: serde_json::Error| Status::internal(format!("Couldn't build archive: {}", e));
+    zip.start_file("profile.json", json).map_err(zip_error)?;
+    serde_json::to_writer_pretty(&mut zip, profile).map_err(write_error)?;
+    zip.start_file("changes.json", json).map_err(zip_error)?;
+    serde_json::to_writer_pretty(&mut zip, &changes).map_err(write_error)?;
+    for (name, content) in pictures {
+        zip.start_file(format!("pictures/{}", name), image).map_err(zip_error)?;
+        zip.write_all(&content).map_err(|e| zip_error(e.into()))?;
+    }
+
+    Ok(zip.finish().map_err(zip_error)?.into_inner())
+}
+
+/// Removes the user, their language skills and API tokens and hands their changes over to
+/// [`DELETED_AUTHOR`].
+pub fn delete_user(conn: &DbConnection, user_id: &str) -> Result<(), Status> {
+    use crate::db::schema::{api_tokens, changes, user_languages, users};
+
+    conn.transaction::<_, DieselError, _>(|| {
+        diesel::update(changes::table.filter(changes::author.eq(user_id)))
+            .set(changes::author.eq(DELETED_AUTHOR))
+            .execute(conn)?;
+        diesel::delete(user_languages::table.filter(user_languages::user_id.eq(user_id)))
+            .execute(conn)?;
+        diesel::delete(api_tokens::table.filter(api_tokens::user_id.eq(user_id)))
+            .execute(conn)?;
+        diesel::delete(users::table.find(user_id))
+            .execute(conn)?;
+        Ok(())
+    }).into_status()
+}
@@ -0,0 +1,1044 @@
+//! Validation of bearer tokens, either forwarded by the web frontend from the identity provider
+//! or personal API tokens from [`crate::tokens`].
+//!
+//! Signing keys are loaded from the provider's JWKS on startup, or from the snapshot saved by the
+//! last fetch if the provider is down, and refetched periodically and whenever a token is signed
+//! with an unknown key. Every failure is reported as
+//! `UNAUTHENTICATED` with an [`ErrorInfo`] in the `auth` domain, so clients can tell an expired
+//! token (refresh and retry) from a forged one.
+//!
+//! Every RPC has to be listed in [`RPC_ACCESS`] with the scope it needs, [`Authorized`] checks
+//! it before the request reaches the service. Handlers get the caller from
+//! [`Principal::from_request`], which is only set after a token passed validation.
+
+use crate::{settings::ClaimMapping, tokens, Database, IntoStatus};
+use api_types::errors::ErrorInfo;
+use jsonwebtoken::{decode, decode_header, errors::ErrorKind, Algorithm, DecodingKey, Validation};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::{
+    collections::HashMap,
+    error::Error,
+    fmt::{self, Display},
+    fs, mem,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, RwLock},
+    time::{Duration, Instant}
+};
+use tokio::time;
+use tonic::{
+    body::BoxBody,
+    codegen::{http, BoxFuture, Context, Poll, Service},
+    metadata::{MetadataMap, MetadataValue},
+    transport::NamedService,
+    Code, Request, Status
+};
+
+/// Clock skew allowed between us and the identity provider, in seconds.
+const LEEWAY: u64 = 60;
+/// Tokens with unknown key ids trigger a refetch at most this often, so they can't be used to
+/// flood the provider with requests.
+const MIN_REFETCH_INTERVAL: Duration = Duration::from_secs(60);
+/// tonic 0.3 doesn't let handlers read request extensions, so the principal travels in binary
+/// metadata. The interceptor drops whatever a client sent under this key.
+const PRINCIPAL_KEY: &str = "x-principal-bin";
+/// Key the claims were passed under before the principal, also dropped so nothing can still
+/// trust a client-sent value.
+const LEGACY_PRINCIPAL_KEY: &str = "user-bin";
+
+pub const USER_READ: &str = "user.read";
+pub const USER_WRITE: &str = "user.write";
+pub const SUBTITLES_READ: &str = "subtitles.read";
+pub const SUBTITLES_WRITE: &str = "subtitles.write";
+/// Scope of the tokens the web frontend requests, which can do everything a user can. Tokens
+/// from the identity provider get it in place of the configured [`ClaimMapping::full_access`].
+pub const FULL_ACCESS: &str = "User";
+
+pub enum Access {
+    /// Open to anonymous callers, for public data
+    Public,
+    /// Needs a token with the scope (or [`FULL_ACCESS`])
+    Scope(&'static str)
+}
+
+/// Access rules by gRPC path. RPCs missing from here are rejected, so new ones have to be
+/// added explicitly.
+const RPC_ACCESS: &[(&str, Access)] = &[
+    ("/user.UserService/Send", Access::Scope(USER_READ)),
+    ("/user.UserService/SetProfilePicture", Access::Scope(USER_WRITE)),
+    ("/user.UserService/GetUser", Access::Scope(USER_READ)),
+    ("/user.UserService/GetProfilePicture", Access::Public),
+    ("/user.UserService/UpdateProfile", Access::Scope(USER_WRITE)),
+    ("/user.UserService/GetUserStats", Access::Public),
+    // API tokens never get full access, so a leaked one can't take the whole account or be
+    // used to mint more
+    ("/user.UserService/ExportMyData", Access::Scope(FULL_ACCESS)),
+    ("/user.UserService/DeleteAccount", Access::Scope(FULL_ACCESS)),
+    ("/user.UserService/CreateApiToken", Access::Scope(FULL_ACCESS)),
+    ("/user.UserService/ListApiTokens", Access::Scope(FULL_ACCESS)),
+    ("/user.UserService/RevokeApiToken", Access::Scope(FULL_ACCESS)),
+    ("/subtitles.VideoSubs/SetSubtitles", Access::Scope(SUBTITLES_WRITE)),
+    ("/subtitles.VideoSubs/GetSubtitles", Access::Scope(SUBTITLES_READ)),
+    ("/subtitles.VideoSubs/DownloadSubtitles", Access::Scope(SUBTITLES_READ)),
+    ("/subtitles.VideoSubs/ImportSubtitles", Access::Scope(SUBTITLES_WRITE)),
+    ("/subtitles.VideoSubs/ListSourceCaptions", Access::Scope(SUBTITLES_READ)),
+    ("/subtitles.VideoSubs/GetVideo", Access::Scope(SUBTITLES_READ)),
+    ("/subtitles.VideoSubs/AddVideo", Access::Scope(SUBTITLES_WRITE))
+];
+
+/// The authenticated caller of a request.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Principal {
+    pub subject: String,
+    pub name: String,
+    pub emails: Vec<String>,
+    pub scopes: Vec<String>,
+    pub roles: Vec<String>
+}
+
+/// Looks up a claim by its full name first, so namespaced claims like
+/// `https://example.com/roles` work, then as a dotted path.
+fn claim<'a>(claims: &'a Value, name: &str) -> Option<&'a Value> {
+    claims.get(name).or_else(|| {
+        name.split('.').try_fold(claims, |value, key| value.get(key))
+    })
+}
+
+fn claim_string(claims: &Value, name: &str) -> Option<String> {
+    claim(claims, name)
+        .and_then(Value::as_str)
+        .filter(|value| !value.is_empty())
+        .map(str::to_string)
+}
+
+/// Lists can be JSON arrays or space separated strings, like OAuth scopes.
+fn claim_list(claims: &Value, name: &str) -> Vec<String> {
+    match claim(claims, name) {
+        Some(Value::String(items)) => items.split_whitespace().map(str::to_string).collect(),
+        Some(Value::Array(items)) => items.iter()
+            .filter_map(Value::as_str)
+            .map(str::to_string)
+            .collect(),
+        _ => Vec::new()
+    }
+}
+
+impl ClaimMapping {
+    /// The provider's name for full access is translated, a scope that just happens to be
+    /// called like ours is dropped.
+    fn scopes(&self, claims: &Value) -> Vec<String> {
+        claim_list(claims, &self.scopes)
+            .into_iter()
+            .filter_map(|scope| match scope {
+                scope if scope == self.full_access => Some(FULL_ACCESS.to_string()),
+                scope if scope == FULL_ACCESS => None,
+                scope => Some(scope)
+            })
+            .collect()
+    }
+
+    pub fn principal(&self, claims: &Value) -> Result<Principal, AuthError> {
+        let subject = claim_string(claims, &self.subject)
+            .ok_or_else(|| AuthError::MissingClaim(self.subject.clone()))?;
+        Ok(Principal {
+            // Not every provider includes a name, the subject is better than nothing
+            name: claim_string(claims, &self.name).unwrap_or_else(|| subject.clone()),
+            subject,
+            emails: claim_list(claims, &self.email),
+            scopes: self.scopes(claims),
+            roles: claim_list(claims, &self.roles)
+        })
+    }
+}
+
+impl Principal {
+    /// The caller of an authenticated request, `UNAUTHENTICATED` if there is none.
+    pub fn from_request<T>(request: &Request<T>) -> Result<Principal, Status> {
+        let principal = request
+            .metadata()
+            .get_bin(PRINCIPAL_KEY)
+            .and_then(|principal| principal.to_bytes().ok())
+            .ok_or_else(|| Status::unauthenticated("not authenticated"))?;
+        serde_json::from_slice(&principal)
+            .map_err(|e| Status::internal(format!("Invalid principal: {}", e)))
+    }
+
+    pub fn has_scope(&self, scope: &str) -> bool {
+        self.scopes.iter().any(|granted| granted == scope || granted == FULL_ACCESS)
+    }
+
+    fn attach(&self, metadata: &mut MetadataMap) {
+        let json = serde_json::to_vec(self).unwrap();
+        metadata.insert_bin(PRINCIPAL_KEY, MetadataValue::from_bytes(&json));
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum AuthError {
+    /// Not a bearer token, or not a JWT at all
+    Malformed(String),
+    Expired,
+    NotYetValid,
+    InvalidSignature,
+    /// Signed with a key the provider doesn't publish, with the key id
+    UnknownKey(String),
+    WrongAudience,
+    WrongIssuer,
+    /// A claim the mapping needs isn't in the token, with the claim's name
+    MissingClaim(String),
+    /// An API token that doesn't exist or was revoked
+    UnknownToken
+}
+
+impl AuthError {
+    fn reason(&self) -> &'static str {
+        match self {
+            AuthError::Malformed(_) => "MALFORMED_TOKEN",
+            AuthError::Expired => "TOKEN_EXPIRED",
+            AuthError::NotYetValid => "TOKEN_NOT_YET_VALID",
+            AuthError::InvalidSignature => "INVALID_SIGNATURE",
+            AuthError::UnknownKey(_) => "UNKNOWN_KEY",
+            AuthError::WrongAudience => "WRONG_AUDIENCE",
+            AuthError::WrongIssuer => "WRONG_ISSUER",
+            AuthError::MissingClaim(_) => "MISSING_CLAIM",
+            AuthError::UnknownToken => "UNKNOWN_TOKEN"
+        }
+    }
+}
+
+impl Display for AuthError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AuthError::Malformed(message) => write!(f, "malformed token: {}", message),
+            AuthError::Expired => write!(f, "token has expired"),
+            AuthError::NotYetValid => write!(f, "token isn't valid yet"),
+            AuthError::InvalidSignature => write!(f, "token signature is invalid"),
+            AuthError::UnknownKey(kid) => write!(f, "token is signed with unknown key {}", kid),
+            AuthError::WrongAudience => write!(f, "token wasn't issued for this API"),
+            AuthError::WrongIssuer => write!(f, "token wasn't issued by the configured provider"),
+            AuthError::MissingClaim(claim) => write!(f, "token is missing the {} claim", claim),
+            AuthError::UnknownToken => write!(f, "API token doesn't exist or was revoked")
+        }
+    }
+}
+
+impl Error for AuthError {}
+
+impl From<AuthError> for Status {
+    fn from(err: AuthError) -> Self {
+        ErrorInfo::new("auth", err.reason()).into_status(Code::Unauthenticated, err.to_string())
+    }
+}
+
+impl From<jsonwebtoken::errors::Error> for AuthError {
+    fn from(err: jsonwebtoken::errors::Error) -> Self {
+        match err.kind() {
+            ErrorKind::ExpiredSignature => AuthError::Expired,
+            ErrorKind::ImmatureSignature => AuthError::NotYetValid,
+            ErrorKind::InvalidSignature => AuthError::InvalidSignature,
+            ErrorKind::InvalidAudience => AuthError::WrongAudience,
+            ErrorKind::InvalidIssuer => AuthError::WrongIssuer,
+            ErrorKind::InvalidAlgorithm => AuthError::Malformed("unsupported algorithm".to_string()),
+            _ => AuthError::Malformed(err.to_string())
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct Jwk {
+    #[serde(default)]
+    kid: String,
+    kty: String,
+    #[serde(default)]
+    n: String,
+    #[serde(default)]
+    e: String
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct JwkSet {
+    keys: Vec<Jwk>
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+struct ProviderMetadata {
+    issuer: String,
+    jwks_uri: String
+}
+
+/// What was last fetched from the provider, saved so the API can start while it's unreachable.
+#[derive(Serialize, Deserialize)]
+struct Snapshot {
+    /// The configured issuer, the one in the metadata can differ from it
+    issuer: String,
+    metadata: ProviderMetadata,
+    jwks: JwkSet
+}
+
+impl Snapshot {
+    async fn fetch(issuer: &str) -> Result<Self, Box<dyn Error>> {
+        let config_url = format!("{}/.well-known/openid-configuration", issuer.trim_end_matches('/'));
+        let metadata = reqwest::get(&config_url)
+            .await?
+            .error_for_status()?
+            .json::<ProviderMetadata>()
+            .await?;
+        let jwks = fetch_jwks(&metadata.jwks_uri).await?;
+        Ok(Snapshot { issuer: issuer.to_string(), metadata, jwks })
+    }
+
+    fn read(path: &Path, issuer: &str) -> Result<Self, Box<dyn Error>> {
+        let snapshot: Snapshot = serde_json::from_slice(&fs::read(path)?)?;
+        if snapshot.issuer != issuer {
+            return Err(format!("saved keys are for {}", snapshot.issuer).into());
+        }
+        Ok(snapshot)
+    }
+
+    fn write(&self, path: &Path) {
+        let result = serde_json::to_vec(self)
+            .map_err(Into::into)
+            .and_then(|json| fs::write(path, json));
+        if let Err(err) = result {
+            eprintln!("Couldn't save signing keys to {}: {}", path.display(), err);
+        }
+    }
+}
+
+async fn fetch_jwks(jwks_uri: &str) -> Result<JwkSet, Box<dyn Error>> {
+    Ok(reqwest::get(jwks_uri)
+        .await?
+        .error_for_status()?
+        .json::<JwkSet>()
+        .await?)
+}
+
+/// Uses the RSA keys from a JWKS, other key types are ignored.
+fn decoding_keys(jwks: &JwkSet) -> Result<HashMap<String, DecodingKey<'static>>, Box<dyn Error>> {
+    let keys = jwks.keys.iter()
+        .filter(|key| key.kty == "RSA")
+        .map(|key| {
+            let decoding_key = DecodingKey::from_rsa_components(&key.n, &key.e).into_static();
+            (key.kid.clone(), decoding_key)
+        })
+        .collect::<HashMap<_, _>>();
+    if keys.is_empty() {
+        return Err("JWKS doesn't contain any RSA keys".into());
+    }
+    Ok(keys)
+}
+
+/// Where the keys of a discovered provider come from, so they can be refetched.
+struct KeySource {
+    issuer: String,
+    metadata: ProviderMetadata,
+    snapshot: Option<PathBuf>
+}
+
+pub struct TokenValidator {
+    /// Keyed by key id, keys without one are stored under an empty id
+    keys: RwLock<HashMap<String, DecodingKey<'static>>>,
+    validation: Validation,
+    claims: ClaimMapping,
+    /// Unset for fixed key sets, which can't be refreshed
+    source: Option<KeySource>,
+    /// When a token with an unknown key last caused a refetch
+    last_refetch: Mutex<Option<Instant>>
+}
+
+impl TokenValidator {
+    pub fn new(
+        issuer: &str,
+        audience: &str,
+        claims: ClaimMapping,
+        keys: HashMap<String, DecodingKey<'static>>
+    ) -> Self {
+        let mut validation = Validation::new(Algorithm::RS256);
+        validation.leeway = LEEWAY;
+        validation.validate_nbf = true;
+        validation.iss = Some(issuer.to_string());
+        validation.set_audience(&[audience]);
+        Self {
+            keys: RwLock::new(keys),
+            validation,
+            claims,
+            source: None,
+            last_refetch: Mutex::new(None)
+        }
+    }
+
+    pub fn from_jwks(
+        issuer: &str,
+        audience: &str,
+        claims: ClaimMapping,
+        jwks: &JwkSet
+    ) -> Result<Self, Box<dyn Error>> {
+        Ok(Self::new(issuer, audience, claims, decoding_keys(jwks)?))
+    }
+
+    fn from_snapshot(snapshot: &Snapshot, audience: &str, claims: ClaimMapping) -> Result<Self, Box<dyn Error>> {
+        let mut validator = Self::from_jwks(&snapshot.metadata.issuer, audience, claims, &snapshot.jwks)?;
+        validator.source = Some(KeySource {
+            issuer: snapshot.issuer.clone(),
+            metadata: snapshot.metadata.clone(),
+            snapshot: None
+        });
+        Ok(validator)
+    }
+
+    /// Loads the issuer and signing keys from the provider's discovery document. What was fetched
+    /// is saved to `snapshot`, which is used instead if the provider can't be reached.
+    pub async fn load(
+        issuer: &str,
+        audience: &str,
+        claims: ClaimMapping,
+        snapshot: &Path
+    ) -> Result<Self, Box<dyn Error>> {
+        let fetched = match Snapshot::fetch(issuer).await {
+            Ok(fetched) => {
+                fetched.write(snapshot);
+                fetched
+            }
+            Err(err) => {
+                eprintln!("Couldn't reach identity provider, using saved signing keys: {}", err);
+                Snapshot::read(snapshot, issuer)
+                    .map_err(|e| format!("couldn't load saved signing keys from {}: {}", snapshot.display(), e))?
+            }
+        };
+
+        let mut validator = Self::from_snapshot(&fetched, audience, claims)?;
+        if let Some(source) = &mut validator.source {
+            source.snapshot = Some(snapshot.to_path_buf());
+        }
+        Ok(validator)
+    }
+
+    /// Refetches the provider's keys. Validators built from a fixed key set are left alone.
+    pub async fn refresh(&self) -> Result<(), Box<dyn Error>> {
+        let source = match &self.source {
+            Some(source) => source,
+            None => return Ok(())
+        };
+        let jwks = fetch_jwks(&source.metadata.jwks_uri).await?;
+        let keys = decoding_keys(&jwks)?;
+        *self.keys.write().unwrap() = keys;
+
+        if let Some(path) = &source.snapshot {
+            let snapshot = Snapshot {
+                issuer: source.issuer.clone(),
+                metadata: source.metadata.clone(),
+                jwks
+            };
+            snapshot.write(path);
+        }
+        Ok(())
+    }
+
+    /// Refetches the keys every `period`, so rotated keys are known before tokens use them. A zero
+    /// `period` disables this, keys are then only refetched when a token uses an unknown one.
+    pub fn refresh_periodically(self: Arc<Self>, period: Duration) {
+        if period == Duration::from_secs(0) {
+            return;
+        }
+        tokio::spawn(async move {
+            let mut interval = time::interval_at(time::Instant::now() + period, period);
+            loop {
+                interval.tick().await;
+                if let Err(err) = self.refresh().await {
+                    eprintln!("Couldn't refresh signing keys: {}", err);
+                }
+            }
+        });
+    }
+
+    /// Refetches the keys unless that was done recently, returns whether it did.
+    async fn refetch(&self) -> bool {
+        {
+            let mut last_refetch = self.last_refetch.lock().unwrap();
+            if last_refetch.map_or(false, |last| last.elapsed() < MIN_REFETCH_INTERVAL) {
+                return false;
+            }
+            *last_refetch = Some(Instant::now());
+        }
+        match self.refresh().await {
+            Ok(()) => true,
+            Err(err) => {
+                eprintln!("Couldn't refetch signing keys: {}", err);
+                false
+            }
+        }
+    }
+
+    pub fn validate(&self, token: &str) -> Result<Principal, AuthError> {
+        let header = decode_header(token)?;
+        let kid = header.kid.unwrap_or_default();
+        let keys = self.keys.read().unwrap();
+        let key = keys.get(&kid).ok_or_else(|| AuthError::UnknownKey(kid))?;
+        let claims = decode::<Value>(token, key, &self.validation)?.claims;
+        self.claims.principal(&claims)
+    }
+
+    /// Validates a token, refetching the keys first if it's signed with one that isn't known
+    /// yet, since the provider may have rotated them.
+    pub async fn validate_or_refetch(&self, token: &str) -> Result<Principal, AuthError> {
+        match self.validate(token) {
+            Err(AuthError::UnknownKey(_)) if self.refetch().await => self.validate(token),
+            result => result
+        }
+    }
+}
+
+/// The token from the value of an `authorization` header.
+fn bearer_token(header: &[u8]) -> Result<&str, AuthError> {
+    std::str::from_utf8(header)
+        .ok()
+        .and_then(|header| header.strip_prefix("Bearer "))
+        .map(str::trim)
+        .ok_or_else(|| AuthError::Malformed("expected a bearer token".to_string()))
+}
+
+/// Accepts both JWTs from the identity provider and personal API tokens.
+pub struct Authenticator {
+    validator: Arc<TokenValidator>,
+    db: Database
+}
+
+impl Authenticator {
+    pub fn new(validator: Arc<TokenValidator>, db: Database) -> Self {
+        Self { validator, db }
+    }
+
+    /// Validates the value of an `authorization` header.
+    pub async fn authenticate(&self, header: &[u8]) -> Result<Principal, Status> {
+        let token = bearer_token(header)?;
+        if tokens::is_api_token(token) {
+            let conn = self.db.get().into_status()?;
+            tokens::authenticate(&conn, token)
+        } else {
+            Ok(self.validator.validate_or_refetch(token).await?)
+        }
+    }
+}
+
+/// Checks the caller of a request to `path` against [`RPC_ACCESS`] and attaches the principal
+/// to its metadata. An `authorization` header is always validated, even for public RPCs.
+pub async fn authorize(auth: &Authenticator, path: &str, metadata: &mut MetadataMap) -> Result<(), Status> {
+    metadata.remove_bin(PRINCIPAL_KEY);
+    metadata.remove_bin(LEGACY_PRINCIPAL_KEY);
+    let access = RPC_ACCESS.iter()
+        .find(|(rpc, _)| *rpc == path)
+        .map(|(_, access)| access)
+        .ok_or_else(|| Status::permission_denied(format!("No access rule for {}", path)))?;
+
+    let principal = match metadata.get("authorization") {
+        Some(header) => Some(auth.authenticate(header.as_bytes()).await?),
+        None => None
+    };
+    if let Access::Scope(scope) = access {
+        let principal = principal.as_ref()
+            .ok_or_else(|| Status::unauthenticated("not authenticated"))?;
+        if !principal.has_scope(scope) {
+            return Err(ErrorInfo::new("auth", "MISSING_SCOPE")
+                .with_metadata("scope", *scope)
+                .into_status(Code::PermissionDenied, format!("Token doesn't grant {}", scope)));
+        }
+    }
+
+    if let Some(principal) = principal {
+        principal.attach(metadata);
+    }
+    Ok(())
+}
+
+/// Wraps a gRPC service so every request goes through [`authorize`] first. Interceptors can't
+/// be used for this since they don't get to see which RPC is called.
+#[derive(Clone)]
+pub struct Authorized<S> {
+    inner: S,
+    auth: Arc<Authenticator>
+}
+
+impl<S> Authorized<S> {
+    pub fn new(inner: S, auth: Arc<Authenticator>) -> Self {
+        Self { inner, auth }
+    }
+}
+
+impl<S: NamedService> NamedService for Authorized<S> {
+    const NAME: &'static str = S::NAME;
+}
+
+impl<S, B> Service<http::Request<B>> for Authorized<S>
+where
+    S: Service<http::Request<B>, Response = http::Response<BoxBody>> + Clone + Send + 'static,
+    S::Future: Send + 'static,
+    S::Error: Send + 'static,
+    B: Send + 'static
+{
+    type Response = http::Response<BoxBody>;
+    type Error = S::Error;
+    type Future = BoxFuture<Self::Response, Self::Error>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, mut req: http::Request<B>) -> Self::Future {
+        // Validation may have to refetch keys, so the call happens later. The service that was
+        // polled ready has to handle it, the clone takes its place for the next request.
+        let clone = self.inner.clone();
+        let mut inner = mem::replace(&mut self.inner, clone);
+        let auth = self.auth.clone();
+
+        Box::pin(async move {
+            let mut metadata = MetadataMap::from_headers(mem::take(req.headers_mut()));
+            let result = authorize(&auth, req.uri().path(), &mut metadata).await;
+            *req.headers_mut() = metadata.into_headers();
+
+            match result {
+                Ok(()) => inner.call(req).await,
+                Err(status) => Ok(status.to_http())
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::{models::NewUser, schema::{api_tokens, users}};
+    use chrono::{Duration, Utc};
+    use diesel::{
+        r2d2::{ConnectionManager, Pool},
+        ExpressionMethods, QueryDsl, RunQueryDsl, SqliteConnection
+    };
+    use jsonwebtoken::{encode, EncodingKey, Header};
+    use serde_json::json;
+    use std::net::SocketAddr;
+    use uuid::Uuid;
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::TcpListener
+    };
+
+    const ISSUER: &str = "https://login.example.com/tenant/v2.0/";
+    const AUDIENCE: &str = "api-client-id";
+    const KEY: &[u8] = include_bytes!("../tests/fixtures/test_key.pem");
+    const OTHER_KEY: &[u8] = include_bytes!("../tests/fixtures/other_key.pem");
+
+    const JWKS: &str = include_str!("../tests/fixtures/jwks.json");
+
+    fn serving(jwks: &str) -> Arc<Mutex<Option<String>>> {
+        Arc::new(Mutex::new(Some(jwks.to_string())))
+    }
+
+    /// Serves a discovery document and `jwks`, one connection at a time. While `jwks` is empty
+    /// every request fails, as if the provider was down.
+    async fn mock_issuer(jwks: Arc<Mutex<Option<String>>>) -> String {
+        let mut listener = TcpListener::bind(SocketAddr::from(([127, 0, 0, 1], 0))).await.unwrap();
+        let issuer = format!("http://{}", listener.local_addr().unwrap());
+        let metadata = json!({
+            "issuer": issuer,
+            "jwks_uri": format!("{}/jwks", issuer)
+        }).to_string();
+
+        tokio::spawn(async move {
+            loop {
+                let (mut socket, _) = listener.accept().await.unwrap();
+                let mut buffer = [0; 4096];
+                let read = socket.read(&mut buffer).await.unwrap();
+                let request = String::from_utf8_lossy(&buffer[..read]);
+                let current = jwks.lock().unwrap().clone();
+                let (status, body) = match current {
+                    None => ("503 Service Unavailable", String::new()),
+                    Some(_) if request.starts_with("GET /.well-known/openid-configuration ") => {
+                        ("200 OK", metadata.clone())
+                    }
+                    Some(jwks) => ("200 OK", jwks)
+                };
+                let response = format!(
+                    "HTTP/1.1 {}\r\ncontent-type: application/json\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{}",
+                    status,
+                    body.len(),
+                    body
+                );
+                socket.write_all(response.as_bytes()).await.unwrap();
+            }
+        });
+        issuer
+    }
+
+    /// Like [`TokenValidator::load`] without saving a snapshot.
+    async fn discover(issuer: &str, claims: ClaimMapping) -> TokenValidator {
+        let snapshot = Snapshot::fetch(issuer).await.unwrap();
+        TokenValidator::from_snapshot(&snapshot, AUDIENCE, claims).unwrap()
+    }
+
+    fn validator() -> TokenValidator {
+        let jwks = serde_json::from_str(JWKS).unwrap();
+        TokenValidator::from_jwks(ISSUER, AUDIENCE, ClaimMapping::default(), &jwks).unwrap()
+    }
+
+    fn claims() -> Value {
+        let now = Utc::now().timestamp();
+        json!({
+            "iss": ISSUER,
+            "exp": now + 3600,
+            "nbf": now,
+            "iat": now,
+            "aud": AUDIENCE,
+            "oid": "00000000-0000-0000-0000-000000000001",
+            "sub": "00000000-0000-0000-0000-000000000001",
+            "name": "Test User",
+            "emails": ["test@example.com"],
+            "tfp": "B2C_1_signin",
+            "scp": "User",
+            "ver": "1.0"
+        })
+    }
+
+    fn sign(claims: &Value, key: &[u8], kid: &str) -> String {
+        let header = Header {
+            kid: Some(kid.to_string()),
+            ..Header::new(Algorithm::RS256)
+        };
+        encode(&header, claims, &EncodingKey::from_rsa_pem(key).unwrap()).unwrap()
+    }
+
+    fn with(field: &str, value: Value) -> Value {
+        let mut claims = claims();
+        claims[field] = value;
+        claims
+    }
+
+    const READ_RPC: &str = "/subtitles.VideoSubs/GetSubtitles";
+    const WRITE_RPC: &str = "/subtitles.VideoSubs/SetSubtitles";
+    const PUBLIC_RPC: &str = "/user.UserService/GetUserStats";
+
+    fn authenticator() -> Authenticator {
+        authenticator_with(validator())
+    }
+
+    /// Uses a fresh in-memory database, a single connection so it isn't lost between queries.
+    fn authenticator_with(validator: TokenValidator) -> Authenticator {
+        let manager = ConnectionManager::<SqliteConnection>::new(":memory:");
+        let db = Pool::builder().max_size(1).build(manager).unwrap();
+        crate::embedded_migrations::run(&db.get().unwrap()).unwrap();
+        Authenticator::new(Arc::new(validator), db)
+    }
+
+    async fn intercept(path: &str, metadata: &[(&'static str, &[u8])]) -> Result<Request<()>, Status> {
+        intercept_with(&authenticator(), path, metadata).await
+    }
+
+    async fn intercept_with(
+        auth: &Authenticator,
+        path: &str,
+        metadata: &[(&'static str, &[u8])]
+    ) -> Result<Request<()>, Status> {
+        let mut request = Request::new(());
+        for (key, value) in metadata {
+            if key.ends_with("-bin") {
+                request.metadata_mut().insert_bin(*key, MetadataValue::from_bytes(value));
+            } else {
+                request.metadata_mut().insert(*key, std::str::from_utf8(value).unwrap().parse().unwrap());
+            }
+        }
+        authorize(auth, path, request.metadata_mut()).await?;
+        Ok(request)
+    }
+
+    fn bearer(claims: &Value) -> String {
+        format!("Bearer {}", sign(claims, KEY, "test-key"))
+    }
+
+    /// Creates a user with an API token and returns the token.
+    fn api_token(auth: &Authenticator, scopes: &[&str]) -> String {
+        let conn = auth.db.get().unwrap();
+        diesel::insert_into(users::table)
+            .values(&NewUser { id: "script-user", username: "scripter", email: None })
+            .execute(&conn)
+            .unwrap();
+        let scopes: Vec<_> = scopes.iter().map(|scope| scope.to_string()).collect();
+        tokens::create(&conn, "script-user", "CI", &scopes, 30).unwrap().1
+    }
+
+    #[test]
+    fn accepts_valid_token() {
+        let token = sign(&claims(), KEY, "test-key");
+        let principal = validator().validate(&token).unwrap();
+        assert_eq!(principal.subject, "00000000-0000-0000-0000-000000000001");
+        assert_eq!(principal.name, "Test User");
+        assert_eq!(principal.emails, vec!["test@example.com"]);
+    }
+
+    #[test]
+    fn rejects_token_without_subject() {
+        let mut claims = claims();
+        claims.as_object_mut().unwrap().remove("sub");
+        let token = sign(&claims, KEY, "test-key");
+        assert_eq!(
+            validator().validate(&token).unwrap_err(),
+            AuthError::MissingClaim("sub".to_string())
+        );
+    }
+
+    #[tokio::test]
+    async fn maps_claims_from_discovered_issuer() {
+        let issuer = mock_issuer(serving(JWKS)).await;
+        let keycloak = ClaimMapping {
+            name: "preferred_username".to_string(),
+            email: "email".to_string(),
+            scopes: "scope".to_string(),
+            roles: "realm_access.roles".to_string(),
+            full_access: "subunity".to_string(),
+            ..ClaimMapping::default()
+        };
+        let validator = discover(&issuer, keycloak).await;
+
+        let now = Utc::now().timestamp();
+        let mut claims = json!({
+            "iss": issuer,
+            "exp": now + 3600,
+            "nbf": now,
+            "iat": now,
+            "aud": AUDIENCE,
+            "sub": "f5a1c9e2-3b7d-4e0a-9c6f-2d8b1a4e7c30",
+            "preferred_username": "tester",
+            "email": "tester@example.com",
+            "scope": "openid subunity",
+            "realm_access": { "roles": ["moderator"] }
+        });
+        let token = sign(&claims, KEY, "test-key");
+
+        let principal = validator.validate(&token).unwrap();
+        assert_eq!(principal, Principal {
+            subject: "f5a1c9e2-3b7d-4e0a-9c6f-2d8b1a4e7c30".to_string(),
+            name: "tester".to_string(),
+            emails: vec!["tester@example.com".to_string()],
+            scopes: vec!["openid".to_string(), FULL_ACCESS.to_string()],
+            roles: vec!["moderator".to_string()]
+        });
+
+        let auth = authenticator_with(validator);
+        let header = format!("Bearer {}", token);
+        let request = intercept_with(&auth, WRITE_RPC, &[("authorization", header.as_bytes())]).await.unwrap();
+        assert_eq!(Principal::from_request(&request).unwrap(), principal);
+
+        // Only the configured scope grants full access
+        claims["scope"] = json!("openid User");
+        let header = format!("Bearer {}", sign(&claims, KEY, "test-key"));
+        let status = intercept_with(&auth, WRITE_RPC, &[("authorization", header.as_bytes())]).await.unwrap_err();
+        assert_eq!(status.code(), Code::PermissionDenied);
+    }
+
+    #[test]
+    fn accepts_audience_list() {
+        let token = sign(&with("aud", json!(["other", AUDIENCE])), KEY, "test-key");
+        assert!(validator().validate(&token).is_ok());
+    }
+
+    #[test]
+    fn rejects_expired_token() {
+        let expired = Utc::now().timestamp() - 2 * LEEWAY as i64;
+        let token = sign(&with("exp", json!(expired)), KEY, "test-key");
+        assert_eq!(validator().validate(&token).unwrap_err(), AuthError::Expired);
+    }
+
+    #[test]
+    fn rejects_token_from_the_future() {
+        let later = Utc::now().timestamp() + 2 * LEEWAY as i64;
+        let token = sign(&with("nbf", json!(later)), KEY, "test-key");
+        assert_eq!(validator().validate(&token).unwrap_err(), AuthError::NotYetValid);
+    }
+
+    #[test]
+    fn rejects_wrong_signature() {
+        let token = sign(&claims(), OTHER_KEY, "test-key");
+        assert_eq!(validator().validate(&token).unwrap_err(), AuthError::InvalidSignature);
+    }
+
+    #[test]
+    fn rejects_unknown_key() {
+        let token = sign(&claims(), OTHER_KEY, "other-key");
+        assert_eq!(
+            validator().validate(&token).unwrap_err(),
+            AuthError::UnknownKey("other-key".to_string())
+        );
+    }
+
+    #[test]
+    fn rejects_wrong_audience() {
+        let token = sign(&with("aud", json!("someone-else")), KEY, "test-key");
+        assert_eq!(validator().validate(&token).unwrap_err(), AuthError::WrongAudience);
+    }
+
+    #[test]
+    fn rejects_wrong_issuer() {
+        let token = sign(&with("iss", json!("https://evil.example.com/")), KEY, "test-key");
+        assert_eq!(validator().validate(&token).unwrap_err(), AuthError::WrongIssuer);
+    }
+
+    #[tokio::test]
+    async fn refetches_rotated_keys() {
+        // The same key under another id, as if the provider had rotated to a new one since
+        let jwks = serving(&JWKS.replace("test-key", "old-key"));
+        let issuer = mock_issuer(jwks.clone()).await;
+        let validator = discover(&issuer, ClaimMapping::default()).await;
+        let token = sign(&with("iss", json!(issuer)), KEY, "test-key");
+        assert_eq!(validator.validate(&token).unwrap_err(), AuthError::UnknownKey("test-key".to_string()));
+
+        *jwks.lock().unwrap() = Some(JWKS.to_string());
+        assert!(validator.validate_or_refetch(&token).await.is_ok());
+        assert!(validator.validate(&token).is_ok());
+    }
+
+    #[tokio::test]
+    async fn starts_from_snapshot_while_issuer_is_down() {
+        let jwks = serving(JWKS);
+        let issuer = mock_issuer(jwks.clone()).await;
+        let snapshot = std::env::temp_dir().join(format!("jwks-{}.json", Uuid::new_v4()));
+        TokenValidator::load(&issuer, AUDIENCE, ClaimMapping::default(), &snapshot).await.unwrap();
+
+        *jwks.lock().unwrap() = None;
+        let validator = TokenValidator::load(&issuer, AUDIENCE, ClaimMapping::default(), &snapshot).await.unwrap();
+        let token = sign(&with("iss", json!(issuer)), KEY, "test-key");
+        assert!(validator.validate(&token).is_ok());
+
+        // Failed refreshes keep the keys there are
+        assert!(validator.refresh().await.is_err());
+        assert!(validator.validate(&token).is_ok());
+        fs::remove_file(&snapshot).unwrap();
+    }
+
+    #[test]
+    fn rejects_malformed_tokens() {
+        let validator = validator();
+        assert!(matches!(validator.validate("not a token"), Err(AuthError::Malformed(_))));
+        assert!(matches!(bearer_token(b"Basic abc"), Err(AuthError::Malformed(_))));
+        assert!(matches!(bearer_token(&[0xff, 0xfe]), Err(AuthError::Malformed(_))));
+    }
+
+    #[tokio::test]
+    async fn attaches_principal() {
+        let header = bearer(&claims());
+        let request = intercept(READ_RPC, &[("authorization", header.as_bytes())]).await.unwrap();
+        let principal = Principal::from_request(&request).unwrap();
+        assert_eq!(principal.subject, "00000000-0000-0000-0000-000000000001");
+        assert_eq!(principal.scopes, vec!["User"]);
+    }
+
+    #[tokio::test]
+    async fn ignores_principal_sent_by_client() {
+        let spoofed = Principal {
+            subject: "someone-else".to_string(),
+            name: "Mallory".to_string(),
+            emails: Vec::new(),
+            scopes: vec!["User".to_string()],
+            roles: Vec::new()
+        };
+        let json = serde_json::to_vec(&spoofed).unwrap();
+
+        let request = intercept(PUBLIC_RPC, &[(PRINCIPAL_KEY, &json), (LEGACY_PRINCIPAL_KEY, &json)]).await.unwrap();
+        assert_eq!(Principal::from_request(&request).unwrap_err().code(), Code::Unauthenticated);
+        assert!(request.metadata().get_bin(LEGACY_PRINCIPAL_KEY).is_none());
+
+        let status = intercept(READ_RPC, &[(PRINCIPAL_KEY, &json)]).await.unwrap_err();
+        assert_eq!(status.code(), Code::Unauthenticated);
+
+        let header = bearer(&claims());
+        let request = intercept(READ_RPC, &[(PRINCIPAL_KEY, &json), ("authorization", header.as_bytes())]).await.unwrap();
+        assert_eq!(Principal::from_request(&request).unwrap().subject, "00000000-0000-0000-0000-000000000001");
+    }
+
+    #[tokio::test]
+    async fn rejects_invalid_authorization() {
+        let status = intercept(PUBLIC_RPC, &[("authorization", b"Bearer nonsense")]).await.unwrap_err();
+        assert_eq!(status.code(), Code::Unauthenticated);
+    }
+
+    #[tokio::test]
+    async fn allows_anonymous_public_calls() {
+        assert!(intercept(PUBLIC_RPC, &[]).await.is_ok());
+        assert_eq!(intercept(READ_RPC, &[]).await.unwrap_err().code(), Code::Unauthenticated);
+    }
+
+    #[tokio::test]
+    async fn enforces_scopes() {
+        let read_only = bearer(&with("scp", json!("subtitles.read user.read")));
+        assert!(intercept(READ_RPC, &[("authorization", read_only.as_bytes())]).await.is_ok());
+
+        let status = intercept(WRITE_RPC, &[("authorization", read_only.as_bytes())]).await.unwrap_err();
+        assert_eq!(status.code(), Code::PermissionDenied);
+        let info = ErrorInfo::from_status(&status).unwrap();
+        assert_eq!(info.reason, "MISSING_SCOPE");
+        assert_eq!(info.metadata["scope"], SUBTITLES_WRITE);
+
+        let full_access = bearer(&claims());
+        assert!(intercept(WRITE_RPC, &[("authorization", full_access.as_bytes())]).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn rejects_unlisted_rpcs() {
+        let header = bearer(&claims());
+        let status = intercept("/user.UserService/Unknown", &[("authorization", header.as_bytes())]).await.unwrap_err();
+        assert_eq!(status.code(), Code::PermissionDenied);
+    }
+
+    #[tokio::test]
+    async fn accepts_api_tokens() {
+        let auth = authenticator();
+        let header = format!("Bearer {}", api_token(&auth, &[SUBTITLES_READ]));
+        let request = intercept_with(&auth, READ_RPC, &[("authorization", header.as_bytes())]).await.unwrap();
+        let principal = Principal::from_request(&request).unwrap();
+        assert_eq!(principal.subject, "script-user");
+        assert_eq!(principal.name, "scripter");
+
+        let status = intercept_with(&auth, WRITE_RPC, &[("authorization", header.as_bytes())]).await.unwrap_err();
+        assert_eq!(status.code(), Code::PermissionDenied);
+
+        // Not even with every scope a token can have
+        let scopes: Vec<_> = tokens::GRANTABLE_SCOPES.iter().map(|scope| scope.to_string()).collect();
+        let token = tokens::create(&auth.db.get().unwrap(), "script-user", "All", &scopes, 30).unwrap().1;
+        let header = format!("Bearer {}", token);
+        let full_access_rpcs = [
+            "/user.UserService/CreateApiToken",
+            "/user.UserService/ExportMyData",
+            "/user.UserService/DeleteAccount"
+        ];
+        for rpc in &full_access_rpcs {
+            let status = intercept_with(&auth, rpc, &[("authorization", header.as_bytes())]).await.unwrap_err();
+            assert_eq!(status.code(), Code::PermissionDenied, "{}", rpc);
+        }
+    }
+
+    #[tokio::test]
+    async fn rejects_expired_and_revoked_api_tokens() {
+        let auth = authenticator();
+        let header = format!("Bearer {}", api_token(&auth, &[SUBTITLES_READ]));
+        let conn = auth.db.get().unwrap();
+
+        diesel::update(api_tokens::table)
+            .set(api_tokens::expires_at.eq(Utc::now().naive_utc() - Duration::minutes(1)))
+            .execute(&conn)
+            .unwrap();
+        drop(conn);
+        let status = intercept_with(&auth, READ_RPC, &[("authorization", header.as_bytes())]).await.unwrap_err();
+        assert_eq!(ErrorInfo::from_status(&status).unwrap().reason, "TOKEN_EXPIRED");
+
+        let conn = auth.db.get().unwrap();
+        let id = api_tokens::table.select(api_tokens::id).first::<String>(&conn).unwrap();
+        tokens::revoke(&conn, "script-user", &id).unwrap();
+        drop(conn);
+        let status = intercept_with(&auth, READ_RPC, &[("authorization", header.as_bytes())]).await.unwrap_err();
+        assert_eq!(ErrorInfo::from_status(&status).unwrap().reason, "UNKNOWN_TOKEN");
+    }
+
+    #[test]
+    fn reports_reason_in_status() {
+        let token = sign(&claims(), OTHER_KEY, "test-key");
+        let status: Status = validator().validate(&token).unwrap_err().into();
+        assert_eq!(status.code(), Code::Unauthenticated);
+        assert_eq!(ErrorInfo::from_status(&status).unwrap().reason, "INVALID_SIGNATURE");
+    }
+}
@@ -0,0 +1,337 @@
+//! Profile picture processing.
+//!
+//! Uploads are decoded, rotated according to their EXIF orientation, cropped and scaled to each
+//! of [`SIZES`]. Every size is stored as both PNG and WebP. Since only the decoded pixels are
+//! re-encoded, EXIF and any other metadata in the upload are dropped.
+
+use api_types::user::{profile_picture_request::Format, ImageUploadRequest};
+use exif::{In, Tag};
+use image::{
+    imageops::FilterType, io::Reader as ImageReader, DynamicImage, GenericImageView,
+    ImageOutputFormat
+};
+use std::io::Cursor;
+use tonic::Status;
+
+/// Sizes generated for every upload, in pixels. The largest one is served by default.
+pub const SIZES: [u32; 4] = [32, 64, 128, 256];
+/// Uploads above this are rejected before decoding.
+const MAX_UPLOAD_BYTES: usize = 10 * 1024 * 1024;
+/// Limits the decoded size so a small, highly compressed file can't exhaust memory.
+const MAX_DIMENSION: u32 = 8192;
+const WEBP_QUALITY: f32 = 85.;
+
+pub struct Avatar {
+    pub size: u32,
+    pub format: Format,
+    pub content: Vec<u8>
+}
+
+pub fn content_type(format: Format) -> &'static str {
+    match format {
+        Format::Png => "image/png",
+        Format::Webp => "image/webp"
+    }
+}
+
+pub fn extension(format: Format) -> &'static str {
+    match format {
+        Format::Png => "png",
+        Format::Webp => "webp"
+    }
+}
+
+/// The smallest generated size that's at least `size`, so it only ever gets scaled down. 0 is
+/// what an unset size arrives as and gets the largest one.
+pub fn closest_size(size: u32) -> u32 {
+    let largest = SIZES[SIZES.len() - 1];
+    if size == 0 {
+        return largest;
+    }
+    SIZES
+        .iter()
+        .copied()
+        .find(|available| *available >= size)
+        .unwrap_or(largest)
+}
+
+fn decode(content: &[u8]) -> Result<DynamicImage, Status> {
+    if content.len() > MAX_UPLOAD_BYTES {
+        return Err(Status::invalid_argument(format!(
+            "Image is larger than {} MB",
+            MAX_UPLOAD_BYTES / 1024 / 1024
+        )));
+    }
+
+    let invalid = |e: image::ImageError| Status::invalid_argument(e.to_string());
+    // Only reads the header, the pixels aren't decoded until the size is known to be sane
+    let (width, height) = ImageReader::new(Cursor::new(content))
+        .with_guessed_format()
+        .map_err(|e| Status::invalid_argument(e.to_string()))?
+        .into_dimensions()
+        .map_err(invalid)?;
+    if width > MAX_DIMENSION || height > MAX_DIMENSION {
+        return Err(Status::invalid_argument(format!(
+            "Image can't be larger than {}x{} pixels",
+            MAX_DIMENSION, MAX_DIMENSION
+        )));
+    }
+
+    image::load_from_memory(content).map_err(invalid)
+}
+
+/// EXIF orientation, 1 (upright) if the image doesn't have any.
+fn orientation(content: &[u8]) -> u32 {
+    exif::Reader::new()
+        .read_from_container(&mut Cursor::new(content))
+        .ok()
+        .and_then(|exif| {
+            exif.get_field(Tag::Orientation, In::PRIMARY)
+                .and_then(|field| field.value.get_uint(0))
+        })
+        .unwrap_or(1)
+}
+
+/// Rotates the image so it's displayed the same way browsers show the original.
+fn apply_orientation(image: DynamicImage, orientation: u32) -> DynamicImage {
+    match orientation {
+        2 => image.fliph(),
+        3 => image.rotate180(),
+        4 => image.flipv(),
+        5 => image.rotate90().fliph(),
+        6 => image.rotate90(),
+        7 => image.rotate270().fliph(),
+        8 => image.rotate270(),
+        _ => image
+    }
+}
+
+fn encode(image: &DynamicImage, format: Format) -> Result<Vec<u8>, Status> {
+    match format {
+        Format::Png => {
+            let mut content = Vec::new();
+            image
+                .write_to(&mut content, ImageOutputFormat::Png)
+                .map_err(|e| Status::internal(format!("Couldn't encode image: {}", e)))?;
+            Ok(content)
+        }
+        Format::Webp => {
+            let rgba = image.to_rgba8();
+            let encoded = webp::Encoder::from_rgba(&rgba, rgba.width(), rgba.height())
+                .encode(WEBP_QUALITY);
+            Ok(encoded.to_vec())
+        }
+    }
+}
+
+/// Crops the upload to the selected square and renders every size in every format.
+pub fn process(request: &ImageUploadRequest) -> Result<Vec<Avatar>, Status> {
+    let image = apply_orientation(decode(&request.content)?, orientation(&request.content));
+
+    let (width, height) = image.dimensions();
+    let crop_size = request.crop_size;
+    let in_bounds = |offset: u32, bound: u32| {
+        offset.checked_add(crop_size).map_or(false, |end| end <= bound)
+    };
+    if crop_size == 0 || !in_bounds(request.offset_x, width) || !in_bounds(request.offset_y, height) {
+        return Err(Status::invalid_argument(format!(
+            "Crop of {}px at ({}, {}) is outside the {}x{} image",
+            crop_size, request.offset_x, request.offset_y, width, height
+        )));
+    }
+
+    let cropped = image.crop_imm(request.offset_x, request.offset_y, crop_size, crop_size);
+
+    let mut avatars = Vec::with_capacity(SIZES.len() * 2);
+    for &size in SIZES.iter() {
+        let resized = cropped.resize_exact(size, size, FilterType::Lanczos3);
+        for &format in [Format::Png, Format::Webp].iter() {
+            avatars.push(Avatar {
+                size,
+                format,
+                content: encode(&resized, format)?
+            });
+        }
+    }
+    Ok(avatars)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{Rgb, RgbImage};
+    use tonic::Code;
+
+    /// A PNG whose pixels encode their own coordinates, so moves can be traced.
+    fn png(width: u32, height: u32) -> Vec<u8> {
+        let image = RgbImage::from_fn(width, height, |x, y| Rgb([x as u8, y as u8, 0]));
+        let mut content = Vec::new();
+        DynamicImage::ImageRgb8(image).write_to(&mut content, ImageOutputFormat::Png).unwrap();
+        content
+    }
+
+    fn crc32(bytes: &[u8]) -> u32 {
+        let mut crc = !0u32;
+        for byte in bytes {
+            crc ^= *byte as u32;
+            for _ in 0..8 {
+                crc = if crc & 1 == 1 { (crc >> 1) ^ 0xedb8_8320 } else { crc >> 1 };
+            }
+        }
+        !crc
+    }
+
+    /// Inserts an `eXIf` chunk with just an orientation after the header of a PNG.
+    fn with_orientation(png: Vec<u8>, orientation: u16) -> Vec<u8> {
+        let mut exif = b"MM\0\x2a\0\0\0\x08\0\x01\x01\x12\0\x03\0\0\0\x01".to_vec();
+        exif.extend_from_slice(&orientation.to_be_bytes());
+        exif.extend_from_slice(&[0; 6]);
+
+        let mut chunk = (exif.len() as u32).to_be_bytes().to_vec();
+        chunk.extend_from_slice(b"eXIf");
+        chunk.extend_from_slice(&exif);
+        chunk.extend_from_slice(&crc32(&chunk[4..]).to_be_bytes());
+
+        // Signature and IHDR chunk
+        let header_length = 8 + 25;
+        let mut content = png[..header_length].to_vec();
+        content.extend_from_slice(&chunk);
+        content.extend_from_slice(&png[header_length..]);
+        content
+    }
+
+    fn upload(content: Vec<u8>, crop_size: u32, offset_x: u32, offset_y: u32) -> ImageUploadRequest {
+        ImageUploadRequest {
+            crop_size,
+            offset_x,
+            offset_y,
+            content,
+            ..Default::default()
+        }
+    }
+
+    fn rejection(request: &ImageUploadRequest) -> String {
+        let status = process(request).err().expect("Upload should be rejected");
+        assert_eq!(status.code(), Code::InvalidArgument);
+        status.message().to_string()
+    }
+
+    #[test]
+    fn picks_closest_size() {
+        assert_eq!(closest_size(0), 256);
+        assert_eq!(closest_size(1), 32);
+        assert_eq!(closest_size(64), 64);
+        assert_eq!(closest_size(100), 128);
+        assert_eq!(closest_size(1024), 256);
+    }
+
+    #[test]
+    fn renders_every_size_and_format() {
+        let avatars = process(&upload(png(40, 30), 20, 10, 5)).unwrap();
+        assert_eq!(avatars.len(), 8);
+        for (avatar, (size, format)) in avatars.iter().zip(SIZES.iter().flat_map(|size| {
+            vec![(*size, Format::Png), (*size, Format::Webp)]
+        })) {
+            assert_eq!((avatar.size, avatar.format), (size, format));
+            match format {
+                Format::Png => {
+                    let image = image::load_from_memory(&avatar.content).unwrap();
+                    assert_eq!(image.dimensions(), (size, size));
+                }
+                Format::Webp => {
+                    assert_eq!(&avatar.content[..4], b"RIFF");
+                    assert_eq!(&avatar.content[8..12], b"WEBP");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn rejects_crops_outside_the_image() {
+        let image = png(40, 30);
+        // Exactly the whole height is fine
+        assert!(process(&upload(image.clone(), 30, 10, 0)).is_ok());
+
+        for &(crop_size, offset_x, offset_y) in [
+            (0, 0, 0),
+            (31, 0, 0),
+            (20, 21, 0),
+            (20, 0, 11),
+            (20, u32::MAX, 0),
+            (20, 0, u32::MAX),
+            (u32::MAX, 1, 1)
+        ].iter() {
+            let message = rejection(&upload(image.clone(), crop_size, offset_x, offset_y));
+            assert!(message.starts_with("Crop of"), "{}", message);
+        }
+    }
+
+    #[test]
+    fn rejects_oversized_uploads() {
+        let message = rejection(&upload(vec![0; MAX_UPLOAD_BYTES + 1], 1, 0, 0));
+        assert_eq!(message, "Image is larger than 10 MB");
+
+        for &(width, height) in [(MAX_DIMENSION + 1, 1), (1, MAX_DIMENSION + 1)].iter() {
+            let message = rejection(&upload(png(width, height), 1, 0, 0));
+            assert_eq!(message, "Image can't be larger than 8192x8192 pixels");
+        }
+
+        // Only the header is read, the missing pixel data would fail decoding with another error.
+        // The decoder looks at the next chunk's header before it reports dimensions.
+        let mut header_only = png(MAX_DIMENSION + 1, 1);
+        header_only.truncate(8 + 25 + 8);
+        let message = rejection(&upload(header_only, 1, 0, 0));
+        assert_eq!(message, "Image can't be larger than 8192x8192 pixels");
+
+        assert!(!rejection(&upload(b"not an image".to_vec(), 1, 0, 0)).is_empty());
+    }
+
+    #[test]
+    fn reads_orientation() {
+        assert_eq!(orientation(&png(2, 2)), 1);
+        for value in 1..=8 {
+            assert_eq!(orientation(&with_orientation(png(2, 2), value)), value as u32);
+        }
+    }
+
+    /// Where the pixel from `from` in a 3x2 image ends up after correcting `orientation`.
+    fn moved(orientation: u32, from: (u32, u32)) -> (u32, u32) {
+        let image = DynamicImage::ImageRgb8(RgbImage::from_fn(3, 2, |x, y| Rgb([x as u8, y as u8, 1])));
+        let corrected = apply_orientation(image, orientation).to_rgb8();
+        let (x, y) = corrected.enumerate_pixels()
+            .find(|(_, _, pixel)| pixel.0 == [from.0 as u8, from.1 as u8, 1])
+            .map(|(x, y, _)| (x, y))
+            .unwrap();
+        (x, y)
+    }
+
+    #[test]
+    fn corrects_orientation() {
+        // The top right corner and the pixel below the top left one of a 3x2 image
+        let expected = [
+            (1, (2, 0), (0, 1)),
+            (2, (0, 0), (2, 1)),
+            (3, (0, 1), (2, 0)),
+            (4, (2, 1), (0, 0)),
+            // Transpose, mirrored along the top left to bottom right diagonal
+            (5, (0, 2), (1, 0)),
+            (6, (1, 2), (0, 0)),
+            // Transverse, mirrored along the other diagonal
+            (7, (1, 0), (0, 2)),
+            (8, (0, 0), (1, 2))
+        ];
+        for &(orientation, top_right, below_top_left) in expected.iter() {
+            assert_eq!(moved(orientation, (2, 0)), top_right, "orientation {}", orientation);
+            assert_eq!(moved(orientation, (0, 1)), below_top_left, "orientation {}", orientation);
+        }
+    }
+
+    #[test]
+    fn applies_orientation_before_cropping() {
+        // Rotated a quarter turn the 40x30 image is 30x40, so this crop only fits afterwards
+        let rotated = with_orientation(png(40, 30), 6);
+        assert!(process(&upload(rotated, 30, 0, 10)).is_ok());
+        assert!(process(&upload(png(40, 30), 30, 0, 10)).is_err());
+    }
+}
@@ -1,6 +1,10 @@
 use super::schema::users;
 use super::schema::subtitles;
 use super::schema::changes;
+use super::schema::videos;
+use super::schema::video_metadata;
+use super::schema::user_languages;
+use super::schema::api_tokens;
 use chrono::NaiveDateTime;
 
 #[derive(Queryable, Debug)]
@@ -8,7 +12,9 @@ pub struct User {
     pub id: String,
     pub username: String,
     pub email: Option<String>,
-    pub picture: Option<String>
+    pub picture: Option<String>,
+    pub display_name: String,
+    pub bio: String
 }
 
 #[derive(Insertable)]
@@ -19,6 +25,29 @@ pub struct NewUser<'a> {
     pub email: Option<&'a str>
 }
 
+#[derive(Queryable, Insertable, Debug)]
+#[table_name = "user_languages"]
+pub struct UserLanguage {
+    pub user_id: String,
+    pub language: String,
+    pub can_caption: bool,
+    pub can_translate: bool
+}
+
+#[derive(Queryable, Insertable, Debug)]
+#[table_name = "api_tokens"]
+pub struct ApiToken {
+    pub id: String,
+    pub user_id: String,
+    pub name: String,
+    pub token_hash: String,
+    /// Space separated, like OAuth scopes
+    pub scopes: String,
+    pub created_at: NaiveDateTime,
+    pub expires_at: NaiveDateTime,
+    pub last_used_at: Option<NaiveDateTime>
+}
+
 #[derive(Queryable, Debug)]
 #[derive(Identifiable)]
 #[primary_key(video_id, language)]
@@ -26,7 +55,8 @@ pub struct NewUser<'a> {
 pub struct Subtitles {
     pub video_id: String,
     pub language: String,
-    pub subs_json: String
+    pub subs_json: String,
+    pub speakers_json: String
 }
 
 #[derive(Insertable)]
@@ -37,12 +67,18 @@ pub struct NewSubtitles<'a> {
     pub subs_json: &'a str
 }
 
+/// A change as it's read back, load it by selecting [`Change::COLUMNS`].
 #[derive(Queryable, Debug)]
 pub struct Change {
-    id: u32,
-    time_stamp: NaiveDateTime,
-    author: String,
-    changes_json: String
+    pub timestamp: NaiveDateTime,
+    pub changes_json: String,
+    pub video_id: String,
+    pub language: String
+}
+
+impl Change {
+    pub const COLUMNS: (changes::timestamp, changes::changes_json, changes::video_id, changes::language) =
+        (changes::timestamp, changes::changes_json, changes::video_id, changes::language);
 }
 
 #[derive(Insertable)]
@@ -50,5 +86,47 @@ pub struct Change {
 pub struct NewChange<'a> {
     pub timestamp: &'a NaiveDateTime,
     pub author: &'a str,
-    pub changes_json: &'a str
+    pub changes_json: &'a str,
+    pub video_id: &'a str,
+    pub language: &'a str
+}
+
+#[derive(Queryable, Debug)]
+#[derive(Identifiable)]
+#[table_name = "videos"]
+pub struct Video {
+    pub id: String,
+    pub title: String,
+    pub channel_id: String,
+    pub channel_title: String,
+    pub duration: String,
+    pub thumbnails_json: String,
+    pub fetched_at: NaiveDateTime,
+    pub source: String,
+    pub source_id: String
+}
+
+#[derive(Insertable)]
+#[table_name = "videos"]
+pub struct NewVideo<'a> {
+    pub id: &'a str,
+    pub title: &'a str,
+    pub channel_id: &'a str,
+    pub channel_title: &'a str,
+    pub duration: &'a str,
+    pub thumbnails_json: &'a str,
+    pub fetched_at: &'a NaiveDateTime,
+    pub source: &'a str,
+    pub source_id: &'a str
+}
+
+#[derive(Queryable, Insertable, Debug)]
+#[table_name = "video_metadata"]
+pub struct VideoMetadata {
+    pub id: String,
+    pub title: String,
+    pub channel_id: String,
+    pub channel_title: String,
+    pub duration: String,
+    pub thumbnails_json: String
 }
\ No newline at end of file
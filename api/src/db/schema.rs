@@ -1,9 +1,24 @@
+table! {
+    api_tokens (id) {
+        id -> Text,
+        user_id -> Text,
+        name -> Text,
+        token_hash -> Text,
+        scopes -> Text,
+        created_at -> Timestamp,
+        expires_at -> Timestamp,
+        last_used_at -> Nullable<Timestamp>,
+    }
+}
+
 table! {
     changes (id) {
         id -> Nullable<Integer>,
         timestamp -> Timestamp,
         author -> Text,
         changes_json -> Text,
+        video_id -> Text,
+        language -> Text,
     }
 }
 
@@ -12,6 +27,7 @@ table! {
         video_id -> Text,
         language -> Text,
         subs_json -> Text,
+        speakers_json -> Text,
     }
 }
 
@@ -21,11 +37,51 @@ table! {
         username -> Text,
         email -> Nullable<Text>,
         picture -> Nullable<Text>,
+        display_name -> Text,
+        bio -> Text,
+    }
+}
+
+table! {
+    user_languages (user_id, language) {
+        user_id -> Text,
+        language -> Text,
+        can_caption -> Bool,
+        can_translate -> Bool,
+    }
+}
+
+table! {
+    video_metadata (id) {
+        id -> Text,
+        title -> Text,
+        channel_id -> Text,
+        channel_title -> Text,
+        duration -> Text,
+        thumbnails_json -> Text,
+    }
+}
+
+table! {
+    videos (id) {
+        id -> Text,
+        title -> Text,
+        channel_id -> Text,
+        channel_title -> Text,
+        duration -> Text,
+        thumbnails_json -> Text,
+        fetched_at -> Timestamp,
+        source -> Text,
+        source_id -> Text,
     }
 }
 
 allow_tables_to_appear_in_same_query!(
+    api_tokens,
     changes,
     subtitles,
+    user_languages,
     users,
+    video_metadata,
+    videos,
 );
@@ -0,0 +1,241 @@
+use api_types::subtitles::{
+    download_request::Format,
+    subtitles::{Alignment, Entry, Position, Speaker, Word}
+};
+use std::collections::HashMap;
+use std::fmt::Write;
+use subparse::{
+    timetypes::{TimePoint, TimeSpan},
+    SrtFile, SubtitleFileInterface
+};
+use tonic::Status;
+
+const ASS_HEADER: &str = "[Script Info]
+ScriptType: v4.00+
+WrapStyle: 0
+ScaledBorderAndShadow: yes
+
+[V4+ Styles]
+Format: Name, Fontname, Fontsize, PrimaryColour, SecondaryColour, OutlineColour, BackColour, Bold, Italic, Underline, StrikeOut, ScaleX, ScaleY, Spacing, Angle, BorderStyle, Outline, Shadow, Alignment, MarginL, MarginR, MarginV, Encoding
+Style: Default,Arial,20,&H00FFFFFF,&H000000FF,&H00000000,&H00000000,0,0,0,0,100,100,0,0,1,2,2,2,10,10,10,1
+
+[Events]
+Format: Layer, Start, End, Style, Name, MarginL, MarginR, MarginV, Effect, Text
+";
+
+/// Speaker names by id.
+type Speakers<'a> = HashMap<&'a str, &'a str>;
+
+pub fn export(format: Format, entries: Vec<Entry>, speakers: &[Speaker]) -> Result<Vec<u8>, Status> {
+    let speakers: Speakers = speakers.iter()
+        .map(|speaker| (&*speaker.id, &*speaker.name))
+        .collect();
+    match format {
+        Format::Srt => srt(entries, &speakers),
+        Format::Vtt => Ok(vtt(&entries, &speakers).into_bytes()),
+        Format::Ass => Ok(ass(&entries, &speakers).into_bytes())
+    }
+}
+
+fn speaker_name<'a>(entry: &Entry, speakers: &Speakers<'a>) -> Option<&'a str> {
+    speakers.get(&*entry.speaker).copied()
+}
+
+fn srt(entries: Vec<Entry>, speakers: &Speakers) -> Result<Vec<u8>, Status> {
+    let entries = entries.into_iter()
+        .map(|entry| {
+            let start = TimePoint::from_msecs((entry.start_seconds * 1000.) as i64);
+            let end = TimePoint::from_msecs((entry.end_seconds * 1000.) as i64);
+            let span = TimeSpan::new(start, end);
+            let text = match speaker_name(&entry, speakers) {
+                Some(name) => format!("{}: {}", name, entry.text),
+                None => entry.text
+            };
+            (span, text)
+        })
+        .collect();
+    SrtFile::create(entries)
+        .and_then(|srt| srt.to_data())
+        .map_err(|e| Status::internal(format!("Couldn't create SRT file: {}", e)))
+}
+
+fn millis(seconds: f32) -> u64 {
+    (seconds.max(0.) * 1000.).round() as u64
+}
+
+/// `HH:MM:SS.mmm`
+fn vtt_timestamp(seconds: f32) -> String {
+    let ms = millis(seconds);
+    format!("{:02}:{:02}:{:02}.{:03}", ms / 3_600_000, ms / 60_000 % 60, ms / 1000 % 60, ms % 1000)
+}
+
+fn vtt_escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+}
+
+/// Cue settings for anything but the default bottom center placement.
+fn vtt_settings(entry: &Entry) -> String {
+    let mut settings = String::new();
+    if entry.position() == Position::Top {
+        settings.push_str(" line:0");
+    }
+    match entry.alignment() {
+        Alignment::Center => {}
+        Alignment::Left => settings.push_str(" align:left"),
+        Alignment::Right => settings.push_str(" align:right")
+    }
+    settings
+}
+
+fn vtt(entries: &[Entry], speakers: &Speakers) -> String {
+    let mut out = String::from("WEBVTT\n");
+    for entry in entries {
+        write!(
+            out,
+            "\n{} --> {}{}\n",
+            vtt_timestamp(entry.start_seconds),
+            vtt_timestamp(entry.end_seconds),
+            vtt_settings(entry)
+        ).unwrap();
+
+        if let Some(name) = speaker_name(entry, speakers) {
+            write!(out, "<v {}>", vtt_escape(name)).unwrap();
+        }
+
+        if entry.words.is_empty() {
+            out.push_str(&vtt_escape(&entry.text));
+        } else {
+            // Karaoke style timestamp tags in front of every word after the cue start
+            for word in &entry.words {
+                if word.start_seconds > entry.start_seconds {
+                    write!(out, "<{}>", vtt_timestamp(word.start_seconds)).unwrap();
+                }
+                out.push_str(&vtt_escape(&word.text));
+            }
+        }
+        out.push('\n');
+    }
+    out
+}
+
+/// `H:MM:SS.cc`
+fn ass_timestamp(seconds: f32) -> String {
+    let cs = millis(seconds) / 10;
+    format!("{}:{:02}:{:02}.{:02}", cs / 360_000, cs / 6000 % 60, cs / 100 % 60, cs % 100)
+}
+
+fn ass_escape(text: &str) -> String {
+    text.replace('{', "(").replace('}', ")").replace('\n', "\\N")
+}
+
+/// Renders words as `{\kNN}` syllables, with the duration in centiseconds until the next word.
+fn ass_karaoke(entry: &Entry) -> String {
+    let centis = |from: f32, to: f32| ((to - from).max(0.) * 100.).round() as u32;
+    let ends = entry.words.iter()
+        .skip(1)
+        .map(|word| word.start_seconds)
+        .chain(Some(entry.end_seconds));
+
+    let mut out = String::new();
+    if let Some(Word { start_seconds, .. }) = entry.words.first() {
+        if *start_seconds > entry.start_seconds {
+            write!(out, "{{\\k{}}}", centis(entry.start_seconds, *start_seconds)).unwrap();
+        }
+    }
+    for (word, end) in entry.words.iter().zip(ends) {
+        write!(out, "{{\\k{}}}{}", centis(word.start_seconds, end), ass_escape(&word.text)).unwrap();
+    }
+    out
+}
+
+/// `{\anN}` override using numpad positions, empty for the default bottom center.
+fn ass_position(entry: &Entry) -> String {
+    let column = match entry.alignment() {
+        Alignment::Left => 1,
+        Alignment::Center => 2,
+        Alignment::Right => 3
+    };
+    let row = match entry.position() {
+        Position::Bottom => 0,
+        Position::Top => 6
+    };
+    match row + column {
+        2 => String::new(),
+        an => format!("{{\\an{}}}", an)
+    }
+}
+
+fn ass(entries: &[Entry], speakers: &Speakers) -> String {
+    let mut out = String::from(ASS_HEADER);
+    for entry in entries {
+        let text = if entry.words.is_empty() {
+            ass_escape(&entry.text)
+        } else {
+            ass_karaoke(entry)
+        };
+        // Commas separate the fields before `Text`
+        let name = speaker_name(entry, speakers).unwrap_or_default().replace(',', " ");
+        writeln!(
+            out,
+            "Dialogue: 0,{},{},Default,{},0,0,0,,{}{}",
+            ass_timestamp(entry.start_seconds),
+            ass_timestamp(entry.end_seconds),
+            name,
+            ass_position(entry),
+            text
+        ).unwrap();
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(start_seconds: f32, end_seconds: f32, words: &[(f32, &str)]) -> Entry {
+        Entry {
+            start_seconds,
+            end_seconds,
+            text: words.iter().map(|(_, text)| *text).collect(),
+            words: words.iter()
+                .map(|(start_seconds, text)| Word { start_seconds: *start_seconds, text: text.to_string() })
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn formats_timestamps() {
+        assert_eq!(vtt_timestamp(0.), "00:00:00.000");
+        assert_eq!(vtt_timestamp(3723.4567), "01:02:03.457");
+        assert_eq!(vtt_timestamp(-1.), "00:00:00.000");
+        assert_eq!(ass_timestamp(3723.456), "1:02:03.45");
+    }
+
+    #[test]
+    fn tags_words_after_the_cue_start() {
+        let entries = [entry(1., 3., &[(1., "Hello"), (1.5, " there")])];
+        assert_eq!(vtt(&entries, &Speakers::new()), "WEBVTT\n\n00:00:01.000 --> 00:00:03.000\nHello<00:00:01.500> there\n");
+    }
+
+    #[test]
+    fn times_syllables_until_the_next_word() {
+        let karaoke = ass_karaoke(&entry(1., 3., &[(1.25, "Hel"), (1.5, "lo"), (2., " world")]));
+        assert_eq!(karaoke, "{\\k25}{\\k25}Hel{\\k50}lo{\\k100} world");
+    }
+
+    #[test]
+    fn escapes_speakers_and_places_cues() {
+        let mut entry = entry(0., 1., &[]);
+        entry.text = "a < b".to_string();
+        entry.speaker = "s".to_string();
+        entry.set_position(Position::Top);
+        entry.set_alignment(Alignment::Right);
+        let speakers: Speakers = vec![("s", "Tom & Jerry")].into_iter().collect();
+
+        let vtt = vtt(&[entry.clone()], &speakers);
+        assert!(vtt.ends_with(" line:0 align:right\n<v Tom &amp; Jerry>a &lt; b\n"), "{}", vtt);
+        let ass = ass(&[entry], &speakers);
+        assert!(ass.ends_with("Dialogue: 0,0:00:00.00,0:00:01.00,Default,Tom & Jerry,0,0,0,,{\\an9}a < b\n"), "{}", ass);
+    }
+}
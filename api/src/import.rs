@@ -0,0 +1,424 @@
+//! Parsing of subtitle files into entries, the reverse of [`crate::export`]. Word timings are
+//! kept from WebVTT timestamp tags and ASS `\k` tags, speakers from WebVTT voice tags and the
+//! ASS name field.
+
+use api_types::{
+    errors::ErrorInfo,
+    subtitles::{
+        download_request::Format,
+        subtitles::{Alignment, Entry, Position, Speaker, Word}
+    }
+};
+use tonic::{Code, Status};
+
+pub fn import(format: Format, content: &[u8]) -> Result<(Vec<Entry>, Vec<Speaker>), Status> {
+    let content = std::str::from_utf8(content)
+        .map_err(|_| invalid_file(0, "isn't UTF-8"))?
+        .trim_start_matches('\u{feff}')
+        .replace("\r\n", "\n");
+    let mut speakers = Speakers::default();
+    let entries = match format {
+        Format::Srt => srt(&content)?,
+        Format::Vtt => vtt(&content, &mut speakers)?,
+        Format::Ass => ass(&content, &mut speakers)?
+    };
+    Ok((entries, speakers.0))
+}
+
+/// `line` is 1-based, 0 if the whole file is affected.
+fn invalid_file(line: usize, message: &str) -> Status {
+    let status_message = match line {
+        0 => format!("File {}", message),
+        line => format!("Line {}: {}", line, message)
+    };
+    ErrorInfo::new("subtitles", "INVALID_FILE")
+        .with_metadata("line", line.to_string())
+        .into_status(Code::InvalidArgument, status_message)
+}
+
+/// Speakers by name, numbered in order of appearance.
+#[derive(Default)]
+struct Speakers(Vec<Speaker>);
+
+impl Speakers {
+    fn id(&mut self, name: &str) -> String {
+        let name = name.trim();
+        if name.is_empty() {
+            return String::new();
+        }
+        if let Some(speaker) = self.0.iter().find(|speaker| speaker.name == name) {
+            return speaker.id.clone();
+        }
+        let id = format!("speaker-{}", self.0.len() + 1);
+        self.0.push(Speaker { id: id.clone(), name: name.to_string(), color: String::new() });
+        id
+    }
+}
+
+/// Blocks separated by blank lines, with the number of their first line.
+fn blocks(content: &str) -> Vec<(usize, Vec<&str>)> {
+    let mut blocks = Vec::new();
+    let mut current: Option<(usize, Vec<&str>)> = None;
+    for (i, line) in content.lines().enumerate() {
+        if line.trim().is_empty() {
+            blocks.extend(current.take());
+        } else {
+            current.get_or_insert_with(|| (i + 1, Vec::new())).1.push(line);
+        }
+    }
+    blocks.extend(current);
+    blocks
+}
+
+/// `[H:]MM:SS` followed by a fraction after `separator`, e.g. `01:02:03.456` or `1:02:03.45`.
+fn timestamp(text: &str, separator: char) -> Option<f32> {
+    let mut parts = text.trim().rsplitn(2, separator);
+    let fraction = parts.next()?;
+    let clock = parts.next()?;
+    if fraction.is_empty() || !fraction.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let fraction = fraction.parse::<f32>().ok()? / 10f32.powi(fraction.len() as i32);
+
+    let mut seconds = 0.;
+    let mut fields = 0;
+    for field in clock.split(':') {
+        if field.is_empty() || !field.bytes().all(|b| b.is_ascii_digit()) {
+            return None;
+        }
+        seconds = seconds * 60. + field.parse::<f32>().ok()?;
+        fields += 1;
+    }
+    Some(seconds + fraction).filter(|_| fields == 2 || fields == 3)
+}
+
+/// `start --> end` and whatever follows it, like WebVTT cue settings.
+fn timing(line: &str, separator: char) -> Option<(f32, f32, &str)> {
+    let (start, rest) = split_once(line, "-->")?;
+    let rest = rest.trim_start();
+    let end_length = rest.find(char::is_whitespace).unwrap_or(rest.len());
+    Some((
+        timestamp(start, separator)?,
+        timestamp(&rest[..end_length], separator)?,
+        &rest[end_length..]
+    ))
+}
+
+fn split_once<'a>(text: &'a str, separator: &str) -> Option<(&'a str, &'a str)> {
+    let index = text.find(separator)?;
+    Some((&text[..index], &text[index + separator.len()..]))
+}
+
+fn srt(content: &str) -> Result<Vec<Entry>, Status> {
+    blocks(content)
+        .into_iter()
+        .map(|(line, lines)| {
+            // The counter is optional in practice
+            let timing_index = lines.iter().position(|line| line.contains("-->"))
+                .ok_or_else(|| invalid_file(line, "has no timing"))?;
+            let (start_seconds, end_seconds, _) = timing(lines[timing_index], ',')
+                .ok_or_else(|| invalid_file(line + timing_index, "has an invalid timing"))?;
+            Ok(Entry {
+                start_seconds,
+                end_seconds,
+                text: lines[timing_index + 1..].join("\n"),
+                ..Default::default()
+            })
+        })
+        .collect()
+}
+
+fn vtt_unescape(text: &str) -> String {
+    text.replace("&lt;", "<").replace("&gt;", ">").replace("&nbsp;", "\u{a0}").replace("&amp;", "&")
+}
+
+/// Cue settings [`crate::export`] writes, others are ignored.
+fn vtt_settings(entry: &mut Entry, settings: &str) {
+    for setting in settings.split_whitespace() {
+        match setting {
+            "line:0" | "line:0%" => entry.set_position(Position::Top),
+            "align:left" | "align:start" => entry.set_alignment(Alignment::Left),
+            "align:right" | "align:end" => entry.set_alignment(Alignment::Right),
+            _ => {}
+        }
+    }
+}
+
+/// Splits the cue text at timestamp tags into words, dropping other tags. The voice tag's name
+/// is returned as the speaker.
+fn vtt_payload(entry: &mut Entry, payload: &str, speakers: &mut Speakers) {
+    let mut words = vec![Word { start_seconds: entry.start_seconds, text: String::new() }];
+    let mut rest = payload;
+    while let Some(open) = rest.find('<') {
+        words.last_mut().unwrap().text.push_str(&vtt_unescape(&rest[..open]));
+        let close = match rest[open..].find('>') {
+            Some(close) => open + close,
+            None => {
+                rest = &rest[open..];
+                break;
+            }
+        };
+        let tag = &rest[open + 1..close];
+        if let Some(start_seconds) = timestamp(tag, '.') {
+            words.push(Word { start_seconds, text: String::new() });
+        } else if let Some(name) = tag.strip_prefix("v ") {
+            entry.speaker = speakers.id(name);
+        } else if let Some(classes_and_name) = tag.strip_prefix("v.") {
+            // `<v.class Name>` has the classes before the name
+            entry.speaker = speakers.id(classes_and_name.splitn(2, ' ').nth(1).unwrap_or_default());
+        }
+        rest = &rest[close + 1..];
+    }
+    words.last_mut().unwrap().text.push_str(&vtt_unescape(rest));
+
+    words.retain(|word| !word.text.is_empty());
+    entry.text = words.iter().map(|word| &*word.text).collect();
+    if words.len() > 1 || words.iter().any(|word| word.start_seconds > entry.start_seconds) {
+        entry.words = words;
+    }
+}
+
+fn vtt(content: &str, speakers: &mut Speakers) -> Result<Vec<Entry>, Status> {
+    if !content.starts_with("WEBVTT") {
+        return Err(invalid_file(1, "doesn't start with WEBVTT"));
+    }
+    let mut entries = Vec::new();
+    // The first block is the header
+    for (line, lines) in blocks(content).into_iter().skip(1) {
+        let timing_index = match lines.iter().position(|line| line.contains("-->")) {
+            Some(index) => index,
+            // NOTE, STYLE and REGION blocks
+            None => continue
+        };
+        let (start_seconds, end_seconds, settings) = timing(lines[timing_index], '.')
+            .ok_or_else(|| invalid_file(line + timing_index, "has an invalid timing"))?;
+        let mut entry = Entry { start_seconds, end_seconds, ..Default::default() };
+        vtt_settings(&mut entry, settings);
+        vtt_payload(&mut entry, &lines[timing_index + 1..].join("\n"), speakers);
+        entries.push(entry);
+    }
+    Ok(entries)
+}
+
+/// `{\anN}` numpad positions, the middle row counts as bottom.
+fn ass_position(entry: &mut Entry, an: u32) {
+    if !(1..=9).contains(&an) {
+        return;
+    }
+    entry.set_alignment(match (an - 1) % 3 {
+        0 => Alignment::Left,
+        1 => Alignment::Center,
+        _ => Alignment::Right
+    });
+    if an >= 7 {
+        entry.set_position(Position::Top);
+    }
+}
+
+/// Applies the override tags of a `{...}` block, returns the karaoke duration if it has one.
+fn ass_overrides(entry: &mut Entry, block: &str) -> Option<f32> {
+    let mut karaoke = None;
+    for tag in block.split('\\').filter(|tag| !tag.is_empty()) {
+        let digits = |prefix: &str| tag.strip_prefix(prefix)
+            .filter(|value| !value.is_empty() && value.bytes().all(|b| b.is_ascii_digit()))
+            .and_then(|value| value.parse::<u32>().ok());
+        if let Some(an) = digits("an") {
+            ass_position(entry, an);
+        } else if let Some(centis) = digits("kf").or_else(|| digits("ko")).or_else(|| digits("k")).or_else(|| digits("K")) {
+            karaoke = Some(centis as f32 / 100.);
+        }
+    }
+    karaoke
+}
+
+fn ass_unescape(text: &str) -> String {
+    text.replace("\\N", "\n").replace("\\n", "\n").replace("\\h", "\u{a0}")
+}
+
+/// Splits the text at karaoke tags into words timed by the tags' durations.
+fn ass_text(entry: &mut Entry, text: &str) {
+    let mut words = Vec::new();
+    let mut plain = String::new();
+    let mut time = entry.start_seconds;
+    let mut karaoke = false;
+    let mut rest = text;
+    loop {
+        let (segment, block, after) = match rest.find('{') {
+            Some(open) => match rest[open..].find('}') {
+                Some(close) => (&rest[..open], Some(&rest[open + 1..open + close]), &rest[open + close + 1..]),
+                None => (rest, None, "")
+            },
+            None => (rest, None, "")
+        };
+        let segment = ass_unescape(segment);
+        plain.push_str(&segment);
+        if karaoke && !segment.is_empty() {
+            if let Some(Word { text, .. }) = words.last_mut().filter(|word: &&mut Word| word.text.is_empty()) {
+                *text = segment;
+            } else {
+                words.push(Word { start_seconds: time, text: segment });
+            }
+        }
+
+        let block = match block {
+            Some(block) => block,
+            None => break
+        };
+        rest = after;
+        if let Some(duration) = ass_overrides(entry, block) {
+            // The syllable after the tag starts now and lasts `duration`
+            karaoke = true;
+            words.retain(|word| !word.text.is_empty());
+            words.push(Word { start_seconds: time, text: String::new() });
+            time += duration;
+        }
+    }
+
+    words.retain(|word| !word.text.is_empty());
+    entry.text = plain;
+    entry.words = words;
+}
+
+fn ass(content: &str, speakers: &mut Speakers) -> Result<Vec<Entry>, Status> {
+    let mut format: Vec<String> = ["layer", "start", "end", "style", "name", "marginl", "marginr", "marginv", "effect", "text"]
+        .iter()
+        .map(|field| field.to_string())
+        .collect();
+    let mut in_events = false;
+    let mut entries = Vec::new();
+
+    for (i, line) in content.lines().enumerate() {
+        let line = line.trim_start();
+        if line.starts_with('[') {
+            in_events = line.eq_ignore_ascii_case("[events]");
+            continue;
+        }
+        if !in_events {
+            continue;
+        }
+        if let Some(fields) = line.strip_prefix("Format:") {
+            format = fields.split(',').map(|field| field.trim().to_lowercase()).collect();
+            continue;
+        }
+        let dialogue = match line.strip_prefix("Dialogue:") {
+            Some(dialogue) => dialogue,
+            None => continue
+        };
+
+        // Only the last field, the text, may contain commas
+        let values: Vec<_> = dialogue.splitn(format.len(), ',').collect();
+        let field = |name: &str| format.iter()
+            .position(|field| field == name)
+            .and_then(|index| values.get(index))
+            .map(|value| value.trim_start());
+        let time = |name: &str| field(name).and_then(|value| timestamp(value, '.'))
+            .ok_or_else(|| invalid_file(i + 1, &format!("has an invalid {} time", name)));
+
+        let mut entry = Entry {
+            start_seconds: time("start")?,
+            end_seconds: time("end")?,
+            speaker: speakers.id(field("name").unwrap_or_default()),
+            ..Default::default()
+        };
+        ass_text(&mut entry, field("text").unwrap_or_default());
+        entries.push(entry);
+    }
+    Ok(entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::export::export;
+
+    fn assert_close(actual: f32, expected: f32) {
+        assert!((actual - expected).abs() < 0.006, "{} != {}", actual, expected);
+    }
+
+    fn karaoke_entry() -> Entry {
+        let word = |start_seconds, text: &str| Word { start_seconds, text: text.to_string() };
+        Entry {
+            start_seconds: 1.,
+            end_seconds: 3.5,
+            text: "Hello there world".to_string(),
+            words: vec![word(1.2, "Hello"), word(1.8, " there"), word(2.75, " world")],
+            speaker: "a".to_string(),
+            position: Position::Top as i32,
+            alignment: Alignment::Left as i32,
+            ..Default::default()
+        }
+    }
+
+    fn speakers() -> Vec<Speaker> {
+        vec![Speaker { id: "a".to_string(), name: "Alice".to_string(), color: "#ff0000".to_string() }]
+    }
+
+    fn assert_round_trip(format: Format) {
+        let original = vec![
+            karaoke_entry(),
+            Entry { start_seconds: 4., end_seconds: 5., text: "Plain".to_string(), ..Default::default() }
+        ];
+        let file = export(format, original.clone(), &speakers()).unwrap();
+        let (entries, speakers) = import(format, &file).unwrap();
+
+        assert_eq!(speakers.len(), 1);
+        assert_eq!(speakers[0].name, "Alice");
+        assert_eq!(entries.len(), 2);
+        for (entry, original) in entries.iter().zip(&original) {
+            assert_close(entry.start_seconds, original.start_seconds);
+            assert_close(entry.end_seconds, original.end_seconds);
+            assert_eq!(entry.text, original.text);
+            assert_eq!(entry.position, original.position);
+            assert_eq!(entry.alignment, original.alignment);
+            assert_eq!(entry.words.len(), original.words.len());
+            for (word, original) in entry.words.iter().zip(&original.words) {
+                assert_close(word.start_seconds, original.start_seconds);
+                assert_eq!(word.text, original.text);
+            }
+        }
+        assert_eq!(entries[0].speaker, speakers[0].id);
+        assert!(entries[1].speaker.is_empty());
+    }
+
+    #[test]
+    fn keeps_words_through_vtt() {
+        assert_round_trip(Format::Vtt);
+    }
+
+    #[test]
+    fn keeps_words_through_ass() {
+        assert_round_trip(Format::Ass);
+    }
+
+    #[test]
+    fn imports_srt() {
+        let srt = "1\r\n00:00:01,000 --> 00:00:02,500\r\nFirst line\r\nsecond line\r\n\r\n2\r\n00:01:02,030 --> 00:01:04,000\r\nNext\r\n";
+        let (entries, speakers) = import(Format::Srt, srt.as_bytes()).unwrap();
+        assert!(speakers.is_empty());
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].text, "First line\nsecond line");
+        assert_close(entries[0].end_seconds, 2.5);
+        assert_close(entries[1].start_seconds, 62.03);
+    }
+
+    #[test]
+    fn reads_vtt_without_hours_and_skips_notes() {
+        let vtt = "WEBVTT - Title\n\nNOTE written by hand\n\nintro\n01:02.500 --> 01:04.000 position:10%\n<i>Tom &amp; Jerry</i>\n";
+        let (entries, _) = import(Format::Vtt, vtt.as_bytes()).unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_close(entries[0].start_seconds, 62.5);
+        assert_eq!(entries[0].text, "Tom & Jerry");
+        assert!(entries[0].words.is_empty());
+        assert_eq!(entries[0].position(), Position::Bottom);
+    }
+
+    #[test]
+    fn rejects_invalid_files() {
+        let status = import(Format::Vtt, b"WEBVTT\n\n00:01.000 --> later\nText\n").unwrap_err();
+        assert_eq!(status.code(), Code::InvalidArgument);
+        assert_eq!(ErrorInfo::from_status(&status).unwrap().metadata["line"], "3");
+
+        assert!(import(Format::Vtt, b"1\n00:00:01,000 --> 00:00:02,000\nText\n").is_err());
+        assert!(import(Format::Srt, &[0xff, 0xfe, 0x00]).is_err());
+    }
+}
@@ -6,8 +6,11 @@ extern crate diesel;
 extern crate diesel_migrations;
 
 use crate::{
-    settings::{Authentication, Settings},
-    user::UserService
+    auth::{Authenticator, Authorized, TokenValidator},
+    settings::{Authentication, KeyCache, Settings},
+    storage::BlobStore,
+    user::UserService,
+    videos::VideoInfoProvider
 };
 use api_types::user::user_service_server::UserServiceServer;
 use config::Config;
@@ -16,18 +19,26 @@ use diesel::{
     result::Error as DieselError,
     SqliteConnection
 };
-use openid::{biscuit::Url, Client, CompactJson, Discovered, Jws, SingleOrMultiple, Userinfo};
 use r2d2::PooledConnection;
-use serde::{Deserialize, Serialize};
-use std::{env, error::Error, sync::Arc};
-use tonic::{metadata::MetadataValue, transport::Server, Request, Status};
+use std::{env, error::Error, path::Path, sync::Arc, time::Duration};
+use tonic::{transport::Server, Status};
 use api_types::subtitles::video_subs_server::VideoSubsServer;
 use crate::subtitles::VideoSubService;
 
+mod account;
+mod auth;
+mod avatars;
 mod db;
+mod export;
+mod import;
 mod settings;
+mod stats;
+mod storage;
 mod user;
 mod subtitles;
+mod timedtext;
+mod tokens;
+mod videos;
 mod youtube_caption_scraper;
 
 trait IntoStatus<T> {
@@ -52,6 +63,8 @@ impl<T> IntoStatus<T> for Result<T, r2d2::Error> {
 
 pub struct State {
     db: Database,
+    videos: Box<dyn VideoInfoProvider>,
+    storage: Box<dyn BlobStore>,
     #[allow(dead_code)]
     conf: Settings
 }
@@ -60,21 +73,6 @@ impl State {
     pub fn db(&self) -> Result<DbConnection, Status> {
         self.db.get().into_status()
     }
-
-    #[cfg(feature = "cloud-storage")]
-    pub fn blob(&self) -> azure_sdk_storage_core::key_client::KeyClient {
-        use azure_sdk_storage_core::client as blob_client;
-        use settings::Storage;
-
-        let Settings {
-            storage: Storage {
-                blob_account,
-                blob_key
-            },
-            ..
-        } = &self.conf;
-        blob_client::with_access_key(blob_account, blob_key)
-    }
 }
 
 type Database = Pool<ConnectionManager<SqliteConnection>>;
@@ -93,25 +91,22 @@ async fn main() -> Result<(), Box<dyn Error>> {
         .merge(config::Environment::with_prefix("APP"))?;
     let settings: Settings = config.try_into()?;
 
-    let auth = {
+    let validator = {
         let Settings {
             authentication:
                 Authentication {
                     client_id,
-                    client_secret,
                     issuer,
+                    claims,
+                    keys: KeyCache { snapshot, refresh_minutes },
                     ..
                 },
             ..
         } = &settings;
-        let client = Client::discover(
-            client_id.to_string(),
-            client_secret.to_string(),
-            None,
-            issuer.parse()?
-        ).await.unwrap();
-
-        Arc::new(client)
+        let validator = TokenValidator::load(issuer, client_id, claims.clone(), Path::new(snapshot)).await?;
+        let validator = Arc::new(validator);
+        validator.clone().refresh_periodically(Duration::from_secs(refresh_minutes * 60));
+        validator
     };
 
     let database_url = env::var("DATABASE_URL").expect("DATABASE_URL must be set");
@@ -123,14 +118,18 @@ async fn main() -> Result<(), Box<dyn Error>> {
         embedded_migrations::run(&conn).unwrap();
     }
 
+    let auth = Arc::new(Authenticator::new(validator, pool.clone()));
+    let videos = videos::provider(&settings.videos, &pool)?;
     let state = Arc::new(State {
         db: pool,
+        videos,
+        storage: storage::blob_store(&settings.storage)?,
         conf: settings
     });
 
     let addr = "[::1]:50051".parse().unwrap();
-    let user = UserServiceServer::with_interceptor(UserService(state.clone()), authorize(auth.clone()));
-    let subtitles = VideoSubsServer::with_interceptor(VideoSubService(state.clone()), authorize(auth.clone()));
+    let user = Authorized::new(UserServiceServer::new(UserService(state.clone())), auth.clone());
+    let subtitles = Authorized::new(VideoSubsServer::new(VideoSubService(state.clone())), auth.clone());
     println!("Server listening on {}", addr);
     Server::builder()
         .add_service(user)
@@ -138,125 +137,3 @@ async fn main() -> Result<(), Box<dyn Error>> {
         .serve(addr).await?;
     Ok(())
 }
-
-#[derive(Serialize, Deserialize, Debug)]
-struct Claims {
-    iss: Url,
-    exp: i64,
-    nbf: i64,
-    aud: SingleOrMultiple<String>,
-    oid: String,
-    sub: String,
-    name: String,
-    #[serde(default)]
-    emails: Vec<String>,
-    tfp: String,
-    scp: String,
-    #[serde(default)]
-    azp: Option<String>,
-    ver: String,
-    iat: i64
-}
-
-const USER_INFO: Userinfo = Userinfo {
-    sub: None,
-    name: None,
-    given_name: None,
-    family_name: None,
-    middle_name: None,
-    nickname: None,
-    preferred_username: None,
-    profile: None,
-    picture: None,
-    website: None,
-    email: None,
-    email_verified: false,
-    gender: None,
-    birthdate: None,
-    zoneinfo: None,
-    locale: None,
-    phone_number: None,
-    phone_number_verified: false,
-    address: None,
-    updated_at: None
-};
-
-impl openid::Claims for Claims {
-    fn iss(&self) -> &Url {
-        &self.iss
-    }
-
-    fn sub(&self) -> &str {
-        &self.sub
-    }
-
-    fn aud(&self) -> &SingleOrMultiple<String> {
-        &self.aud
-    }
-
-    fn exp(&self) -> i64 {
-        self.exp
-    }
-
-    fn iat(&self) -> i64 {
-        self.iat
-    }
-
-    fn auth_time(&self) -> Option<i64> {
-        None
-    }
-
-    fn nonce(&self) -> Option<&String> {
-        None
-    }
-
-    fn at_hash(&self) -> Option<&String> {
-        None
-    }
-
-    fn c_hash(&self) -> Option<&String> {
-        None
-    }
-
-    fn acr(&self) -> Option<&String> {
-        None
-    }
-
-    fn amr(&self) -> Option<&Vec<String>> {
-        None
-    }
-
-    fn azp(&self) -> Option<&String> {
-        self.azp.as_ref()
-    }
-
-    fn userinfo(&self) -> &Userinfo {
-        &USER_INFO
-    }
-}
-impl CompactJson for Claims {}
-
-fn authorize(
-    client: Arc<Client<Discovered, Claims>>
-) -> impl Fn(Request<()>) -> Result<Request<()>, Status> {
-    move |mut req| {
-        if let Some(token) = req.metadata().get("authorization") {
-            let token = token
-                .to_str()
-                .map_err(|_| Status::aborted("bad authorization header"))?
-                .replacen("Bearer ", "", 1);
-
-            let mut token = Jws::new_encoded(&token);
-            client.decode_token(&mut token).unwrap();
-            client.validate_token(&mut token, None, None).unwrap();
-            let token = token.payload().unwrap();
-            let json = serde_json::to_string(&token).unwrap();
-            let meta = MetadataValue::from_str(&json).unwrap();
-            req.metadata_mut().insert("user", meta);
-
-            Ok(req)
-        } else {
-            Ok(req)
-        }
-    }
-}
@@ -3,7 +3,10 @@ use serde::Deserialize;
 #[derive(Default, Deserialize)]
 pub struct Settings {
     pub authentication: Authentication,
-    pub storage: Storage
+    #[serde(default)]
+    pub storage: Storage,
+    #[serde(default)]
+    pub videos: Videos
 }
 
 #[derive(Default, Deserialize)]
@@ -15,11 +18,134 @@ pub struct Authentication {
 
     pub signin_policy: String,
     pub edit_profile_policy: String,
-    pub reset_password_policy: String
+    pub reset_password_policy: String,
+
+    #[serde(default)]
+    pub claims: ClaimMapping,
+    #[serde(default)]
+    pub keys: KeyCache
 }
 
-#[derive(Default, Deserialize)]
+/// How the provider's signing keys are kept up to date.
+#[derive(Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct KeyCache {
+    /// Where the last fetched keys are saved, used when the provider can't be reached on startup
+    pub snapshot: String,
+    /// How often to refetch the keys, so rotated ones are picked up, 0 to only refetch on
+    /// unknown keys
+    pub refresh_minutes: u64
+}
+
+impl Default for KeyCache {
+    fn default() -> Self {
+        KeyCache {
+            snapshot: "jwks_snapshot.json".to_string(),
+            refresh_minutes: 60
+        }
+    }
+}
+
+/// Which token claims hold what, so providers other than Azure AD B2C can be used. Nested
+/// claims can be addressed with dots, e.g. `realm_access.roles` for Keycloak.
+#[derive(Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct ClaimMapping {
+    pub subject: String,
+    pub name: String,
+    /// A single address or a list of them
+    pub email: String,
+    /// Space separated string or list
+    pub scopes: String,
+    pub roles: String,
+    /// The scope the web frontend requests, which grants everything a user can do
+    pub full_access: String
+}
+
+impl Default for ClaimMapping {
+    fn default() -> Self {
+        ClaimMapping {
+            subject: "sub".to_string(),
+            name: "name".to_string(),
+            email: "emails".to_string(),
+            scopes: "scp".to_string(),
+            roles: "roles".to_string(),
+            full_access: "User".to_string()
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(default)]
 pub struct Storage {
+    pub backend: StorageBackend,
+    /// Directory for the local backend
+    pub path: String,
+    /// Azure container or S3 bucket
+    pub container: String,
+
     pub blob_account: String,
-    pub blob_key: String
+    pub blob_key: String,
+
+    /// S3 service root, e.g. `http://localhost:9000` for MinIO
+    pub endpoint: String,
+    pub region: String,
+    pub access_key: String,
+    pub secret_key: String
+}
+
+impl Default for Storage {
+    fn default() -> Self {
+        Storage {
+            backend: StorageBackend::Local,
+            path: "../target/user_profiles".to_string(),
+            container: "profile-pictures".to_string(),
+            blob_account: String::new(),
+            blob_key: String::new(),
+            endpoint: String::new(),
+            region: String::new(),
+            access_key: String::new(),
+            secret_key: String::new()
+        }
+    }
+}
+
+#[derive(Deserialize, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum StorageBackend {
+    Local,
+    S3,
+    Azure
+}
+
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct Videos {
+    pub provider: VideoProvider,
+    /// YouTube Data API key, falls back to the `GOOGLE_API_KEY` environment variable
+    pub api_key: String,
+    /// YouTube Data API root, can point to a local stand-in
+    pub base_url: String,
+    /// Metadata file for the local provider
+    pub path: String
+}
+
+impl Default for Videos {
+    fn default() -> Self {
+        Videos {
+            provider: VideoProvider::YouTube,
+            api_key: String::new(),
+            base_url: "https://www.googleapis.com/youtube/v3".to_string(),
+            path: String::new()
+        }
+    }
+}
+
+#[derive(Deserialize, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum VideoProvider {
+    YouTube,
+    Local,
+    /// The `video_metadata` table
+    Database
 }
@@ -0,0 +1,47 @@
+//! Contribution statistics computed from the `changes` log.
+
+use crate::{db::models::Change, DbConnection, IntoStatus};
+use api_types::user::UserStats;
+use diesel::{ExpressionMethods, QueryDsl, RunQueryDsl};
+use std::collections::{BTreeSet, HashSet};
+use tonic::Status;
+
+/// Number of entries a change touched. Each change is a list of per-entry differences.
+fn entries_changed(change: &Change) -> u32 {
+    serde_json::from_str::<Vec<serde_json::Value>>(&change.changes_json)
+        .map(|diff| diff.len() as u32)
+        .unwrap_or(0)
+}
+
+/// Aggregates everything `author` has changed. The user itself isn't filled in.
+pub fn user_stats(conn: &DbConnection, user_id: &str) -> Result<UserStats, Status> {
+    use crate::db::schema::changes::dsl::*;
+
+    let user_changes = changes
+        .filter(author.eq(user_id))
+        .select(Change::COLUMNS)
+        .load::<Change>(conn)
+        .into_status()?;
+
+    // Changes from before tracks were recorded have an empty video id and language
+    let tracks: HashSet<_> = user_changes.iter()
+        .filter(|change| !change.video_id.is_empty())
+        .map(|change| (&*change.video_id, &*change.language))
+        .collect();
+    let languages: BTreeSet<_> = user_changes.iter()
+        .filter(|change| !change.language.is_empty())
+        .map(|change| change.language.clone())
+        .collect();
+
+    Ok(UserStats {
+        user: None,
+        edits: user_changes.len() as u32,
+        tracks_touched: tracks.len() as u32,
+        entries_edited: user_changes.iter().map(entries_changed).sum(),
+        languages: languages.into_iter().collect(),
+        last_active: user_changes.iter()
+            .map(|change| change.timestamp.and_utc().timestamp())
+            .max()
+            .unwrap_or(0)
+    })
+}
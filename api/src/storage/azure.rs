@@ -0,0 +1,77 @@
+use super::{BlobStore, StorageError};
+use async_trait::async_trait;
+use azure_sdk_core::errors::AzureError;
+use azure_sdk_storage_blob::prelude::*;
+use azure_sdk_storage_core::{client, key_client::KeyClient};
+
+/// Stores blobs in an Azure storage account container.
+pub struct AzureStore {
+    client: KeyClient,
+    container: String
+}
+
+impl AzureStore {
+    pub fn new(account: &str, key: &str, container: &str) -> Self {
+        Self {
+            client: client::with_access_key(account, key),
+            container: container.to_string()
+        }
+    }
+}
+
+fn azure_error(err: AzureError) -> StorageError {
+    match &err {
+        AzureError::UnexpectedHTTPResult(result) => {
+            StorageError::Backend(result.status_code().as_u16(), err.to_string())
+        }
+        _ => StorageError::Backend(0, err.to_string())
+    }
+}
+
+fn is_not_found(err: &AzureError) -> bool {
+    matches!(err, AzureError::UnexpectedHTTPResult(result) if result.status_code().as_u16() == 404)
+}
+
+#[async_trait]
+impl BlobStore for AzureStore {
+    async fn put(&self, key: &str, data: Vec<u8>, content_type: &str) -> Result<(), StorageError> {
+        self.client
+            .put_block_blob()
+            .with_container_name(&self.container)
+            .with_blob_name(key)
+            .with_content_type(content_type)
+            .with_body(&data)
+            .finalize()
+            .await
+            .map_err(azure_error)?;
+        Ok(())
+    }
+
+    async fn get(&self, key: &str) -> Result<Option<Vec<u8>>, StorageError> {
+        let result = self.client
+            .get_blob()
+            .with_container_name(&self.container)
+            .with_blob_name(key)
+            .finalize()
+            .await;
+        match result {
+            Ok(response) => Ok(Some(response.data)),
+            Err(err) if is_not_found(&err) => Ok(None),
+            Err(err) => Err(azure_error(err))
+        }
+    }
+
+    async fn delete(&self, key: &str) -> Result<(), StorageError> {
+        let result = self.client
+            .delete_blob()
+            .with_container_name(&self.container)
+            .with_blob_name(key)
+            .with_delete_snapshots_method(DeleteSnapshotsMethod::Include)
+            .finalize()
+            .await;
+        match result {
+            Err(err) if !is_not_found(&err) => Err(azure_error(err)),
+            _ => Ok(())
+        }
+    }
+}
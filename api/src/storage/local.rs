@@ -0,0 +1,51 @@
+use super::{BlobStore, StorageError};
+use async_trait::async_trait;
+use std::{
+    io::{self, ErrorKind},
+    path::{Path, PathBuf}
+};
+use tokio::fs;
+
+/// Stores blobs as files in a directory.
+pub struct LocalStore {
+    root: PathBuf
+}
+
+impl LocalStore {
+    pub fn new(root: impl AsRef<Path>) -> io::Result<Self> {
+        let root = root.as_ref().to_path_buf();
+        std::fs::create_dir_all(&root)?;
+        Ok(Self { root })
+    }
+
+    /// Keys are generated by us, but make sure they can't escape the directory anyway.
+    fn path(&self, key: &str) -> io::Result<PathBuf> {
+        if key.is_empty() || key.contains(|c| c == '/' || c == '\\') || key.starts_with('.') {
+            return Err(io::Error::new(ErrorKind::InvalidInput, format!("invalid blob key {}", key)));
+        }
+        Ok(self.root.join(key))
+    }
+}
+
+#[async_trait]
+impl BlobStore for LocalStore {
+    async fn put(&self, key: &str, data: Vec<u8>, _content_type: &str) -> Result<(), StorageError> {
+        fs::write(self.path(key)?, data).await?;
+        Ok(())
+    }
+
+    async fn get(&self, key: &str) -> Result<Option<Vec<u8>>, StorageError> {
+        match fs::read(self.path(key)?).await {
+            Ok(data) => Ok(Some(data)),
+            Err(err) if err.kind() == ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(err.into())
+        }
+    }
+
+    async fn delete(&self, key: &str) -> Result<(), StorageError> {
+        match fs::remove_file(self.path(key)?).await {
+            Err(err) if err.kind() != ErrorKind::NotFound => Err(err.into()),
+            _ => Ok(())
+        }
+    }
+}
@@ -0,0 +1,97 @@
+//! Blob storage for user uploads, e.g. profile pictures.
+//!
+//! The backend is picked at runtime from [`Storage::backend`]: a local directory, an
+//! S3-compatible bucket or Azure blob storage (which needs the `cloud-storage` feature).
+
+use crate::settings::{Storage, StorageBackend};
+use async_trait::async_trait;
+use std::{
+    error::Error,
+    fmt::{self, Display},
+    io
+};
+use tonic::Status;
+
+#[cfg(feature = "cloud-storage")]
+mod azure;
+mod local;
+mod s3;
+
+#[cfg(feature = "cloud-storage")]
+pub use azure::AzureStore;
+pub use local::LocalStore;
+pub use s3::S3Store;
+
+#[derive(Debug)]
+pub enum StorageError {
+    Io(io::Error),
+    Network(reqwest::Error),
+    /// The backend rejected the request, with its status and response
+    Backend(u16, String)
+}
+
+impl Display for StorageError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StorageError::Io(err) => write!(f, "storage IO error: {}", err),
+            StorageError::Network(err) => write!(f, "couldn't reach storage: {}", err),
+            StorageError::Backend(status, body) => write!(f, "storage returned {}: {}", status, body)
+        }
+    }
+}
+
+impl Error for StorageError {}
+
+impl From<io::Error> for StorageError {
+    fn from(err: io::Error) -> Self {
+        StorageError::Io(err)
+    }
+}
+
+impl From<reqwest::Error> for StorageError {
+    fn from(err: reqwest::Error) -> Self {
+        StorageError::Network(err)
+    }
+}
+
+impl From<StorageError> for Status {
+    fn from(err: StorageError) -> Self {
+        match err {
+            StorageError::Network(_) => Status::unavailable(err.to_string()),
+            _ => Status::internal(err.to_string())
+        }
+    }
+}
+
+#[async_trait]
+pub trait BlobStore: Send + Sync {
+    async fn put(&self, key: &str, data: Vec<u8>, content_type: &str) -> Result<(), StorageError>;
+
+    /// `None` if there's no blob with this key.
+    async fn get(&self, key: &str) -> Result<Option<Vec<u8>>, StorageError>;
+
+    /// Deleting a blob that doesn't exist isn't an error.
+    async fn delete(&self, key: &str) -> Result<(), StorageError>;
+}
+
+/// Builds the store from settings, failing on startup if it's misconfigured.
+pub fn blob_store(settings: &Storage) -> Result<Box<dyn BlobStore>, Box<dyn Error>> {
+    match settings.backend {
+        StorageBackend::Local => Ok(Box::new(LocalStore::new(&settings.path)?)),
+        StorageBackend::S3 => Ok(Box::new(S3Store::new(
+            &settings.endpoint,
+            &settings.region,
+            &settings.container,
+            &settings.access_key,
+            &settings.secret_key
+        )?)),
+        #[cfg(feature = "cloud-storage")]
+        StorageBackend::Azure => Ok(Box::new(AzureStore::new(
+            &settings.blob_account,
+            &settings.blob_key,
+            &settings.container
+        ))),
+        #[cfg(not(feature = "cloud-storage"))]
+        StorageBackend::Azure => Err("Azure storage needs the cloud-storage feature".into())
+    }
+}
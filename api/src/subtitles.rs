@@ -1,13 +1,15 @@
 use api_types::subtitles::video_subs_server::VideoSubs;
-use tonic::{Status, Response, Request};
-use api_types::subtitles::{Subtitles, SetSubtitleResponse, SubtitleId, DownloadRequest, Chunk};
+use tonic::{Code, Status, Response, Request};
+use api_types::subtitles::{Subtitles, SetSubtitleResponse, SubtitleId, DownloadRequest, ImportRequest, Chunk, VideoId, SourceCaptionList, Video, AddVideoRequest};
+use api_types::subtitles::video::Source;
 use diesel::{RunQueryDsl, QueryDsl};
-use crate::{State, IntoStatus, DbConnection, youtube_caption_scraper};
+use crate::{State, IntoStatus, DbConnection, export, import, videos, youtube_caption_scraper};
 use std::ops::Deref;
 use crate::db::models::{NewSubtitles, NewChange};
 use async_trait::async_trait;
 use crate::db::models;
-use api_types::subtitles::subtitles::Entry;
+use api_types::subtitles::subtitles::{Entry, Speaker};
+use api_types::errors::ErrorInfo;
 use chrono::Utc;
 use crate::user::get_user;
 use serde::{Serialize, Deserialize};
@@ -16,9 +18,8 @@ use std::sync::Arc;
 use futures::Stream;
 use std::pin::Pin;
 use api_types::subtitles::download_request::Format;
-use subparse::{SrtFile, SubtitleFileInterface};
-use subparse::timetypes::{TimePoint, TimeSpan};
 use std::io::{Cursor, Read};
+use std::collections::HashSet;
 use prost::bytes::Buf;
 
 pub struct VideoSubService(pub Arc<State>);
@@ -51,85 +52,121 @@ fn diff(old: &[Entry], new: &[Entry]) -> Vec<Difference> {
         .collect()
 }
 
-async fn init_subtitles(conn: DbConnection, video_id: &str, language: &str) -> Result<models::Subtitles, Status> {
-    use crate::db::schema::subtitles;
+/// Videos with an hour or more need timestamps with an hour part.
+const LONG_VIDEO_SECONDS: f32 = 3600.;
+/// Durations are rounded to whole seconds, so allow entries to end slightly after them.
+const DURATION_TOLERANCE: f32 = 1.;
 
-    let generated_subs = youtube_caption_scraper::get_subtitles(video_id, language).await;
-    if let Some(generated_subs) = generated_subs {
-        let json = serde_json::to_string(&generated_subs.entries).unwrap();
-        let new = NewSubtitles {
-            video_id: &generated_subs.video_id,
-            language: &generated_subs.language,
-            subs_json: &json
-        };
-        diesel::insert_into(subtitles::table)
-            .values(&new)
-            .execute(&conn)
-            .into_status()?;
-
-        Ok(subtitles::table.find((video_id, language))
-            .load::<models::Subtitles>(&conn)
-            .into_status()?
-            .pop().unwrap())
-    } else {
-        let json = serde_json::to_string::<[Entry]>(&[]).unwrap();
-        Ok(models::Subtitles {
-            video_id: video_id.to_string(),
-            language: language.to_string(),
-            subs_json: json
-        })
+fn out_of_range(index: usize, message: &str) -> Status {
+    ErrorInfo::new("subtitles", "OUT_OF_RANGE")
+        .with_metadata("index", index.to_string())
+        .into_status(Code::InvalidArgument, format!("Entry {}: {}", index + 1, message))
+}
+
+/// Entries have to start at or after 0, can't end before they start and have to end before the
+/// video does if its duration is known.
+fn validate_times(entries: &[Entry], duration: Option<f32>) -> Result<(), Status> {
+    for (i, entry) in entries.iter().enumerate() {
+        if !entry.start_seconds.is_finite() || !entry.end_seconds.is_finite() || entry.start_seconds < 0. {
+            return Err(out_of_range(i, "starts before the video"));
+        }
+        if entry.end_seconds < entry.start_seconds {
+            return Err(out_of_range(i, "ends before it starts"));
+        }
+        if let Some(duration) = duration {
+            if entry.end_seconds > duration + DURATION_TOLERANCE {
+                return Err(out_of_range(i, "ends after the video"));
+            }
+        }
     }
+    Ok(())
 }
 
-async fn get_or_init_subtitles(conn: DbConnection, video_id: &str, language: &str) -> Result<models::Subtitles, Status> {
-    use crate::db::schema::subtitles;
+/// Fraction of the video that has an entry showing, overlapping entries are counted once.
+fn coverage(entries: &[Entry], duration: f32) -> f32 {
+    let mut spans: Vec<_> = entries.iter()
+        .map(|entry| (entry.start_seconds.max(0.), entry.end_seconds.min(duration)))
+        .filter(|(start, end)| end > start)
+        .collect();
+    spans.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
 
-    let existing: Option<models::Subtitles> = subtitles::table.find((video_id, language))
-        .load::<models::Subtitles>(&conn)
-        .into_status()?
-        .pop();
+    let mut covered = 0.;
+    let mut covered_until = 0f32;
+    for (start, end) in spans {
+        if end > covered_until {
+            covered += end - start.max(covered_until);
+            covered_until = end;
+        }
+    }
+    covered / duration
+}
 
-    if let Some(existing) = existing {
-        Ok(existing)
-    } else {
-        init_subtitles(conn, video_id, language).await
+/// Speakers need a unique id and a name, and entries may only reference registered speakers.
+fn validate_speakers(speakers: &[Speaker], entries: &[Entry]) -> Result<(), Status> {
+    let mut ids = HashSet::new();
+    for speaker in speakers {
+        if speaker.id.is_empty() || speaker.name.trim().is_empty() {
+            return Err(Status::invalid_argument("Speakers need an id and a name"));
+        }
+        if !ids.insert(&*speaker.id) {
+            return Err(Status::invalid_argument(format!("Duplicate speaker id {}", speaker.id)));
+        }
+    }
+    match entries.iter().find(|entry| !entry.speaker.is_empty() && !ids.contains(&*entry.speaker)) {
+        Some(entry) => Err(Status::invalid_argument(format!("Unknown speaker {}", entry.speaker))),
+        None => Ok(())
     }
 }
 
-#[derive(Serialize, Deserialize)]
-struct VideoInfoResponse {
-    pub items: Vec<VideoInfo>
+fn find_subtitles(conn: &DbConnection, video_id: &str, language: &str) -> Result<Option<models::Subtitles>, Status> {
+    use crate::db::schema::subtitles;
+
+    Ok(subtitles::table.find((video_id, language))
+        .load::<models::Subtitles>(conn)
+        .into_status()?
+        .pop())
 }
 
-#[derive(Serialize, Deserialize)]
-pub struct VideoInfo {
-    pub snippet: Snippet
+fn insert_subtitles(conn: &DbConnection, video_id: &str, language: &str, entries: &[Entry]) -> Result<models::Subtitles, Status> {
+    use crate::db::schema::subtitles;
+
+    let json = serde_json::to_string(entries).unwrap();
+    let new = NewSubtitles {
+        video_id,
+        language,
+        subs_json: &json
+    };
+    diesel::insert_into(subtitles::table)
+        .values(&new)
+        .execute(conn)
+        .into_status()?;
+
+    find_subtitles(conn, video_id, language)?
+        .ok_or_else(|| Status::internal("Subtitles weren't saved"))
 }
 
-#[derive(Serialize, Deserialize)]
-pub struct Snippet {
-    pub title: String,
-    #[serde(rename = "channelId")]
-    pub channel_id: String,
-    #[serde(rename = "channelTitle")]
-    pub channel_title: String
+/// Only YouTube videos have existing captions to start from. Ids that aren't registered yet are
+/// YouTube ids.
+fn has_source_captions(conn: &DbConnection, video_id: &str) -> Result<bool, Status> {
+    Ok(videos::find_video(conn, video_id)?
+        .map_or(true, |video| videos::source_of(&video) == Source::YouTube))
+}
+
+async fn init_subtitles(conn: DbConnection, video_id: &str, language: &str, source: Option<&str>) -> Result<models::Subtitles, Status> {
+    let entries = if has_source_captions(&conn, video_id)? {
+        youtube_caption_scraper::get_subtitles(video_id, language, source).await?
+    } else {
+        Vec::new()
+    };
+    insert_subtitles(&conn, video_id, language, &entries)
 }
 
-pub async fn get_video_info(id: &str) -> Result<VideoInfo, Status> {
-    let key = std::env::var("GOOGLE_API_KEY")
-        .expect("Missing google API key");
-    let client = reqwest::Client::new();
-
-    let mut info = client.get("https://www.googleapis.com/youtube/v3/videos")
-        .query(&[("part", "snippet"), ("id", id), ("key", &key)])
-        .send()
-        .await
-        .map_err(|e| Status::internal(format!("{}", e)))?
-        .json::<VideoInfoResponse>()
-        .await
-        .map_err(|e| Status::internal(format!("{}", e)))?;
-
-    info.items.pop().ok_or_else(|| Status::not_found("Video not found"))
+async fn get_or_init_subtitles(conn: DbConnection, video_id: &str, language: &str, source: Option<&str>) -> Result<models::Subtitles, Status> {
+    if let Some(existing) = find_subtitles(&conn, video_id, language)? {
+        Ok(existing)
+    } else {
+        init_subtitles(conn, video_id, language, source).await
+    }
 }
 
 #[async_trait]
@@ -141,7 +178,25 @@ impl VideoSubs for VideoSubService {
         let user = get_user(&request, &conn)?;
 
         let req = request.into_inner();
-        let existing = get_or_init_subtitles(conn, &req.video_id, &req.language).await?;
+        validate_speakers(&req.speakers, &req.entries)?;
+        // The duration only bounds the times, saving mustn't depend on the metadata provider
+        let duration = match videos::get_video(&self.0, &req.video_id).await {
+            Ok(video) => videos::duration_seconds(&video),
+            Err(status) => {
+                eprintln!("Saving {}/{} without its duration: {}", req.video_id, req.language, status.message());
+                None
+            }
+        };
+        validate_times(&req.entries, duration)?;
+        let existing = match get_or_init_subtitles(conn, &req.video_id, &req.language, None).await {
+            Ok(existing) => existing,
+            // Saving must still work when there's nothing to seed the track from
+            Err(status) if ErrorInfo::from_status(&status).is_some() => {
+                eprintln!("Starting {}/{} empty: {}", req.video_id, req.language, status.message());
+                insert_subtitles(&self.db()?, &req.video_id, &req.language, &[])?
+            }
+            Err(status) => return Err(status)
+        };
         let conn = self.db()?;
 
         let existing_subs = serde_json::from_str::<Vec<Entry>>(&existing.subs_json).unwrap();
@@ -153,7 +208,9 @@ impl VideoSubs for VideoSubService {
             let new_changes = NewChange {
                 timestamp: &now,
                 author: &user.id,
-                changes_json: &changes_json
+                changes_json: &changes_json,
+                video_id: &req.video_id,
+                language: &req.language
             };
             diesel::insert_into(changes::table)
                 .values(&new_changes)
@@ -169,21 +226,41 @@ impl VideoSubs for VideoSubService {
                 .into_status()?;
         }
 
-        Ok(Response::new(SetSubtitleResponse {}))
+        let existing_speakers = serde_json::from_str::<Vec<Speaker>>(&existing.speakers_json).unwrap();
+        if existing_speakers != req.speakers {
+            let json = serde_json::to_string(&req.speakers)
+                .map_err(|_| Status::invalid_argument("Can't serialize to JSON"))?;
+
+            diesel::update(&existing)
+                .set(speakers_json.eq(json))
+                .execute(&conn)
+                .into_status()?;
+        }
+
+        Ok(Response::new(SetSubtitleResponse {
+            coverage: duration.map_or(0., |duration| coverage(&req.entries, duration))
+        }))
     }
 
     async fn get_subtitles(&self, request: Request<SubtitleId>) -> Result<Response<Subtitles>, Status> {
         let req = request.into_inner();
-        let subs = get_or_init_subtitles(self.db()?, &req.video_id, &req.language).await?;
+        let source = Some(&*req.source).filter(|source| !source.is_empty());
+        let subs = get_or_init_subtitles(self.db()?, &req.video_id, &req.language, source).await?;
         let entries = serde_json::from_str::<Vec<Entry>>(&subs.subs_json).unwrap();
-        let video_info = get_video_info(&subs.video_id).await?;
+        let speakers = serde_json::from_str::<Vec<Speaker>>(&subs.speakers_json).unwrap();
+        let video = videos::get_video(&self.0, &subs.video_id).await?;
+        let duration = videos::duration_seconds(&video);
         Ok(Response::new(Subtitles {
+            coverage: duration.map_or(0., |duration| coverage(&entries, duration)),
+            duration_seconds: duration.unwrap_or_default(),
+            is_video_long: duration.map_or(false, |duration| duration >= LONG_VIDEO_SECONDS),
             entries,
             video_id: subs.video_id,
             language: subs.language,
-            video_title: video_info.snippet.title,
-            uploader_id: video_info.snippet.channel_id,
-            uploader_name: video_info.snippet.channel_title
+            video_title: video.title,
+            uploader_id: video.channel_id,
+            uploader_name: video.channel_title,
+            speakers
         }))
     }
 
@@ -193,36 +270,100 @@ impl VideoSubs for VideoSubService {
         let req = request.into_inner();
 
         let conn = self.db()?;
-        let subs = get_or_init_subtitles(conn, &req.video_id, &req.language).await?;
+        let subs = get_or_init_subtitles(conn, &req.video_id, &req.language, None).await?;
         let entries: Vec<Entry> = serde_json::from_str(&subs.subs_json).unwrap();
-        let format = Format::from_i32(req.format).unwrap();
-
-        let out = match format {
-            Format::Srt => {
-                let entries = entries.into_iter()
-                    .map(|entry| {
-                        let start = TimePoint::from_msecs((entry.start_seconds * 1000.) as i64);
-                        let end = TimePoint::from_msecs((entry.end_seconds * 1000.) as i64);
-                        let span = TimeSpan::new(start, end);
-                        (span, entry.text)
-                    })
-                    .collect();
-                let srt = SrtFile::create(entries).unwrap();
-                let mut data = Cursor::new(srt.to_data().unwrap());
-                let out = async_stream::try_stream! {
-                    while data.has_remaining() {
-                        let mut buf = vec![0; 1024];
-                        let n = data.read(&mut buf).unwrap();
-                        buf.truncate(n);
-                        yield Chunk {
-                            content: buf
-                        }
-                    }
-                };
-                out
+        let speakers: Vec<Speaker> = serde_json::from_str(&subs.speakers_json).unwrap();
+        let format = Format::from_i32(req.format)
+            .ok_or_else(|| Status::invalid_argument("Unknown subtitle format"))?;
+
+        let mut data = Cursor::new(export::export(format, entries, &speakers)?);
+        let out = async_stream::try_stream! {
+            while data.has_remaining() {
+                let mut buf = vec![0; 1024];
+                let n = data.read(&mut buf).unwrap();
+                buf.truncate(n);
+                yield Chunk {
+                    content: buf
+                }
             }
         };
 
         Ok(Response::new(Box::pin(out) as Self::DownloadSubtitlesStream))
     }
-}
\ No newline at end of file
+
+    async fn import_subtitles(&self, request: Request<ImportRequest>) -> Result<Response<Subtitles>, Status> {
+        let req = request.into_inner();
+        let format = Format::from_i32(req.format)
+            .ok_or_else(|| Status::invalid_argument("Unknown subtitle format"))?;
+        // Saving goes through SetSubtitles so the editor can review the entries first
+        let (entries, speakers) = import::import(format, &req.content)?;
+        Ok(Response::new(Subtitles {
+            entries,
+            speakers,
+            video_id: req.video_id,
+            language: req.language,
+            ..Default::default()
+        }))
+    }
+
+    async fn list_source_captions(&self, request: Request<VideoId>) -> Result<Response<SourceCaptionList>, Status> {
+        let req = request.into_inner();
+        if !has_source_captions(&self.db()?, &req.video_id)? {
+            return Ok(Response::new(SourceCaptionList { captions: Vec::new() }));
+        }
+
+        let client = reqwest::Client::new();
+        let tracks = youtube_caption_scraper::get_caption_tracks(&client, &req.video_id).await?;
+
+        Ok(Response::new(SourceCaptionList {
+            captions: tracks.into_iter().map(Into::into).collect()
+        }))
+    }
+
+    async fn get_video(&self, request: Request<VideoId>) -> Result<Response<Video>, Status> {
+        let req = request.into_inner();
+        let video = videos::get_video(&self.0, &req.video_id).await?;
+        Ok(Response::new(video.into()))
+    }
+
+    async fn add_video(&self, request: Request<AddVideoRequest>) -> Result<Response<Video>, Status> {
+        let conn = self.db()?;
+        get_user(&request, &conn)?;
+
+        let video = videos::add_video(&self.0, request.into_inner()).await?;
+        Ok(Response::new(video.into()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(start_seconds: f32, end_seconds: f32) -> Entry {
+        Entry { start_seconds, end_seconds, ..Entry::default() }
+    }
+
+    fn rejected_index(entries: &[Entry], duration: Option<f32>) -> String {
+        let status = validate_times(entries, duration).unwrap_err();
+        assert_eq!(status.code(), Code::InvalidArgument);
+        ErrorInfo::from_status(&status).unwrap().metadata["index"].clone()
+    }
+
+    #[test]
+    fn accepts_entries_within_the_video() {
+        let entries = [entry(0., 1.5), entry(1.5, 1.5), entry(58., 60.5)];
+        assert!(validate_times(&entries, Some(60.)).is_ok());
+        assert!(validate_times(&entries, None).is_ok());
+    }
+
+    #[test]
+    fn rejects_entries_out_of_range() {
+        assert_eq!(rejected_index(&[entry(0., 1.), entry(-0.5, 1.)], None), "1");
+        assert_eq!(rejected_index(&[entry(2., 1.)], None), "0");
+        assert_eq!(rejected_index(&[entry(f32::NAN, 1.)], None), "0");
+        assert_eq!(rejected_index(&[entry(0., f32::INFINITY)], None), "0");
+        assert_eq!(rejected_index(&[entry(0., 1.), entry(59., 62.)], Some(60.)), "1");
+        // Without a duration there's no upper bound
+        assert!(validate_times(&[entry(59., 62.)], None).is_ok());
+    }
+}
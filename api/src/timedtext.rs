@@ -0,0 +1,296 @@
+//! Parser for YouTube's timedtext transcripts.
+//!
+//! Three formats are understood and detected from the document itself:
+//! * the classic `<transcript><text start="" dur="">` XML,
+//! * `fmt=srv3` XML (`<timedtext format="3">`) with millisecond `<p>`/`<s>` timings,
+//! * `fmt=json3` with `events` and `segs`.
+//!
+//! srv3 and json3 carry word level offsets for auto-generated tracks which are kept in
+//! [`Cue::words`].
+
+use htmlescape::decode_html;
+use regex::Regex;
+use roxmltree::{Document, Node};
+use serde::Deserialize;
+use std::fmt::{self, Display};
+
+/// How long a cue without a duration lasts if nothing follows it.
+const DEFAULT_DURATION: f32 = 2.0;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cue {
+    pub start_seconds: f32,
+    pub end_seconds: f32,
+    pub text: String,
+    pub words: Vec<Word>
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Word {
+    pub start_seconds: f32,
+    pub text: String
+}
+
+#[derive(Debug)]
+pub enum ParseError {
+    Xml(roxmltree::Error),
+    Json(serde_json::Error),
+    UnknownFormat(String)
+}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseError::Xml(err) => write!(f, "invalid transcript XML: {}", err),
+            ParseError::Json(err) => write!(f, "invalid transcript JSON: {}", err),
+            ParseError::UnknownFormat(root) => write!(f, "unknown transcript format <{}>", root)
+        }
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+/// A cue as found in the document, before missing durations are filled in.
+struct RawCue {
+    start_seconds: f32,
+    duration: Option<f32>,
+    text: String,
+    words: Vec<Word>
+}
+
+pub fn parse(transcript: &str) -> Result<Vec<Cue>, ParseError> {
+    let transcript = transcript.trim_start_matches('\u{feff}').trim_start();
+    let cues = if transcript.starts_with('{') {
+        parse_json3(transcript)?
+    } else {
+        let doc = Document::parse(transcript).map_err(ParseError::Xml)?;
+        let root = doc.root_element();
+        match root.tag_name().name() {
+            "transcript" => parse_classic(root),
+            "timedtext" => parse_srv3(root),
+            other => return Err(ParseError::UnknownFormat(other.to_string()))
+        }
+    };
+    Ok(finish(cues))
+}
+
+fn parse_classic(root: Node) -> Vec<RawCue> {
+    let tag_regex = Regex::new(r#"</?[^>]+(>|$)"#).unwrap();
+
+    root.children()
+        .filter(|node| node.has_tag_name("text"))
+        .filter_map(|node| {
+            let start_seconds = node.attribute("start")?.parse().ok()?;
+            let duration = node.attribute("dur").and_then(|dur| dur.parse().ok());
+
+            // The content is HTML escaped a second time inside the XML and may contain markup
+            let html = node.text().unwrap_or_default();
+            let html = tag_regex.replace_all(html, "");
+            let text = decode_html(&html).unwrap_or_else(|_| html.to_string());
+
+            Some(RawCue {
+                start_seconds,
+                duration,
+                text,
+                words: Vec::new()
+            })
+        })
+        .collect()
+}
+
+fn millis(node: Node, name: &str) -> Option<f32> {
+    node.attribute(name)?.parse::<f32>().ok().map(|ms| ms / 1000.)
+}
+
+fn parse_srv3(root: Node) -> Vec<RawCue> {
+    root.descendants()
+        .filter(|node| node.has_tag_name("p"))
+        .filter_map(|node| {
+            let start_seconds = millis(node, "t")?;
+            let duration = millis(node, "d");
+
+            let segments: Vec<_> = node.children().filter(|child| child.has_tag_name("s")).collect();
+            let (text, words) = if segments.is_empty() {
+                let text = node.descendants()
+                    .filter(|child| child.is_text())
+                    .filter_map(|child| child.text())
+                    .collect();
+                (text, Vec::new())
+            } else {
+                let words = segments.into_iter()
+                    .map(|segment| Word {
+                        start_seconds: start_seconds + millis(segment, "t").unwrap_or(0.),
+                        text: segment.text().unwrap_or_default().to_string()
+                    })
+                    .collect::<Vec<_>>();
+                (words.iter().map(|word| &*word.text).collect(), words)
+            };
+
+            Some(RawCue {
+                start_seconds,
+                duration,
+                text,
+                words
+            })
+        })
+        .collect()
+}
+
+#[derive(Deserialize)]
+struct Json3 {
+    #[serde(default)]
+    events: Vec<Json3Event>
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Json3Event {
+    t_start_ms: Option<f32>,
+    d_duration_ms: Option<f32>,
+    #[serde(default)]
+    segs: Vec<Json3Segment>
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Json3Segment {
+    #[serde(default)]
+    utf8: String,
+    t_offset_ms: Option<f32>
+}
+
+fn parse_json3(transcript: &str) -> Result<Vec<RawCue>, ParseError> {
+    let json = serde_json::from_str::<Json3>(transcript).map_err(ParseError::Json)?;
+
+    Ok(json.events
+        .into_iter()
+        .filter(|event| !event.segs.is_empty())
+        .filter_map(|event| {
+            let start_seconds = event.t_start_ms? / 1000.;
+            let has_offsets = event.segs.iter().any(|seg| seg.t_offset_ms.is_some());
+            let text = event.segs.iter().map(|seg| &*seg.utf8).collect();
+            let words = if has_offsets {
+                event.segs
+                    .into_iter()
+                    .map(|seg| Word {
+                        start_seconds: start_seconds + seg.t_offset_ms.unwrap_or(0.) / 1000.,
+                        text: seg.utf8
+                    })
+                    .collect()
+            } else {
+                Vec::new()
+            };
+
+            Some(RawCue {
+                start_seconds,
+                duration: event.d_duration_ms.map(|ms| ms / 1000.),
+                text,
+                words
+            })
+        })
+        .collect())
+}
+
+/// Drops cues without any text and derives missing end times from the following cue.
+fn finish(cues: Vec<RawCue>) -> Vec<Cue> {
+    let cues: Vec<_> = cues.into_iter()
+        .map(|cue| RawCue {
+            text: cue.text.trim().to_string(),
+            ..cue
+        })
+        .filter(|cue| !cue.text.is_empty())
+        .collect();
+    let next_starts: Vec<_> = cues.iter()
+        .skip(1)
+        .map(|cue| Some(cue.start_seconds))
+        .chain(Some(None))
+        .collect();
+
+    cues.into_iter()
+        .zip(next_starts)
+        .map(|(cue, next_start)| {
+            let end_seconds = match (cue.duration, next_start) {
+                (Some(duration), _) => cue.start_seconds + duration,
+                (None, Some(next_start)) if next_start > cue.start_seconds => next_start,
+                (None, _) => cue.start_seconds + DEFAULT_DURATION
+            };
+            Cue {
+                start_seconds: cue.start_seconds,
+                end_seconds,
+                text: cue.text,
+                words: cue.words
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(actual: f32, expected: f32) {
+        assert!((actual - expected).abs() < 0.001, "{} != {}", actual, expected);
+    }
+
+    #[test]
+    fn parses_classic_transcript() {
+        let cues = parse(include_str!("../tests/fixtures/timedtext/classic.xml")).unwrap();
+
+        assert_eq!(cues.len(), 4);
+        assert_eq!(cues[0].text, "Hey everyone, welcome back!");
+        assert_close(cues[0].start_seconds, 0.32);
+        assert_close(cues[0].end_seconds, 3.04);
+        assert_eq!(cues[1].text, "Today we're looking at \"Rust & WebAssembly\"");
+        assert_eq!(cues[2].text, "[Music]");
+        assert!(cues.iter().all(|cue| cue.words.is_empty()));
+    }
+
+    #[test]
+    fn tolerates_missing_attributes() {
+        let cues = parse(include_str!("../tests/fixtures/timedtext/classic.xml")).unwrap();
+
+        // The third line has no `dur` and ends where the next one starts, the last one has no
+        // `dur` either and gets the default duration
+        assert_close(cues[2].end_seconds, cues[3].start_seconds);
+        assert_close(cues[3].end_seconds, cues[3].start_seconds + DEFAULT_DURATION);
+        // The line without a `start` is skipped instead of failing the whole track
+        assert!(cues.iter().all(|cue| cue.text != "no start"));
+    }
+
+    #[test]
+    fn parses_srv3_with_word_timings() {
+        let cues = parse(include_str!("../tests/fixtures/timedtext/srv3.xml")).unwrap();
+
+        assert_eq!(cues.len(), 3);
+        assert_eq!(cues[0].text, "so today we're going");
+        assert_close(cues[0].start_seconds, 1.2);
+        assert_close(cues[0].end_seconds, 4.8);
+        let words: Vec<_> = cues[0].words.iter().map(|word| word.text.trim()).collect();
+        assert_eq!(words, ["so", "today", "we're", "going"]);
+        assert_close(cues[0].words[1].start_seconds, 1.68);
+
+        // Manually created lines in srv3 have no segments
+        assert_eq!(cues[2].text, "Tom & Jerry");
+        assert!(cues[2].words.is_empty());
+    }
+
+    #[test]
+    fn parses_json3_with_word_timings() {
+        let cues = parse(include_str!("../tests/fixtures/timedtext/json3.json")).unwrap();
+
+        assert_eq!(cues.len(), 2);
+        assert_eq!(cues[0].text, "so today we're going");
+        assert_close(cues[0].end_seconds, 4.8);
+        assert_close(cues[0].words[3].start_seconds, 2.64);
+        assert_eq!(cues[1].text, "to talk about parsers");
+        // No duration on the last event
+        assert_close(cues[1].end_seconds, cues[1].start_seconds + DEFAULT_DURATION);
+    }
+
+    #[test]
+    fn rejects_unknown_documents() {
+        assert!(matches!(parse("<html></html>"), Err(ParseError::UnknownFormat(_))));
+        assert!(matches!(parse("<transcript><text start=\"1\"></transcript>"), Err(ParseError::Xml(_))));
+        assert!(matches!(parse("{\"events\": 1}"), Err(ParseError::Json(_))));
+    }
+}
@@ -0,0 +1,126 @@
+//! Personal API tokens, for using the API from scripts without going through the browser login.
+//!
+//! Tokens are random strings with a recognizable prefix. Only their SHA-256 is stored, which is
+//! enough since they're long and random, so the secret can't be shown again after creation.
+
+use crate::{
+    auth::{AuthError, Principal, SUBTITLES_READ, SUBTITLES_WRITE, USER_READ, USER_WRITE},
+    db::models::{ApiToken, User},
+    DbConnection, IntoStatus
+};
+use chrono::{Duration, Utc};
+use diesel::{ExpressionMethods, OptionalExtension, QueryDsl, RunQueryDsl};
+use sha2::{Digest, Sha256};
+use tonic::Status;
+use uuid::Uuid;
+
+/// Tells API tokens apart from identity provider JWTs.
+pub const TOKEN_PREFIX: &str = "sut_";
+/// Scopes a token can be created with. Full access is reserved for browser sessions, so a
+/// leaked token can't be used to create more.
+pub const GRANTABLE_SCOPES: &[&str] = &[USER_READ, USER_WRITE, SUBTITLES_READ, SUBTITLES_WRITE];
+pub const DEFAULT_LIFETIME_DAYS: u32 = 90;
+pub const MAX_LIFETIME_DAYS: u32 = 365;
+
+pub fn is_api_token(token: &str) -> bool {
+    token.starts_with(TOKEN_PREFIX)
+}
+
+fn hash(token: &str) -> String {
+    hex::encode(Sha256::digest(token.as_bytes()))
+}
+
+/// Stores a new token and returns it along with the secret to hand to the user.
+pub fn create(
+    conn: &DbConnection,
+    user_id: &str,
+    name: &str,
+    scopes: &[String],
+    lifetime_days: u32
+) -> Result<(ApiToken, String), Status> {
+    use crate::db::schema::api_tokens;
+
+    // Two v4 UUIDs give 244 random bits
+    let secret = format!(
+        "{}{}{}",
+        TOKEN_PREFIX,
+        Uuid::new_v4().to_simple(),
+        Uuid::new_v4().to_simple()
+    );
+    let now = Utc::now().naive_utc();
+    let token = ApiToken {
+        id: Uuid::new_v4().to_string(),
+        user_id: user_id.to_string(),
+        name: name.to_string(),
+        token_hash: hash(&secret),
+        scopes: scopes.join(" "),
+        created_at: now,
+        expires_at: now + Duration::days(lifetime_days as i64),
+        last_used_at: None
+    };
+
+    diesel::insert_into(api_tokens::table)
+        .values(&token)
+        .execute(conn)
+        .into_status()?;
+    Ok((token, secret))
+}
+
+pub fn list(conn: &DbConnection, owner: &str) -> Result<Vec<ApiToken>, Status> {
+    use crate::db::schema::api_tokens::dsl::*;
+
+    api_tokens
+        .filter(user_id.eq(owner))
+        .order(created_at.desc())
+        .load::<ApiToken>(conn)
+        .into_status()
+}
+
+/// Deletes one of `owner`'s tokens. Other users' tokens are reported as not found.
+pub fn revoke(conn: &DbConnection, owner: &str, token_id: &str) -> Result<(), Status> {
+    use crate::db::schema::api_tokens::dsl::*;
+
+    let deleted = diesel::delete(api_tokens.filter(id.eq(token_id)).filter(user_id.eq(owner)))
+        .execute(conn)
+        .into_status()?;
+    match deleted {
+        0 => Err(Status::not_found("No such token")),
+        _ => Ok(())
+    }
+}
+
+/// Looks up the owner of a token. Their name and email come from the profile, the scopes from
+/// the token.
+pub fn authenticate(conn: &DbConnection, token: &str) -> Result<Principal, Status> {
+    use crate::db::schema::{api_tokens, users};
+
+    let stored = api_tokens::table
+        .filter(api_tokens::token_hash.eq(hash(token)))
+        .first::<ApiToken>(conn)
+        .optional()
+        .into_status()?
+        .ok_or(AuthError::UnknownToken)?;
+    let now = Utc::now().naive_utc();
+    if stored.expires_at < now {
+        return Err(AuthError::Expired.into());
+    }
+
+    let user = users::table
+        .find(&stored.user_id)
+        .first::<User>(conn)
+        .optional()
+        .into_status()?
+        .ok_or(AuthError::UnknownToken)?;
+    diesel::update(api_tokens::table.find(&stored.id))
+        .set(api_tokens::last_used_at.eq(now))
+        .execute(conn)
+        .into_status()?;
+
+    Ok(Principal {
+        subject: user.id,
+        name: user.username,
+        emails: user.email.into_iter().collect(),
+        scopes: stored.scopes.split_whitespace().map(str::to_string).collect(),
+        roles: Vec::new()
+    })
+}
@@ -1,17 +1,37 @@
 use crate::{
+    account,
+    avatars::{self, Avatar},
     db::{models, models::NewUser},
-    Claims, IntoStatus, State
+    auth::Principal,
+    stats,
+    tokens::{self, DEFAULT_LIFETIME_DAYS, GRANTABLE_SCOPES, MAX_LIFETIME_DAYS},
+    IntoStatus, State
 };
-use api_types::user::{
-    user_service_server, ImageUploadRequest, SayRequest, SayResponse, User, UserIdentity
+use api_types::{
+    errors::ErrorInfo,
+    user::{
+        profile_picture_request::Format, user_service_server, ApiToken, ApiTokenList,
+        CreateApiTokenRequest, CreatedApiToken, DataExport, DeleteAccountRequest,
+        DeleteAccountResponse, ExportRequest, ImageUploadRequest, LanguageSkill,
+        ListApiTokensRequest, ProfilePicture, ProfilePictureRequest, RevokeApiTokenRequest,
+        RevokeApiTokenResponse, SayRequest, SayResponse, UpdateProfileRequest, User, UserIdentity,
+        UserStats, UserStatsRequest
+    }
+};
+use diesel::{
+    result::{DatabaseErrorKind, Error as DieselError},
+    Connection, ExpressionMethods, QueryDsl, QueryResult, RunQueryDsl, SqliteConnection
 };
-use diesel::{ExpressionMethods, QueryDsl, QueryResult, RunQueryDsl, SqliteConnection};
-use image::{imageops::FilterType, DynamicImage};
-use std::{fs::remove_file, ops::Deref, sync::Arc};
-use tonic::{Request, Response, Status};
+use std::{collections::HashSet, ops::Deref, sync::Arc};
+use tonic::{Code, Request, Response, Status};
 use uuid::Uuid;
 
-const PFP_SIZE: u32 = 256;
+const USERNAME_LENGTH: (usize, usize) = (3, 32);
+const MAX_DISPLAY_NAME_LENGTH: usize = 64;
+const MAX_BIO_LENGTH: usize = 1000;
+const MAX_LANGUAGES: usize = 20;
+const MAX_TOKEN_NAME_LENGTH: usize = 64;
+const MAX_API_TOKENS: usize = 20;
 
 pub struct UserService(pub Arc<State>);
 
@@ -28,27 +48,97 @@ impl From<models::User> for User {
         User {
             id: user.id,
             username: user.username,
-            email: user.email.unwrap_or_else(|| String::new())
+            email: user.email.unwrap_or_default(),
+            picture: user.picture.unwrap_or_default(),
+            display_name: user.display_name,
+            bio: user.bio,
+            languages: Vec::new()
         }
     }
 }
 
-fn init_user(claims: Claims, conn: &SqliteConnection) -> QueryResult<models::User> {
+impl From<models::UserLanguage> for LanguageSkill {
+    fn from(language: models::UserLanguage) -> Self {
+        LanguageSkill {
+            language: language.language,
+            can_caption: language.can_caption,
+            can_translate: language.can_translate
+        }
+    }
+}
+
+impl From<models::ApiToken> for ApiToken {
+    fn from(token: models::ApiToken) -> Self {
+        ApiToken {
+            id: token.id,
+            name: token.name,
+            scopes: token.scopes.split_whitespace().map(str::to_string).collect(),
+            created: token.created_at.and_utc().timestamp(),
+            expires: token.expires_at.and_utc().timestamp(),
+            last_used: token.last_used_at.map(|time| time.and_utc().timestamp()).unwrap_or(0)
+        }
+    }
+}
+
+fn is_unique_violation(err: &DieselError) -> bool {
+    matches!(err, DieselError::DatabaseError(DatabaseErrorKind::UniqueViolation, _))
+}
+
+fn is_username_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_' || c == '-' || c == '.'
+}
+
+/// Turns a display name from the identity provider into a valid username, replacing runs of
+/// other characters with `-`. Names with too little left become `user`.
+fn username_from_name(name: &str) -> String {
+    let (min, max) = USERNAME_LENGTH;
+    let mut username = String::new();
+    for c in name.trim().chars() {
+        if is_username_char(c) {
+            username.push(c);
+        } else if !username.is_empty() && !username.ends_with('-') {
+            username.push('-');
+        }
+    }
+    username.truncate(max);
+    let username = username.trim_end_matches('-');
+    if username.len() < min {
+        "user".to_string()
+    } else {
+        username.to_string()
+    }
+}
+
+/// `name` shortened so a `-` and `suffix` fit, for when the plain name is taken.
+fn username_with_suffix(name: &str, suffix: &str) -> String {
+    let (_, max) = USERNAME_LENGTH;
+    let suffix: String = suffix.chars().filter(|c| c.is_ascii_alphanumeric()).take(8).collect();
+    let mut username = username_from_name(name);
+    username.truncate(max - suffix.len() - 1);
+    format!("{}-{}", username.trim_end_matches('-'), suffix)
+}
+
+fn init_user(principal: Principal, conn: &SqliteConnection) -> QueryResult<models::User> {
     use crate::db::schema::users;
 
-    let user = NewUser {
-        id: &claims.sub,
-        username: &claims.name,
-        email: claims.emails.first().map(|s| &**s)
+    let email = principal.emails.first().map(|s| &**s);
+    let insert = |username: &str| {
+        diesel::insert_into(users::table)
+            .values(&NewUser { id: &principal.subject, username, email })
+            .execute(conn)
     };
 
-    let res = diesel::insert_into(users::table)
-        .values(&user)
-        .execute(conn)?;
+    // Names from the identity provider aren't unique, so fall back to one including the id
+    let res = match insert(&username_from_name(&principal.name)) {
+        Err(err) if is_unique_violation(&err) => {
+            insert(&username_with_suffix(&principal.name, &principal.subject))?
+        }
+        res => res?
+    };
     assert_eq!(res, 1);
 
     users::table
-        .find(&claims.sub)
+        .find(&principal.subject)
         .load::<models::User>(conn)?
         .pop()
         .ok_or_else(|| diesel::NotFound)
@@ -57,76 +147,142 @@ fn init_user(claims: Claims, conn: &SqliteConnection) -> QueryResult<models::Use
 pub fn get_user<T>(request: &Request<T>, conn: &SqliteConnection) -> Result<models::User, Status> {
     use crate::db::{models::User, schema::users::dsl::*};
 
-    let user = request
-        .metadata()
-        .get("user")
-        .ok_or_else(|| Status::unauthenticated("not authenticated"))?
-        .to_str()
-        .unwrap();
-    let claims: Claims = serde_json::from_str(user).unwrap();
-    Ok(users
-        .find(&claims.sub)
-        .load::<User>(conn)
-        .into_status()?
-        .pop()
-        .unwrap_or_else(|| init_user(claims, conn).unwrap()))
+    let principal = Principal::from_request(request)?;
+    match users.find(&principal.subject).load::<User>(conn).into_status()?.pop() {
+        Some(user) => Ok(user),
+        None => init_user(principal, conn).into_status()
+    }
 }
 
-impl UserService {
-    #[cfg(feature = "cloud-storage")]
-    async fn save_image(&self, image: DynamicImage, id: &str) {
-        use azure_sdk_storage_blob::Blob;
-        use image::{
-            png::{self, CompressionType, PNGEncoder},
-            GenericImageView
-        };
-        use std::io::BufWriter;
+/// Loads a user's profile including their language skills.
+fn load_profile(conn: &SqliteConnection, user: models::User) -> Result<User, Status> {
+    use crate::db::schema::user_languages::dsl::*;
+
+    let languages = user_languages
+        .filter(user_id.eq(&user.id))
+        .order(language)
+        .load::<models::UserLanguage>(conn)
+        .into_status()?;
+    Ok(User {
+        languages: languages.into_iter().map(Into::into).collect(),
+        ..user.into()
+    })
+}
 
-        let pixels = image.pixels().collect();
-        let mut image_data = Vec::new();
-        let encoder = PNGEncoder::new_with_quality(
-            BufWriter::new(&mut image_data),
-            CompressionType::Best,
-            png::FilterType::Sub
-        );
-        encoder.encode(&pixels, PFP_SIZE, PFP_SIZE, image.color());
+fn invalid_field(field: &str, message: impl Into<String>) -> Status {
+    ErrorInfo::new("users", "INVALID_FIELD")
+        .with_metadata("field", field)
+        .into_status(Code::InvalidArgument, message)
+}
 
-        let client = self.blob();
-        client
-            .put_block_blob()
-            .with_container_name("profile_pictures")
-            .with_blob_name(id)
-            .with_body(&image_data)
-            .finalize()
-            .await
-            .map_err(|e| Status::aborted(format!("{}", e)))?;
+fn validate_username(username: &str) -> Result<(), Status> {
+    let (min, max) = USERNAME_LENGTH;
+    if username.len() < min || username.len() > max {
+        return Err(invalid_field("username", format!("Username must be {} to {} characters long", min, max)));
+    }
+    if !username.chars().all(is_username_char) {
+        return Err(invalid_field("username", "Username can only contain letters, digits, '_', '-' and '.'"));
     }
+    Ok(())
+}
 
-    #[cfg(not(feature = "cloud-storage"))]
-    async fn save_image(&self, image: DynamicImage, id: &str) {
-        image
-            .save(format!("../target/user_profiles/{}.png", id))
-            .unwrap();
+/// Accepts BCP 47 style tags like `en`, `pt-BR` or `zh-Hant`.
+fn is_language_tag(tag: &str) -> bool {
+    let mut parts = tag.split('-');
+    let primary = parts.next().unwrap_or_default();
+    (2..=3).contains(&primary.len())
+        && primary.chars().all(|c| c.is_ascii_lowercase())
+        && parts.all(|part| (2..=8).contains(&part.len()) && part.chars().all(|c| c.is_ascii_alphanumeric()))
+}
+
+fn validate_languages(languages: &[LanguageSkill]) -> Result<(), Status> {
+    if languages.len() > MAX_LANGUAGES {
+        return Err(invalid_field("languages", format!("Can't list more than {} languages", MAX_LANGUAGES)));
+    }
+    let mut seen = HashSet::new();
+    for skill in languages {
+        if !is_language_tag(&skill.language) {
+            return Err(invalid_field("languages", format!("Invalid language code {}", skill.language)));
+        }
+        if !seen.insert(&skill.language) {
+            return Err(invalid_field("languages", format!("{} is listed twice", skill.language)));
+        }
+        if !skill.can_caption && !skill.can_translate {
+            return Err(invalid_field("languages", format!("No skill selected for {}", skill.language)));
+        }
+    }
+    Ok(())
+}
+
+fn validate_token_request(request: &CreateApiTokenRequest) -> Result<(), Status> {
+    let name = request.name.trim();
+    if name.is_empty() || name.chars().count() > MAX_TOKEN_NAME_LENGTH {
+        return Err(invalid_field("name", format!("Name must be 1 to {} characters long", MAX_TOKEN_NAME_LENGTH)));
     }
+    if request.scopes.is_empty() {
+        return Err(invalid_field("scopes", "Select at least one scope"));
+    }
+    if let Some(scope) = request.scopes.iter().find(|scope| !GRANTABLE_SCOPES.contains(&scope.as_str())) {
+        return Err(invalid_field("scopes", format!("Tokens can't be granted {}", scope)));
+    }
+    if request.expires_in_days > MAX_LIFETIME_DAYS {
+        return Err(invalid_field("expiresInDays", format!("Tokens can be valid for at most {} days", MAX_LIFETIME_DAYS)));
+    }
+    Ok(())
+}
+
+/// Storage key of one size and format of a profile picture.
+fn image_key(id: &str, size: u32, format: Format) -> String {
+    format!("{}_{}.{}", id, size, avatars::extension(format))
+}
+
+/// Pictures uploaded before multiple sizes were generated only exist as a single PNG.
+fn legacy_image_key(id: &str) -> String {
+    format!("{}.png", id)
+}
 
-    #[cfg(feature = "cloud-storage")]
-    async fn remove_image(&self, id: &str) {
-        use azure_sdk_storage_blob::Blob;
+impl UserService {
+    async fn save_images(&self, images: Vec<Avatar>, id: &str) -> Result<(), Status> {
+        for image in images {
+            let key = image_key(id, image.size, image.format);
+            self.storage.put(&key, image.content, avatars::content_type(image.format)).await?;
+        }
+        Ok(())
+    }
 
-        let client = self.blob();
+    async fn remove_images(&self, id: &str) -> Result<(), Status> {
+        for &size in avatars::SIZES.iter() {
+            for &format in [Format::Png, Format::Webp].iter() {
+                self.storage.delete(&image_key(id, size, format)).await?;
+            }
+        }
+        self.storage.delete(&legacy_image_key(id)).await?;
+        Ok(())
+    }
 
-        client
-            .delete_blob()
-            .with_container_name("profile_pictures")
-            .with_blob_name(id)
-            .with_delete_snapshots_method(DeleteSnapshotsMethod::Include)
-            .finalize()
-            .await
-            .map_err(|e| Status::aborted(format!("{}", e)))?;
+    /// Loads the requested variant, falling back to the legacy PNG for old uploads.
+    async fn load_image(&self, id: &str, size: u32, format: Format) -> Result<Option<(Vec<u8>, Format)>, Status> {
+        if let Some(content) = self.storage.get(&image_key(id, size, format)).await? {
+            return Ok(Some((content, format)));
+        }
+        let legacy = self.storage.get(&legacy_image_key(id)).await?;
+        Ok(legacy.map(|content| (content, Format::Png)))
     }
 
-    async fn remove_image(&self, id: &str) {
-        remove_file(format!("../target/user_profiles/{}.png", id)).unwrap();
+    /// Every stored variant of a picture, named by size and format.
+    async fn picture_files(&self, id: &str) -> Result<Vec<(String, Vec<u8>)>, Status> {
+        let mut files = Vec::new();
+        for &size in avatars::SIZES.iter() {
+            for &format in [Format::Png, Format::Webp].iter() {
+                if let Some(content) = self.storage.get(&image_key(id, size, format)).await? {
+                    files.push((format!("{}.{}", size, avatars::extension(format)), content));
+                }
+            }
+        }
+        if let Some(content) = self.storage.get(&legacy_image_key(id)).await? {
+            files.push(("picture.png".to_string(), content));
+        }
+        Ok(files)
     }
 }
 
@@ -148,22 +304,19 @@ impl user_service_server::UserService for UserService {
     ) -> Result<Response<api_types::user::Status>, Status> {
         use crate::db::schema::users::dsl::*;
 
-        let req = request.get_ref();
-
-        let image = image::load_from_memory(&req.content)
-            .map_err(|e| Status::invalid_argument(e.to_string()))?
-            .crop(req.offset_x, req.offset_y, req.crop_size, req.crop_size)
-            .resize(PFP_SIZE, PFP_SIZE, FilterType::Nearest);
-
+        let images = avatars::process(request.get_ref())?;
         let image_id = Uuid::new_v4().to_string();
 
-        self.save_image(image, &image_id).await;
+        self.save_images(images, &image_id).await?;
 
         let conn = self.db()?;
         let user = get_user(&request, &conn)?;
 
         if let Some(current_picture) = user.picture {
-            self.remove_image(&current_picture).await;
+            // The new picture is saved already, a leftover file isn't worth failing over
+            if let Err(status) = self.remove_images(&current_picture).await {
+                eprintln!("Couldn't remove old profile picture {}: {}", current_picture, status.message());
+            }
         }
 
         diesel::update(users.find(user.id))
@@ -179,8 +332,220 @@ impl user_service_server::UserService for UserService {
 
     async fn get_user(&self, request: Request<UserIdentity>) -> Result<Response<User>, Status> {
         let conn = self.db.get().into_status()?;
-        get_user(&request, &conn)
-            .map(Into::into)
-            .map(|user| Response::new(user))
+        let user = get_user(&request, &conn)?;
+        load_profile(&conn, user).map(Response::new)
+    }
+
+    async fn update_profile(&self, request: Request<UpdateProfileRequest>) -> Result<Response<User>, Status> {
+        use crate::db::schema::{user_languages, users};
+
+        let conn = self.db()?;
+        let user = get_user(&request, &conn)?;
+        let req = request.into_inner();
+
+        let username = req.username.trim();
+        let display_name = req.display_name.trim();
+        let bio = req.bio.trim();
+        validate_username(username)?;
+        if display_name.chars().count() > MAX_DISPLAY_NAME_LENGTH {
+            return Err(invalid_field("displayName", format!("Display name can't be longer than {} characters", MAX_DISPLAY_NAME_LENGTH)));
+        }
+        if bio.chars().count() > MAX_BIO_LENGTH {
+            return Err(invalid_field("bio", format!("Bio can't be longer than {} characters", MAX_BIO_LENGTH)));
+        }
+        validate_languages(&req.languages)?;
+
+        let languages: Vec<_> = req.languages.into_iter()
+            .map(|skill| models::UserLanguage {
+                user_id: user.id.clone(),
+                language: skill.language,
+                can_caption: skill.can_caption,
+                can_translate: skill.can_translate
+            })
+            .collect();
+
+        let updated = conn.transaction::<_, DieselError, _>(|| {
+            diesel::update(users::table.find(&user.id))
+                .set((
+                    users::username.eq(username),
+                    users::display_name.eq(display_name),
+                    users::bio.eq(bio)
+                ))
+                .execute(&conn)?;
+            diesel::delete(user_languages::table.filter(user_languages::user_id.eq(&user.id)))
+                .execute(&conn)?;
+            diesel::insert_into(user_languages::table)
+                .values(&languages)
+                // Batch inserts are only implemented for SQLite on the connection itself
+                .execute(&*conn)?;
+            users::table.find(&user.id).first::<models::User>(&conn)
+        });
+
+        // The unique index is case insensitive, so this also catches differently cased duplicates
+        let updated = match updated {
+            Err(err) if is_unique_violation(&err) => {
+                return Err(ErrorInfo::new("users", "USERNAME_TAKEN")
+                    .with_metadata("username", username)
+                    .into_status(Code::AlreadyExists, format!("Username {} is already taken", username)));
+            }
+            res => res.into_status()?
+        };
+        load_profile(&conn, updated).map(Response::new)
+    }
+
+    async fn get_user_stats(&self, request: Request<UserStatsRequest>) -> Result<Response<UserStats>, Status> {
+        use crate::db::schema::users::dsl::*;
+
+        let req = request.into_inner();
+        let conn = self.db()?;
+        let user = users
+            .find(&req.user_id)
+            .load::<models::User>(&conn)
+            .into_status()?
+            .pop()
+            .ok_or_else(|| Status::not_found("User not found"))?;
+
+        // Stats are public, so leave out anything private
+        let profile = User {
+            email: String::new(),
+            ..load_profile(&conn, user)?
+        };
+        Ok(Response::new(UserStats {
+            user: Some(profile),
+            ..stats::user_stats(&conn, &req.user_id)?
+        }))
+    }
+
+    async fn export_my_data(&self, request: Request<ExportRequest>) -> Result<Response<DataExport>, Status> {
+        let conn = self.db()?;
+        let user = get_user(&request, &conn)?;
+        let pictures = match &user.picture {
+            Some(picture_id) => self.picture_files(picture_id).await?,
+            None => Vec::new()
+        };
+        let changes = account::authored_changes(&conn, &user.id)?;
+        let file_name = format!("subunity-{}.zip", user.username);
+        let profile = load_profile(&conn, user)?;
+
+        Ok(Response::new(DataExport {
+            archive: account::export_archive(&profile, &changes, pictures)?,
+            file_name
+        }))
+    }
+
+    async fn delete_account(&self, request: Request<DeleteAccountRequest>) -> Result<Response<DeleteAccountResponse>, Status> {
+        let conn = self.db()?;
+        let user = get_user(&request, &conn)?;
+        if request.get_ref().confirm_username != user.username {
+            return Err(invalid_field("confirmUsername", "Enter your username to confirm deleting your account"));
+        }
+
+        // Pictures go first, so a failure leaves the account in place to retry instead of
+        // orphaning the files
+        if let Some(picture_id) = &user.picture {
+            self.remove_images(picture_id).await?;
+        }
+        account::delete_user(&conn, &user.id)?;
+
+        Ok(Response::new(DeleteAccountResponse {}))
+    }
+
+    async fn create_api_token(&self, request: Request<CreateApiTokenRequest>) -> Result<Response<CreatedApiToken>, Status> {
+        let conn = self.db()?;
+        let user = get_user(&request, &conn)?;
+        let req = request.into_inner();
+        validate_token_request(&req)?;
+        if tokens::list(&conn, &user.id)?.len() >= MAX_API_TOKENS {
+            return Err(Status::resource_exhausted(format!("Can't have more than {} API tokens", MAX_API_TOKENS)));
+        }
+
+        let mut scopes = req.scopes;
+        scopes.sort();
+        scopes.dedup();
+        let lifetime = match req.expires_in_days {
+            0 => DEFAULT_LIFETIME_DAYS,
+            days => days
+        };
+        let (token, secret) = tokens::create(&conn, &user.id, req.name.trim(), &scopes, lifetime)?;
+
+        Ok(Response::new(CreatedApiToken {
+            token: Some(token.into()),
+            secret
+        }))
+    }
+
+    async fn list_api_tokens(&self, request: Request<ListApiTokensRequest>) -> Result<Response<ApiTokenList>, Status> {
+        let conn = self.db()?;
+        let user = get_user(&request, &conn)?;
+        let tokens = tokens::list(&conn, &user.id)?;
+
+        Ok(Response::new(ApiTokenList {
+            tokens: tokens.into_iter().map(Into::into).collect()
+        }))
+    }
+
+    async fn revoke_api_token(&self, request: Request<RevokeApiTokenRequest>) -> Result<Response<RevokeApiTokenResponse>, Status> {
+        let conn = self.db()?;
+        let user = get_user(&request, &conn)?;
+        tokens::revoke(&conn, &user.id, &request.get_ref().id)?;
+
+        Ok(Response::new(RevokeApiTokenResponse {}))
+    }
+
+    async fn get_profile_picture(
+        &self,
+        request: Request<ProfilePictureRequest>
+    ) -> Result<Response<ProfilePicture>, Status> {
+        use crate::db::schema::users::dsl::*;
+
+        let req = request.into_inner();
+        let format = req.format();
+        let size = avatars::closest_size(req.size);
+        let user = users
+            .find(&req.user_id)
+            .load::<models::User>(&self.db()?)
+            .into_status()?
+            .pop()
+            .ok_or_else(|| Status::not_found("User not found"))?;
+
+        // A missing blob is treated like no picture so the default avatar is shown instead
+        // Each variant gets its own version so they can be told apart in ETags
+        let image = match &user.picture {
+            Some(picture_id) => self.load_image(picture_id, size, format).await?.map(|(content, format)| {
+                let version = format!("{}-{}-{}", picture_id, size, avatars::extension(format));
+                (content, format, version)
+            }),
+            None => None
+        };
+        let (content, format, version) = image.unwrap_or_else(|| (Vec::new(), format, String::new()));
+
+        Ok(Response::new(ProfilePicture {
+            content,
+            content_type: avatars::content_type(format).to_string(),
+            version,
+            username: user.username
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn derives_valid_usernames_from_names() {
+        assert_eq!(username_from_name("jane.doe"), "jane.doe");
+        assert_eq!(username_from_name("  Jane   Doe (admin) "), "Jane-Doe-admin");
+        assert_eq!(username_from_name("José Müller"), "Jos-M-ller");
+        assert_eq!(username_from_name("山田太郎"), "user");
+        assert_eq!(username_from_name(""), "user");
+        assert_eq!(username_from_name(&"a".repeat(40)).len(), 32);
+
+        let suffixed = username_with_suffix("Jane Doe With A Very Long Display Name", "f81d4fae-7dec-11d0");
+        assert_eq!(suffixed, "Jane-Doe-With-A-Very-Lo-f81d4fae");
+        assert_eq!(username_with_suffix("山田太郎", "auth0|123"), "user-auth0123");
+        for username in &[username_from_name("  Jane   Doe (admin) "), suffixed, username_with_suffix("山田太郎", "auth0|123")] {
+            assert!(validate_username(username).is_ok(), "{}", username);
+        }
     }
 }
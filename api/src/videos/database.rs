@@ -0,0 +1,76 @@
+use super::{VideoInfo, VideoInfoProvider};
+use crate::{db::models::VideoMetadata, Database, IntoStatus};
+use async_trait::async_trait;
+use diesel::{QueryDsl, RunQueryDsl};
+use tonic::Status;
+
+/// Metadata curated in the `video_metadata` table, for deployments that manage their catalogue
+/// in the database instead of a file.
+pub struct DatabaseProvider {
+    db: Database
+}
+
+impl DatabaseProvider {
+    pub fn new(db: Database) -> Self {
+        Self { db }
+    }
+}
+
+#[async_trait]
+impl VideoInfoProvider for DatabaseProvider {
+    async fn video_info(&self, video_id: &str) -> Result<VideoInfo, Status> {
+        use crate::db::schema::video_metadata;
+
+        let conn = self.db.get().into_status()?;
+        let metadata = video_metadata::table.find(video_id)
+            .load::<VideoMetadata>(&conn)
+            .into_status()?
+            .pop()
+            .ok_or_else(|| Status::not_found("Video not found"))?;
+        let thumbnails = serde_json::from_str(&metadata.thumbnails_json)
+            .map_err(|e| Status::internal(format!("Invalid thumbnails for video {}: {}", video_id, e)))?;
+
+        Ok(VideoInfo {
+            title: metadata.title,
+            channel_id: metadata.channel_id,
+            channel_title: metadata.channel_title,
+            duration: metadata.duration,
+            thumbnails
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use diesel::{r2d2::{ConnectionManager, Pool}, SqliteConnection};
+
+    #[tokio::test]
+    async fn reads_curated_videos() {
+        use crate::db::schema::video_metadata;
+
+        let manager = ConnectionManager::<SqliteConnection>::new(":memory:");
+        let db = Pool::builder().max_size(1).build(manager).unwrap();
+        crate::embedded_migrations::run(&db.get().unwrap()).unwrap();
+        diesel::insert_into(video_metadata::table)
+            .values(&VideoMetadata {
+                id: "talk-1".to_string(),
+                title: "Opening talk".to_string(),
+                channel_id: "conf".to_string(),
+                channel_title: "Conference".to_string(),
+                duration: "PT45M".to_string(),
+                thumbnails_json: r#"{"default": {"url": "https://example.com/1.jpg"}}"#.to_string()
+            })
+            .execute(&db.get().unwrap())
+            .unwrap();
+        let provider = DatabaseProvider::new(db);
+
+        let info = provider.video_info("talk-1").await.unwrap();
+        assert_eq!(info.title, "Opening talk");
+        assert_eq!(info.duration, "PT45M");
+        assert_eq!(info.thumbnails["default"].url, "https://example.com/1.jpg");
+
+        let status = provider.video_info("talk-2").await.unwrap_err();
+        assert_eq!(status.code(), tonic::Code::NotFound);
+    }
+}
@@ -0,0 +1,107 @@
+use super::{VideoInfo, VideoInfoProvider};
+use async_trait::async_trait;
+use std::{collections::HashMap, error::Error, fs, path::Path};
+use tonic::Status;
+
+/// Metadata from a local file keyed by video id, for offline deployments and tests.
+///
+/// ```toml
+/// [dQw4w9WgXcQ]
+/// title = "Never Gonna Give You Up"
+/// channel_id = "UCuAXFkgsw1L7xaCfnd5JJOw"
+/// channel_title = "Rick Astley"
+/// duration = "PT3M33S"
+/// ```
+///
+/// JSON files with the same structure work too.
+pub struct LocalProvider {
+    videos: HashMap<String, VideoInfo>
+}
+
+impl LocalProvider {
+    pub fn new(videos: HashMap<String, VideoInfo>) -> Self {
+        Self { videos }
+    }
+
+    /// Loads `.json` files as JSON and anything else as TOML.
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, Box<dyn Error>> {
+        let path = path.as_ref();
+        let contents = fs::read_to_string(path)
+            .map_err(|e| format!("Couldn't read video metadata from {}: {}", path.display(), e))?;
+        let videos = match path.extension().and_then(|ext| ext.to_str()) {
+            Some("json") => serde_json::from_str(&contents)?,
+            _ => toml::from_str(&contents)?
+        };
+        Ok(Self::new(videos))
+    }
+}
+
+#[async_trait]
+impl VideoInfoProvider for LocalProvider {
+    async fn video_info(&self, video_id: &str) -> Result<VideoInfo, Status> {
+        self.videos.get(video_id)
+            .cloned()
+            .ok_or_else(|| Status::not_found("Video not found"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use tonic::Code;
+    use uuid::Uuid;
+
+    /// Writes `contents` to a fresh file in the temp directory.
+    fn metadata_file(extension: &str, contents: &str) -> std::path::PathBuf {
+        let path = env::temp_dir().join(format!("videos-{}.{}", Uuid::new_v4(), extension));
+        fs::write(&path, contents).unwrap();
+        path
+    }
+
+    async fn assert_serves(path: &Path) {
+        let provider = LocalProvider::from_file(path).unwrap();
+        fs::remove_file(path).unwrap();
+
+        let info = provider.video_info("dQw4w9WgXcQ").await.unwrap();
+        assert_eq!(info.title, "Never Gonna Give You Up");
+        assert_eq!(info.channel_title, "Rick Astley");
+        assert_eq!(info.duration, "PT3M33S");
+        assert_eq!(provider.video_info("unknown").await.unwrap_err().code(), Code::NotFound);
+    }
+
+    #[tokio::test]
+    async fn serves_videos_from_toml() {
+        let path = metadata_file("toml", r#"
+            [dQw4w9WgXcQ]
+            title = "Never Gonna Give You Up"
+            channel_id = "UCuAXFkgsw1L7xaCfnd5JJOw"
+            channel_title = "Rick Astley"
+            duration = "PT3M33S"
+        "#);
+        assert_serves(&path).await;
+    }
+
+    #[tokio::test]
+    async fn serves_videos_from_json() {
+        let path = metadata_file("json", r#"{
+            "dQw4w9WgXcQ": {
+                "title": "Never Gonna Give You Up",
+                "channel_id": "UCuAXFkgsw1L7xaCfnd5JJOw",
+                "channel_title": "Rick Astley",
+                "duration": "PT3M33S"
+            }
+        }"#);
+        assert_serves(&path).await;
+    }
+
+    #[test]
+    fn reports_unreadable_files() {
+        let missing = env::temp_dir().join(format!("videos-{}.toml", Uuid::new_v4()));
+        assert!(LocalProvider::from_file(&missing).is_err());
+
+        let path = metadata_file("json", "not json");
+        assert!(LocalProvider::from_file(&path).is_err());
+        fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,331 @@
+//! Cached video metadata.
+//!
+//! Videos are fetched from the configured [`VideoInfoProvider`] once and then served from the
+//! `videos` table. Entries older than [`TTL_HOURS`] are still served, but refreshed in the
+//! background so the next request gets fresh data without waiting on the provider.
+//!
+//! Besides YouTube, videos can come from Vimeo or be plain media URLs. Those have to be added
+//! with [`add_video`] first, which assigns them an id of their own. YouTube videos keep using
+//! their YouTube id so existing tracks stay valid.
+
+use crate::{
+    db::models::{NewVideo, Video},
+    settings::{VideoProvider, Videos},
+    Database, DbConnection, IntoStatus, State
+};
+use api_types::subtitles::{self as proto, video::Source, AddVideoRequest};
+use async_trait::async_trait;
+use chrono::{Duration, Utc};
+use diesel::{ExpressionMethods, QueryDsl, RunQueryDsl};
+use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, env, error::Error, sync::Arc};
+use tonic::Status;
+use url::Url;
+use uuid::Uuid;
+
+mod database;
+mod local;
+mod vimeo;
+mod youtube;
+
+pub use database::DatabaseProvider;
+pub use local::LocalProvider;
+pub use youtube::YouTubeProvider;
+
+const TTL_HOURS: i64 = 24;
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct VideoInfo {
+    pub title: String,
+    pub channel_id: String,
+    pub channel_title: String,
+    /// ISO-8601 duration, e.g. `PT4M13S`
+    #[serde(default)]
+    pub duration: String,
+    #[serde(default)]
+    pub thumbnails: HashMap<String, Thumbnail>
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct Thumbnail {
+    pub url: String,
+    #[serde(default)]
+    pub width: u32,
+    #[serde(default)]
+    pub height: u32
+}
+
+#[async_trait]
+pub trait VideoInfoProvider: Send + Sync {
+    async fn video_info(&self, video_id: &str) -> Result<VideoInfo, Status>;
+}
+
+/// Builds the provider from settings, failing on startup instead of on the first request if
+/// it's misconfigured.
+pub fn provider(settings: &Videos, db: &Database) -> Result<Box<dyn VideoInfoProvider>, Box<dyn Error>> {
+    match settings.provider {
+        VideoProvider::YouTube => {
+            let api_key = Some(settings.api_key.clone())
+                .filter(|key| !key.is_empty())
+                .or_else(|| env::var("GOOGLE_API_KEY").ok())
+                .ok_or("Missing YouTube API key, set videos.api_key or GOOGLE_API_KEY")?;
+            Ok(Box::new(YouTubeProvider::new(&settings.base_url, api_key)))
+        }
+        VideoProvider::Local => Ok(Box::new(LocalProvider::from_file(&settings.path)?)),
+        VideoProvider::Database => Ok(Box::new(DatabaseProvider::new(db.clone())))
+    }
+}
+
+/// Name of a source in the `videos.source` column.
+fn source_name(source: Source) -> &'static str {
+    match source {
+        Source::YouTube => "youtube",
+        Source::Vimeo => "vimeo",
+        Source::Url => "url"
+    }
+}
+
+pub fn source_of(video: &Video) -> Source {
+    match &*video.source {
+        "vimeo" => Source::Vimeo,
+        "url" => Source::Url,
+        _ => Source::YouTube
+    }
+}
+
+/// Parses ISO-8601 durations as used by YouTube (`PT1H2M3S`, `P1DT2H`). Years and months
+/// aren't supported since they don't have a fixed length.
+pub fn parse_duration(duration: &str) -> Option<f32> {
+    let chars = duration.strip_prefix('P')?.chars();
+    let mut seconds = 0.;
+    let mut number = String::new();
+    let mut in_time = false;
+    // Both the duration and its time part need at least one component
+    let mut components = 0;
+    let mut time_components = 0;
+
+    for c in chars {
+        let unit = match c {
+            '0'..='9' | '.' => {
+                number.push(c);
+                continue;
+            }
+            'T' if !in_time && number.is_empty() => {
+                in_time = true;
+                continue;
+            }
+            'W' if !in_time => 604_800.,
+            'D' if !in_time => 86_400.,
+            'H' if in_time => 3_600.,
+            'M' if in_time => 60.,
+            'S' if in_time => 1.,
+            _ => return None
+        };
+        seconds += number.parse::<f32>().ok()? * unit;
+        number.clear();
+        components += 1;
+        if in_time {
+            time_components += 1;
+        }
+    }
+
+    let complete = number.is_empty() && components > 0 && (!in_time || time_components > 0);
+    Some(seconds).filter(|_| complete)
+}
+
+/// Duration of the video in seconds, `None` if it's unknown or a live stream.
+pub fn duration_seconds(video: &Video) -> Option<f32> {
+    parse_duration(&video.duration).filter(|seconds| *seconds > 0.)
+}
+
+impl From<Video> for proto::Video {
+    fn from(video: Video) -> Self {
+        proto::Video {
+            source: source_of(&video) as i32,
+            id: video.id,
+            source_id: video.source_id,
+            title: video.title,
+            channel_id: video.channel_id,
+            channel_title: video.channel_title,
+            duration: video.duration
+        }
+    }
+}
+
+pub fn find_video(conn: &DbConnection, video_id: &str) -> Result<Option<Video>, Status> {
+    use crate::db::schema::videos;
+
+    Ok(videos::table.find(video_id)
+        .load::<Video>(conn)
+        .into_status()?
+        .pop())
+}
+
+fn find_by_source(conn: &DbConnection, video_source: Source, video_source_id: &str) -> Result<Option<Video>, Status> {
+    use crate::db::schema::videos::dsl::*;
+
+    Ok(videos.filter(source.eq(source_name(video_source)))
+        .filter(source_id.eq(video_source_id))
+        .load::<Video>(conn)
+        .into_status()?
+        .pop())
+}
+
+fn save_video(
+    conn: &DbConnection,
+    video_id: &str,
+    source: Source,
+    source_id: &str,
+    info: &VideoInfo
+) -> Result<Video, Status> {
+    use crate::db::schema::videos;
+
+    let now = Utc::now().naive_utc();
+    let thumbnails_json = serde_json::to_string(&info.thumbnails).unwrap();
+    let new = NewVideo {
+        id: video_id,
+        title: &info.title,
+        channel_id: &info.channel_id,
+        channel_title: &info.channel_title,
+        duration: &info.duration,
+        thumbnails_json: &thumbnails_json,
+        fetched_at: &now,
+        source: source_name(source),
+        source_id
+    };
+    diesel::replace_into(videos::table)
+        .values(&new)
+        .execute(conn)
+        .into_status()?;
+
+    find_video(conn, video_id)?
+        .ok_or_else(|| Status::internal("Video wasn't saved"))
+}
+
+/// Marks a stale video as being refreshed. Only one of several concurrent requests for the same
+/// video wins, so the provider is called once per expiry.
+fn claim_refresh(conn: &DbConnection, video: &Video) -> Result<bool, Status> {
+    use crate::db::schema::videos::dsl::*;
+
+    let claimed = diesel::update(videos.filter(id.eq(&video.id)).filter(fetched_at.eq(&video.fetched_at)))
+        .set(fetched_at.eq(Utc::now().naive_utc()))
+        .execute(conn)
+        .into_status()?;
+    Ok(claimed > 0)
+}
+
+/// Media URLs have nothing to refresh from, their metadata is whatever they were added with.
+fn is_stale(video: &Video) -> bool {
+    source_of(video) != Source::Url
+        && Utc::now().naive_utc() - video.fetched_at > Duration::hours(TTL_HOURS)
+}
+
+async fn fetch_info(state: &State, source: Source, source_id: &str) -> Result<VideoInfo, Status> {
+    match source {
+        Source::YouTube => state.videos.video_info(source_id).await,
+        Source::Vimeo => vimeo::video_info(source_id).await,
+        Source::Url => Err(Status::invalid_argument("Media URLs have no metadata to fetch"))
+    }
+}
+
+async fn refresh_video(state: Arc<State>, video_id: String, source: Source, source_id: String) {
+    let result = match fetch_info(&state, source, &source_id).await {
+        Ok(info) => state.db().and_then(|conn| save_video(&conn, &video_id, source, &source_id, &info)),
+        Err(status) => Err(status)
+    };
+    if let Err(status) = result {
+        eprintln!("Failed to refresh video {}: {}", video_id, status.message());
+    }
+}
+
+/// Gets the metadata for a video, only calling the provider if it's not cached yet.
+pub async fn get_video(state: &Arc<State>, video_id: &str) -> Result<Video, Status> {
+    let conn = state.db()?;
+    match find_video(&conn, video_id)? {
+        Some(video) => {
+            if is_stale(&video) && claim_refresh(&conn, &video)? {
+                let refresh = refresh_video(state.clone(), video.id.clone(), source_of(&video), video.source_id.clone());
+                tokio::spawn(refresh);
+            }
+            Ok(video)
+        }
+        // Anything that wasn't added explicitly is a YouTube id
+        None => {
+            let info = state.videos.video_info(video_id).await?;
+            save_video(&conn, video_id, Source::YouTube, video_id, &info)
+        }
+    }
+}
+
+/// Registers a video from any source, returning the existing one if it was added before.
+pub async fn add_video(state: &Arc<State>, request: AddVideoRequest) -> Result<Video, Status> {
+    let source = Source::from_i32(request.source)
+        .ok_or_else(|| Status::invalid_argument("Unknown video source"))?;
+    let source_id = request.source_id.trim();
+
+    let video_id = match source {
+        Source::YouTube => return get_video(state, source_id).await,
+        Source::Vimeo if !source_id.is_empty() && source_id.chars().all(|c| c.is_ascii_digit()) => {
+            format!("vimeo-{}", source_id)
+        }
+        Source::Vimeo => return Err(Status::invalid_argument("Vimeo ids are numeric")),
+        Source::Url => {
+            let url = Url::parse(source_id)
+                .map_err(|e| Status::invalid_argument(format!("Invalid media URL: {}", e)))?;
+            if url.scheme() != "http" && url.scheme() != "https" {
+                return Err(Status::invalid_argument("Media URLs must be http or https"));
+            }
+            Uuid::new_v4().to_simple().to_string()
+        }
+    };
+
+    let conn = state.db()?;
+    if let Some(existing) = find_by_source(&conn, source, source_id)? {
+        return Ok(existing);
+    }
+
+    let title = request.title.trim();
+    let info = match source {
+        Source::Url if title.is_empty() => return Err(Status::invalid_argument("Media URLs need a title")),
+        Source::Url => VideoInfo {
+            title: title.to_string(),
+            channel_id: String::new(),
+            channel_title: String::new(),
+            duration: String::new(),
+            thumbnails: HashMap::new()
+        },
+        _ => {
+            let mut info = fetch_info(state, source, source_id).await?;
+            if !title.is_empty() {
+                info.title = title.to_string();
+            }
+            info
+        }
+    };
+    save_video(&conn, &video_id, source, source_id, &info)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_durations() {
+        assert_eq!(parse_duration("PT4M13S"), Some(253.));
+        assert_eq!(parse_duration("PT1H2M3S"), Some(3723.));
+        assert_eq!(parse_duration("PT1H"), Some(3600.));
+        assert_eq!(parse_duration("PT0.5S"), Some(0.5));
+        assert_eq!(parse_duration("P1DT2H"), Some(93_600.));
+        assert_eq!(parse_duration("P2W"), Some(1_209_600.));
+        // Live streams
+        assert_eq!(parse_duration("P0D"), Some(0.));
+    }
+
+    #[test]
+    fn rejects_invalid_durations() {
+        for duration in &["", "4M13S", "PT", "P1M", "P1Y", "PT1D", "P1H", "PT1H2", "PTT1S", "PT1X", "PT1.2.3S"] {
+            assert_eq!(parse_duration(duration), None, "{}", duration);
+        }
+    }
+}
@@ -0,0 +1,62 @@
+use super::{Thumbnail, VideoInfo};
+use reqwest::StatusCode;
+use serde::Deserialize;
+use std::collections::HashMap;
+use tonic::Status;
+
+/// Vimeo's oEmbed endpoint works without an API key for public and unlisted videos.
+const OEMBED_URL: &str = "https://vimeo.com/api/oembed.json";
+
+#[derive(Deserialize)]
+struct OEmbed {
+    title: String,
+    #[serde(default)]
+    author_name: String,
+    #[serde(default)]
+    author_url: String,
+    /// In seconds
+    #[serde(default)]
+    duration: u64,
+    thumbnail_url: Option<String>,
+    #[serde(default)]
+    thumbnail_width: u32,
+    #[serde(default)]
+    thumbnail_height: u32
+}
+
+pub async fn video_info(video_id: &str) -> Result<VideoInfo, Status> {
+    let video_url = format!("https://vimeo.com/{}", video_id);
+    let response = reqwest::Client::new()
+        .get(OEMBED_URL)
+        .query(&[("url", &video_url)])
+        .send()
+        .await
+        .map_err(|e| Status::unavailable(format!("Couldn't reach Vimeo: {}", e)))?;
+
+    match response.status() {
+        status if status.is_success() => {}
+        StatusCode::NOT_FOUND | StatusCode::FORBIDDEN => return Err(Status::not_found("Video not found")),
+        status => return Err(Status::unavailable(format!("Vimeo returned {}", status)))
+    }
+
+    let oembed = response.json::<OEmbed>()
+        .await
+        .map_err(|e| Status::internal(format!("{}", e)))?;
+
+    let mut thumbnails = HashMap::new();
+    if let Some(url) = oembed.thumbnail_url {
+        thumbnails.insert("default".to_string(), Thumbnail {
+            url,
+            width: oembed.thumbnail_width,
+            height: oembed.thumbnail_height
+        });
+    }
+
+    Ok(VideoInfo {
+        title: oembed.title,
+        channel_id: oembed.author_url,
+        channel_title: oembed.author_name,
+        duration: format!("PT{}S", oembed.duration),
+        thumbnails
+    })
+}
@@ -0,0 +1,163 @@
+use super::{Thumbnail, VideoInfo, VideoInfoProvider};
+use async_trait::async_trait;
+use reqwest::{Client, StatusCode};
+use serde::Deserialize;
+use std::collections::HashMap;
+use tonic::Status;
+
+/// Metadata from the YouTube Data API v3.
+pub struct YouTubeProvider {
+    client: Client,
+    base_url: String,
+    api_key: String
+}
+
+#[derive(Deserialize)]
+struct VideoListResponse {
+    items: Vec<Video>
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Video {
+    snippet: Snippet,
+    content_details: ContentDetails
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Snippet {
+    title: String,
+    channel_id: String,
+    channel_title: String,
+    #[serde(default)]
+    thumbnails: HashMap<String, Thumbnail>
+}
+
+#[derive(Deserialize)]
+struct ContentDetails {
+    duration: String
+}
+
+impl YouTubeProvider {
+    /// `base_url` is the API root, `https://www.googleapis.com/youtube/v3` for the real API.
+    pub fn new(base_url: &str, api_key: String) -> Self {
+        Self {
+            client: Client::new(),
+            base_url: base_url.trim_end_matches('/').to_string(),
+            api_key
+        }
+    }
+}
+
+#[async_trait]
+impl VideoInfoProvider for YouTubeProvider {
+    async fn video_info(&self, video_id: &str) -> Result<VideoInfo, Status> {
+        let response = self.client.get(&format!("{}/videos", self.base_url))
+            .query(&[("part", "snippet,contentDetails"), ("id", video_id), ("key", &self.api_key)])
+            .send()
+            .await
+            .map_err(|e| Status::unavailable(format!("Couldn't reach the YouTube API: {}", e)))?;
+
+        match response.status() {
+            status if status.is_success() => {}
+            StatusCode::FORBIDDEN => return Err(Status::resource_exhausted("YouTube API key rejected or out of quota")),
+            status => return Err(Status::unavailable(format!("YouTube API returned {}", status)))
+        }
+
+        let mut list = response.json::<VideoListResponse>()
+            .await
+            .map_err(|e| Status::internal(format!("{}", e)))?;
+        let video = list.items.pop().ok_or_else(|| Status::not_found("Video not found"))?;
+
+        Ok(VideoInfo {
+            title: video.snippet.title,
+            channel_id: video.snippet.channel_id,
+            channel_title: video.snippet.channel_title,
+            duration: video.content_details.duration,
+            thumbnails: video.snippet.thumbnails
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use std::net::SocketAddr;
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::TcpListener
+    };
+    use tonic::Code;
+
+    const API_KEY: &str = "test-key";
+
+    /// Stands in for the Data API under `/youtube/v3`, knowing a single video. Returns the base
+    /// URL with a trailing slash like a hand-written setting might have.
+    async fn mock_api() -> String {
+        let mut listener = TcpListener::bind(SocketAddr::from(([127, 0, 0, 1], 0))).await.unwrap();
+        let base_url = format!("http://{}/youtube/v3/", listener.local_addr().unwrap());
+        let video = json!({
+            "snippet": {
+                "title": "Never Gonna Give You Up",
+                "channelId": "UCuAXFkgsw1L7xaCfnd5JJOw",
+                "channelTitle": "Rick Astley",
+                "thumbnails": {"default": {"url": "https://i.ytimg.com/vi/dQw4w9WgXcQ/default.jpg", "width": 120, "height": 90}}
+            },
+            "contentDetails": {"duration": "PT3M33S"}
+        });
+
+        tokio::spawn(async move {
+            loop {
+                let (mut socket, _) = listener.accept().await.unwrap();
+                let mut buffer = [0; 4096];
+                let read = socket.read(&mut buffer).await.unwrap();
+                let request = String::from_utf8_lossy(&buffer[..read]);
+                let request_line = request.lines().next().unwrap_or_default();
+                let (status, body) = if !request_line.starts_with("GET /youtube/v3/videos?") {
+                    ("404 Not Found", String::new())
+                } else if !request_line.contains(&format!("key={}", API_KEY)) {
+                    ("403 Forbidden", String::new())
+                } else if request_line.contains("id=dQw4w9WgXcQ") {
+                    ("200 OK", json!({ "items": [video] }).to_string())
+                } else {
+                    ("200 OK", json!({ "items": [] }).to_string())
+                };
+                let response = format!(
+                    "HTTP/1.1 {}\r\ncontent-type: application/json\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{}",
+                    status,
+                    body.len(),
+                    body
+                );
+                socket.write_all(response.as_bytes()).await.unwrap();
+            }
+        });
+        base_url
+    }
+
+    #[tokio::test]
+    async fn fetches_from_base_url() {
+        let provider = YouTubeProvider::new(&mock_api().await, API_KEY.to_string());
+        let info = provider.video_info("dQw4w9WgXcQ").await.unwrap();
+        assert_eq!(info.title, "Never Gonna Give You Up");
+        assert_eq!(info.channel_id, "UCuAXFkgsw1L7xaCfnd5JJOw");
+        assert_eq!(info.channel_title, "Rick Astley");
+        assert_eq!(info.duration, "PT3M33S");
+        assert_eq!(info.thumbnails["default"].width, 120);
+    }
+
+    #[tokio::test]
+    async fn maps_api_errors() {
+        let base_url = mock_api().await;
+        let provider = YouTubeProvider::new(&base_url, API_KEY.to_string());
+        assert_eq!(provider.video_info("unknown").await.unwrap_err().code(), Code::NotFound);
+
+        let provider = YouTubeProvider::new(&base_url, "revoked-key".to_string());
+        assert_eq!(provider.video_info("dQw4w9WgXcQ").await.unwrap_err().code(), Code::ResourceExhausted);
+
+        // Nothing listens on the discard port
+        let provider = YouTubeProvider::new("http://127.0.0.1:9", API_KEY.to_string());
+        assert_eq!(provider.video_info("dQw4w9WgXcQ").await.unwrap_err().code(), Code::Unavailable);
+    }
+}
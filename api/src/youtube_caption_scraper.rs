@@ -1,97 +1,221 @@
-use regex::{Regex, RegexBuilder, Match};
 use serde::Deserialize;
-use htmlescape::decode_html;
-use api_types::subtitles::subtitles::Entry;
-use api_types::subtitles::Subtitles;
-use crate::subtitles::get_video_info;
+use api_types::subtitles::subtitles::{Entry, Word};
+use api_types::subtitles::SourceCaption;
+use api_types::errors::ErrorInfo;
+use crate::timedtext::{self, ParseError};
 use itertools::Itertools;
+use reqwest::Client;
+use std::fmt::{self, Display};
+use tonic::{Code, Status};
+
+const ERROR_DOMAIN: &str = "captions";
+
+#[derive(Debug)]
+pub enum ScraperError {
+    Network(reqwest::Error),
+    NoTracks,
+    LanguageMissing(String),
+    Parse(String)
+}
+
+impl Display for ScraperError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ScraperError::Network(err) => write!(f, "Couldn't reach YouTube: {}", err),
+            ScraperError::NoTracks => write!(f, "The video has no captions"),
+            ScraperError::LanguageMissing(lang) => write!(f, "The video has no captions for {}", lang),
+            ScraperError::Parse(err) => write!(f, "Couldn't parse captions: {}", err)
+        }
+    }
+}
+
+impl std::error::Error for ScraperError {}
+
+impl From<reqwest::Error> for ScraperError {
+    fn from(err: reqwest::Error) -> Self {
+        ScraperError::Network(err)
+    }
+}
+
+impl From<ParseError> for ScraperError {
+    fn from(err: ParseError) -> Self {
+        ScraperError::Parse(err.to_string())
+    }
+}
+
+impl From<ScraperError> for Status {
+    fn from(err: ScraperError) -> Self {
+        let message = err.to_string();
+        let (code, info) = match err {
+            ScraperError::Network(_) => (Code::Unavailable, ErrorInfo::new(ERROR_DOMAIN, "NETWORK")),
+            ScraperError::NoTracks => (Code::NotFound, ErrorInfo::new(ERROR_DOMAIN, "NO_TRACKS")),
+            ScraperError::LanguageMissing(lang) => (
+                Code::NotFound,
+                ErrorInfo::new(ERROR_DOMAIN, "LANGUAGE_MISSING").with_metadata("language", lang)
+            ),
+            ScraperError::Parse(_) => (Code::Internal, ErrorInfo::new(ERROR_DOMAIN, "PARSE_ERROR"))
+        };
+        info.into_status(code, message)
+    }
+}
+
+#[derive(Deserialize, Debug)]
+struct PlayerResponse {
+    #[serde(default)]
+    captions: Option<Captions>
+}
+
+#[derive(Deserialize, Debug)]
+struct Captions {
+    #[serde(rename = "playerCaptionsTracklistRenderer")]
+    tracklist: CaptionTracks
+}
 
 #[derive(Deserialize, Debug)]
 struct CaptionTracks {
-    #[serde(rename = "captionTracks")]
+    #[serde(rename = "captionTracks", default)]
     caption_tracks: Vec<Track>
 }
 
 #[derive(Deserialize, Debug)]
-struct Track {
+pub struct Track {
     #[serde(rename = "baseUrl")]
     base_url: String,
     #[serde(rename = "languageCode")]
-    language_code: String
+    language_code: String,
+    #[serde(rename = "vssId", default)]
+    vss_id: String,
+    #[serde(default)]
+    name: TrackName,
+    #[serde(default)]
+    kind: Option<String>,
+    #[serde(rename = "isTranslatable", default)]
+    is_translatable: bool
 }
 
-pub async fn get_subtitles(video_id: &str, lang: &str) -> Option<Subtitles> {
-    println!("Getting subtitles");
-    let client = reqwest::Client::new();
+#[derive(Deserialize, Debug, Default)]
+struct TrackName {
+    #[serde(rename = "simpleText")]
+    simple_text: Option<String>,
+    #[serde(default)]
+    runs: Vec<TextRun>
+}
 
-    let video_info = client.get("https://youtube.com/get_video_info")
-        .query(&[("video_id", video_id)])
-        .send().await.ok()?
-        .text().await.ok()?;
+#[derive(Deserialize, Debug)]
+struct TextRun {
+    text: String
+}
 
-    println!("Finding the right bits");
-    let regex = Regex::new(r#"captionTracks.*?isTranslatable.*?(true|false)"#).unwrap();
-    let match0 = regex.find(&video_info)?;
-    println!("Decoding...");
-    let tracks = urldecode::decode(match0.as_str().to_string())
-        .replace("\\u0026", "&");
+impl Track {
+    fn is_auto_generated(&self) -> bool {
+        self.kind.as_deref() == Some("asr")
+    }
 
-    // "{" + parsed_string + "}]}"
-    let tracks = serde_json::from_str::<CaptionTracks>(&format!("{{\"{}}}]}}", tracks)).ok()?;
-    let matching_track = tracks.caption_tracks.into_iter()
-        .find(|track| &track.language_code == lang)?;
+    fn name(&self) -> String {
+        match &self.name.simple_text {
+            Some(text) => text.clone(),
+            None => self.name.runs.iter().map(|run| &*run.text).collect()
+        }
+    }
+}
 
-    let transcript = client.get(&matching_track.base_url)
-        .send().await.ok()?
-        .text().await.ok()?;
+impl From<Track> for SourceCaption {
+    fn from(track: Track) -> Self {
+        SourceCaption {
+            auto_generated: track.is_auto_generated(),
+            name: track.name(),
+            id: track.vss_id,
+            language: track.language_code,
+            translatable: track.is_translatable
+        }
+    }
+}
 
-    let start_regex = Regex::new(r#"start="([\d.]+)""#).unwrap();
-    let duration_regex = Regex::new(r#"dur="([\d.]+)"#).unwrap();
+/// Fetches every caption track YouTube has for the video, including auto-generated ones.
+pub async fn get_caption_tracks(client: &Client, video_id: &str) -> Result<Vec<Track>, ScraperError> {
+    let video_info = client.get("https://youtube.com/get_video_info")
+        .query(&[("video_id", video_id)])
+        .send().await?
+        .error_for_status()?
+        .text().await?;
+
+    let (_, player_response) = url::form_urlencoded::parse(video_info.as_bytes())
+        .find(|(key, _)| key == "player_response")
+        .ok_or_else(|| ScraperError::Parse("missing player response".to_string()))?;
+    let player_response = serde_json::from_str::<PlayerResponse>(&player_response)
+        .map_err(|e| ScraperError::Parse(format!("invalid player response: {}", e)))?;
+
+    Ok(player_response.captions
+        .map(|captions| captions.tracklist.caption_tracks)
+        .unwrap_or_default())
+}
+
+/// Picks the track to seed from. An explicit `source` matches the track id, otherwise manually
+/// created tracks in `lang` are preferred over auto-generated ones.
+fn select_track(tracks: Vec<Track>, lang: &str, source: Option<&str>) -> Option<Track> {
+    match source {
+        Some(source) => tracks.into_iter().find(|track| track.vss_id == source),
+        None => tracks.into_iter()
+            .filter(|track| track.language_code == lang)
+            .min_by_key(|track| track.is_auto_generated())
+    }
+}
 
-    let text_regex = Regex::new(r#"<text.+>"#).unwrap();
-    let amp_regex = RegexBuilder::new("&amp;").case_insensitive(true).build().unwrap();
-    let tag_regex = Regex::new(r#"</?[^>]+(>|$)"#).unwrap();
+pub async fn get_subtitles(video_id: &str, lang: &str, source: Option<&str>) -> Result<Vec<Entry>, ScraperError> {
+    println!("Getting subtitles");
+    let client = Client::new();
 
-    fn parse_unwrap(match_: Match) -> f32 {
-        match_.as_str().parse().unwrap()
+    let tracks = get_caption_tracks(&client, video_id).await?;
+    if tracks.is_empty() {
+        return Err(ScraperError::NoTracks);
     }
+    let matching_track = select_track(tracks, lang, source)
+        .ok_or_else(|| ScraperError::LanguageMissing(source.unwrap_or(lang).to_string()))?;
+
+    let transcript = client.get(&matching_track.base_url)
+        .query(&[("fmt", "srv3")])
+        .send().await?
+        .error_for_status()?
+        .text().await?;
 
     println!("Parsing...");
-    let entries = transcript
-        .replace(r#"<?xml version="1.0" encoding="utf-8" ?><transcript>"#, "")
-        .replace("</transcript>", "")
-        .split("</text>")
-        .filter(|line| !line.trim().is_empty())
-        .map(|line| {
-            let start = start_regex.captures(line)?.get(1)?;
-            let duration = duration_regex.captures(line)?.get(1)?;
-
-            let html_text = text_regex.replace(line, "");
-            let html_text = amp_regex.replace_all(&html_text, "&");
-            let html_text = tag_regex.replace_all(&html_text, "");
-            let text = decode_html(&html_text).ok()?;
-
-            //println!("start: {}, duration: {}, text: {}", start.as_str(), duration.as_str(), text);
-            Some((parse_unwrap(start), parse_unwrap(duration), text))
-        })
-        .map(|opt| opt.map(|(start, duration, text)| Entry {
-            start_seconds: start,
-            end_seconds: start + duration,
-            text
-        }))
-        .collect::<Option<Vec<_>>>()?;
-    let entries = entries.into_iter().chunks(2);
-
-    let entries = (&entries).into_iter()
+    let entries = timedtext::parse(&transcript)?
+        .into_iter()
+        .map(|cue| Entry {
+            start_seconds: cue.start_seconds,
+            end_seconds: cue.end_seconds,
+            text: cue.text,
+            words: cue.words
+                .into_iter()
+                .map(|word| Word {
+                    start_seconds: word.start_seconds,
+                    text: word.text
+                })
+                .collect(),
+            ..Default::default()
+        });
+    let entries = entries.chunks(2);
+
+    let entries: Vec<_> = (&entries).into_iter()
         .map(|mut pair| {
             let first = pair.next().unwrap();
             let second = pair.next();
 
             if let Some(second) = second {
+                // Keep word timings only if both lines have them, so they always cover the text
+                let words = if first.words.is_empty() || second.words.is_empty() {
+                    Vec::new()
+                } else {
+                    let mut second_words = second.words;
+                    second_words[0].text.insert(0, ' ');
+                    first.words.into_iter().chain(second_words).collect()
+                };
                 Entry {
                     start_seconds: first.start_seconds,
-                    end_seconds: first.end_seconds,
-                    text: format!("{} {}", first.text, second.text)
+                    end_seconds: first.end_seconds.max(second.end_seconds),
+                    text: format!("{} {}", first.text, second.text),
+                    words,
+                    ..Default::default()
                 }
             } else {
                 first
@@ -101,14 +225,5 @@ pub async fn get_subtitles(video_id: &str, lang: &str) -> Option<Subtitles> {
 
     println!("Done!");
 
-    let video_info = get_video_info(video_id).await.ok()?;
-
-    Some(Subtitles {
-        video_id: video_id.to_string(),
-        language: lang.to_string(),
-        entries,
-        video_title: video_info.snippet.title,
-        uploader_id: video_info.snippet.channel_id,
-        uploader_name: video_info.snippet.channel_title
-    })
+    Ok(entries)
 }
\ No newline at end of file
@@ -5,10 +5,26 @@ fn main() -> Result<(), Box<dyn Error>> {
         .out_dir("src/proto")
         .type_attribute(".", "#[derive(Serialize, Deserialize)]")
         .type_attribute(".", r#"#[serde(rename_all = "camelCase")]"#)
+        // Entries are stored as JSON, so fields added later have to be optional
+        .type_attribute("subtitles.Subtitles.Entry", "#[serde(default)]")
+        .type_attribute("subtitles.Subtitles", "#[serde(default)]")
+        .field_attribute(
+            "subtitles.Subtitles.Entry.words",
+            r#"#[serde(skip_serializing_if = "Vec::is_empty")]"#
+        )
+        .field_attribute(
+            "subtitles.Subtitles.Entry.speaker",
+            r#"#[serde(skip_serializing_if = "String::is_empty")]"#
+        )
+        .field_attribute(
+            "subtitles.Subtitles.Entry.notes",
+            r#"#[serde(skip_serializing_if = "String::is_empty")]"#
+        )
         //.type_attribute(".", "#[derive(Debug)]")
         .compile(&[
             "protos/user.proto",
-            "protos/subtitles.proto"
+            "protos/subtitles.proto",
+            "protos/errors.proto"
         ], &["protos"])?;
     Ok(())
 }
@@ -7,10 +7,105 @@ extern crate serde;
 mod proto;
 
 pub use proto::*;
-use crate::proto::subtitles::Chunk;
+use crate::proto::{
+    errors::{Any, ErrorInfo, RpcStatus},
+    subtitles::Chunk
+};
+use prost::Message;
+use std::collections::HashMap;
+use tonic::{Code, Status};
+
+const ERROR_INFO_TYPE: &str = "type.googleapis.com/google.rpc.ErrorInfo";
+
+fn encode(message: &impl Message) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(message.encoded_len());
+    message.encode(&mut bytes).expect("Vec has enough capacity");
+    bytes
+}
 
 impl AsRef<[u8]> for Chunk {
     fn as_ref(&self) -> &[u8] {
         &self.content
     }
-}
\ No newline at end of file
+}
+
+impl ErrorInfo {
+    pub fn new(domain: &str, reason: &str) -> Self {
+        ErrorInfo {
+            reason: reason.to_string(),
+            domain: domain.to_string(),
+            metadata: HashMap::new()
+        }
+    }
+
+    pub fn with_metadata(mut self, key: &str, value: impl Into<String>) -> Self {
+        self.metadata.insert(key.to_string(), value.into());
+        self
+    }
+
+    /// Builds a status carrying this info in its details, packed in a `google.rpc.Status` like
+    /// other gRPC implementations expect.
+    pub fn into_status(self, code: Code, message: impl Into<String>) -> Status {
+        let message = message.into();
+        let details = RpcStatus {
+            code: code as i32,
+            message: message.clone(),
+            details: vec![Any {
+                type_url: ERROR_INFO_TYPE.to_string(),
+                value: encode(&self)
+            }]
+        };
+        Status::with_details(code, message, encode(&details).into())
+    }
+
+    /// Reads the info from the details of a status, if it has any.
+    pub fn from_status(status: &Status) -> Option<Self> {
+        RpcStatus::decode(status.details())
+            .ok()?
+            .details
+            .into_iter()
+            .find(|detail| detail.type_url == ERROR_INFO_TYPE)
+            .and_then(|detail| ErrorInfo::decode(&*detail.value).ok())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn packs_info_in_rpc_status() {
+        let status = ErrorInfo::new("captions", "NO_TRACKS")
+            .with_metadata("videoId", "abc")
+            .into_status(Code::NotFound, "No captions");
+
+        let details = RpcStatus::decode(status.details()).unwrap();
+        assert_eq!(details.code, Code::NotFound as i32);
+        assert_eq!(details.message, "No captions");
+        assert_eq!(details.details[0].type_url, ERROR_INFO_TYPE);
+
+        let info = ErrorInfo::from_status(&status).unwrap();
+        assert_eq!(info.reason, "NO_TRACKS");
+        assert_eq!(info.metadata["videoId"], "abc");
+    }
+
+    #[test]
+    fn ignores_other_details() {
+        assert_eq!(ErrorInfo::from_status(&Status::not_found("No captions")), None);
+
+        let other = RpcStatus {
+            code: Code::NotFound as i32,
+            message: String::new(),
+            details: vec![Any {
+                type_url: "type.googleapis.com/google.rpc.DebugInfo".to_string(),
+                value: encode(&ErrorInfo::new("captions", "NO_TRACKS"))
+            }]
+        };
+        let status = Status::with_details(Code::NotFound, "No captions", encode(&other).into());
+        assert_eq!(ErrorInfo::from_status(&status), None);
+
+        // Bare info like statuses used to carry
+        let bare = Status::with_details(Code::NotFound, "No captions", encode(&ErrorInfo::new("captions", "NO_TRACKS")).into());
+        assert_eq!(ErrorInfo::from_status(&bare), None);
+    }
+}
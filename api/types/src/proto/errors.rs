@@ -0,0 +1,36 @@
+/// Machine readable details attached to an error status, wire compatible with
+/// google.rpc.ErrorInfo. Statuses carry it packed in an RpcStatus.
+#[derive(Clone, PartialEq, ::prost::Message, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ErrorInfo {
+    /// UPPER_SNAKE_CASE identifier of the error cause, e.g. NO_TRACKS.
+    #[prost(string, tag = "1")]
+    pub reason: std::string::String,
+    /// The logical grouping the reason belongs to, e.g. captions.
+    #[prost(string, tag = "2")]
+    pub domain: std::string::String,
+    #[prost(map = "string, string", tag = "3")]
+    pub metadata: ::std::collections::HashMap<std::string::String, std::string::String>,
+}
+/// Wire compatible with google.rpc.Status, the conventional content of the
+/// grpc-status-details-bin header.
+#[derive(Clone, PartialEq, ::prost::Message, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RpcStatus {
+    #[prost(int32, tag = "1")]
+    pub code: i32,
+    #[prost(string, tag = "2")]
+    pub message: std::string::String,
+    #[prost(message, repeated, tag = "3")]
+    pub details: ::std::vec::Vec<Any>,
+}
+/// Wire compatible with google.protobuf.Any.
+#[derive(Clone, PartialEq, ::prost::Message, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Any {
+    /// type.googleapis.com/ followed by the full name of the packed message.
+    #[prost(string, tag = "1")]
+    pub type_url: std::string::String,
+    #[prost(bytes, tag = "2")]
+    pub value: std::vec::Vec<u8>,
+}
@@ -15,8 +15,23 @@ pub mod download_request {
     #[serde(rename_all = "camelCase")]
     pub enum Format {
         Srt = 0,
+        Vtt = 1,
+        Ass = 2,
     }
 }
+/// Parses a subtitle file into entries without saving them
+#[derive(Clone, PartialEq, ::prost::Message, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImportRequest {
+    #[prost(string, tag = "1")]
+    pub video_id: std::string::String,
+    #[prost(string, tag = "2")]
+    pub language: std::string::String,
+    #[prost(enumeration = "download_request::Format", tag = "3")]
+    pub format: i32,
+    #[prost(bytes, tag = "4")]
+    pub content: std::vec::Vec<u8>,
+}
 #[derive(Clone, PartialEq, ::prost::Message, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Chunk {
@@ -30,9 +45,83 @@ pub struct SubtitleId {
     pub video_id: std::string::String,
     #[prost(string, tag = "2")]
     pub language: std::string::String,
+    /// Id of the source caption track to seed a new track from. Defaults to the
+    /// track matching `language`.
+    #[prost(string, tag = "3")]
+    pub source: std::string::String,
 }
 #[derive(Clone, PartialEq, ::prost::Message, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
+pub struct VideoId {
+    #[prost(string, tag = "1")]
+    pub video_id: std::string::String,
+}
+#[derive(Clone, PartialEq, ::prost::Message, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Video {
+    #[prost(string, tag = "1")]
+    pub id: std::string::String,
+    #[prost(enumeration = "video::Source", tag = "2")]
+    pub source: i32,
+    /// YouTube or Vimeo id, or the media URL.
+    #[prost(string, tag = "3")]
+    pub source_id: std::string::String,
+    #[prost(string, tag = "4")]
+    pub title: std::string::String,
+    #[prost(string, tag = "5")]
+    pub channel_id: std::string::String,
+    #[prost(string, tag = "6")]
+    pub channel_title: std::string::String,
+    /// ISO-8601, e.g. PT4M13S. Empty if unknown.
+    #[prost(string, tag = "7")]
+    pub duration: std::string::String,
+}
+pub mod video {
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
+    #[repr(i32)]
+    #[derive(Serialize, Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    pub enum Source {
+        YouTube = 0,
+        Vimeo = 1,
+        /// Media file served from a URL, e.g. self-hosted.
+        Url = 2,
+    }
+}
+#[derive(Clone, PartialEq, ::prost::Message, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AddVideoRequest {
+    #[prost(enumeration = "video::Source", tag = "1")]
+    pub source: i32,
+    #[prost(string, tag = "2")]
+    pub source_id: std::string::String,
+    /// Required for URLs, looked up for YouTube and Vimeo if empty.
+    #[prost(string, tag = "3")]
+    pub title: std::string::String,
+}
+#[derive(Clone, PartialEq, ::prost::Message, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SourceCaption {
+    #[prost(string, tag = "1")]
+    pub id: std::string::String,
+    #[prost(string, tag = "2")]
+    pub language: std::string::String,
+    #[prost(string, tag = "3")]
+    pub name: std::string::String,
+    #[prost(bool, tag = "4")]
+    pub auto_generated: bool,
+    #[prost(bool, tag = "5")]
+    pub translatable: bool,
+}
+#[derive(Clone, PartialEq, ::prost::Message, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SourceCaptionList {
+    #[prost(message, repeated, tag = "1")]
+    pub captions: ::std::vec::Vec<SourceCaption>,
+}
+#[derive(Clone, PartialEq, ::prost::Message, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[serde(default)]
 pub struct Subtitles {
     #[prost(message, repeated, tag = "1")]
     pub entries: ::std::vec::Vec<subtitles::Entry>,
@@ -46,10 +135,30 @@ pub struct Subtitles {
     pub uploader_id: std::string::String,
     #[prost(string, tag = "6")]
     pub uploader_name: std::string::String,
+    #[prost(message, repeated, tag = "7")]
+    pub speakers: ::std::vec::Vec<subtitles::Speaker>,
+    /// Length of the video, 0 if unknown. Set by the server.
+    #[prost(float, tag = "8")]
+    pub duration_seconds: f32,
+    /// Whether timestamps need an hour part. Set by the server.
+    #[prost(bool, tag = "9")]
+    pub is_video_long: bool,
+    /// Fraction of the video covered by entries, 0 to 1. Set by the server.
+    #[prost(float, tag = "10")]
+    pub coverage: f32,
 }
 pub mod subtitles {
     #[derive(Clone, PartialEq, ::prost::Message, Serialize, Deserialize)]
     #[serde(rename_all = "camelCase")]
+    pub struct Word {
+        #[prost(float, tag = "1")]
+        pub start_seconds: f32,
+        #[prost(string, tag = "2")]
+        pub text: std::string::String,
+    }
+    #[derive(Clone, PartialEq, ::prost::Message, Serialize, Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    #[serde(default)]
     pub struct Entry {
         #[prost(float, tag = "1")]
         pub start_seconds: f32,
@@ -57,11 +166,59 @@ pub mod subtitles {
         pub end_seconds: f32,
         #[prost(string, tag = "3")]
         pub text: std::string::String,
+        /// Optional timings of the individual words in `text`.
+        #[prost(message, repeated, tag = "4")]
+        #[serde(skip_serializing_if = "Vec::is_empty")]
+        pub words: ::std::vec::Vec<Word>,
+        /// Id of one of the track's `speakers`, empty if unknown.
+        #[prost(string, tag = "5")]
+        #[serde(skip_serializing_if = "String::is_empty")]
+        pub speaker: std::string::String,
+        #[prost(enumeration = "Position", tag = "6")]
+        pub position: i32,
+        #[prost(enumeration = "Alignment", tag = "7")]
+        pub alignment: i32,
+        /// Free-form notes for other editors, not exported.
+        #[prost(string, tag = "8")]
+        #[serde(skip_serializing_if = "String::is_empty")]
+        pub notes: std::string::String,
+    }
+    #[derive(Clone, PartialEq, ::prost::Message, Serialize, Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    pub struct Speaker {
+        #[prost(string, tag = "1")]
+        pub id: std::string::String,
+        #[prost(string, tag = "2")]
+        pub name: std::string::String,
+        /// CSS hex color, e.g. #ff0000.
+        #[prost(string, tag = "3")]
+        pub color: std::string::String,
+    }
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
+    #[repr(i32)]
+    #[derive(Serialize, Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    pub enum Position {
+        Bottom = 0,
+        Top = 1,
+    }
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
+    #[repr(i32)]
+    #[derive(Serialize, Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    pub enum Alignment {
+        Center = 0,
+        Left = 1,
+        Right = 2,
     }
 }
 #[derive(Clone, PartialEq, ::prost::Message, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
-pub struct SetSubtitleResponse {}
+pub struct SetSubtitleResponse {
+    /// Fraction of the video covered by the saved entries, 0 if the duration is unknown.
+    #[prost(float, tag = "1")]
+    pub coverage: f32,
+}
 #[doc = r" Generated client implementations."]
 pub mod video_subs_client {
     #![allow(unused_variables, dead_code, missing_docs)]
@@ -140,6 +297,63 @@ pub mod video_subs_client {
                 .server_streaming(request.into_request(), path, codec)
                 .await
         }
+        pub async fn import_subtitles(
+            &mut self,
+            request: impl tonic::IntoRequest<super::ImportRequest>,
+        ) -> Result<tonic::Response<super::Subtitles>, tonic::Status> {
+            self.inner.ready().await.map_err(|e| {
+                tonic::Status::new(
+                    tonic::Code::Unknown,
+                    format!("Service was not ready: {}", e.into()),
+                )
+            })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static("/subtitles.VideoSubs/ImportSubtitles");
+            self.inner.unary(request.into_request(), path, codec).await
+        }
+        pub async fn list_source_captions(
+            &mut self,
+            request: impl tonic::IntoRequest<super::VideoId>,
+        ) -> Result<tonic::Response<super::SourceCaptionList>, tonic::Status> {
+            self.inner.ready().await.map_err(|e| {
+                tonic::Status::new(
+                    tonic::Code::Unknown,
+                    format!("Service was not ready: {}", e.into()),
+                )
+            })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path =
+                http::uri::PathAndQuery::from_static("/subtitles.VideoSubs/ListSourceCaptions");
+            self.inner.unary(request.into_request(), path, codec).await
+        }
+        pub async fn get_video(
+            &mut self,
+            request: impl tonic::IntoRequest<super::VideoId>,
+        ) -> Result<tonic::Response<super::Video>, tonic::Status> {
+            self.inner.ready().await.map_err(|e| {
+                tonic::Status::new(
+                    tonic::Code::Unknown,
+                    format!("Service was not ready: {}", e.into()),
+                )
+            })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static("/subtitles.VideoSubs/GetVideo");
+            self.inner.unary(request.into_request(), path, codec).await
+        }
+        pub async fn add_video(
+            &mut self,
+            request: impl tonic::IntoRequest<super::AddVideoRequest>,
+        ) -> Result<tonic::Response<super::Video>, tonic::Status> {
+            self.inner.ready().await.map_err(|e| {
+                tonic::Status::new(
+                    tonic::Code::Unknown,
+                    format!("Service was not ready: {}", e.into()),
+                )
+            })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static("/subtitles.VideoSubs/AddVideo");
+            self.inner.unary(request.into_request(), path, codec).await
+        }
     }
     impl<T: Clone> Clone for VideoSubsClient<T> {
         fn clone(&self) -> Self {
@@ -178,6 +392,22 @@ pub mod video_subs_server {
             &self,
             request: tonic::Request<super::DownloadRequest>,
         ) -> Result<tonic::Response<Self::DownloadSubtitlesStream>, tonic::Status>;
+        async fn import_subtitles(
+            &self,
+            request: tonic::Request<super::ImportRequest>,
+        ) -> Result<tonic::Response<super::Subtitles>, tonic::Status>;
+        async fn list_source_captions(
+            &self,
+            request: tonic::Request<super::VideoId>,
+        ) -> Result<tonic::Response<super::SourceCaptionList>, tonic::Status>;
+        async fn get_video(
+            &self,
+            request: tonic::Request<super::VideoId>,
+        ) -> Result<tonic::Response<super::Video>, tonic::Status>;
+        async fn add_video(
+            &self,
+            request: tonic::Request<super::AddVideoRequest>,
+        ) -> Result<tonic::Response<super::Video>, tonic::Status>;
     }
     #[derive(Debug)]
     pub struct VideoSubsServer<T: VideoSubs> {
@@ -308,6 +538,130 @@ pub mod video_subs_server {
                     };
                     Box::pin(fut)
                 }
+                "/subtitles.VideoSubs/ImportSubtitles" => {
+                    #[allow(non_camel_case_types)]
+                    struct ImportSubtitlesSvc<T: VideoSubs>(pub Arc<T>);
+                    impl<T: VideoSubs> tonic::server::UnaryService<super::ImportRequest> for ImportSubtitlesSvc<T> {
+                        type Response = super::Subtitles;
+                        type Future = BoxFuture<tonic::Response<Self::Response>, tonic::Status>;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::ImportRequest>,
+                        ) -> Self::Future {
+                            let inner = self.0.clone();
+                            let fut = async move { (*inner).import_subtitles(request).await };
+                            Box::pin(fut)
+                        }
+                    }
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let interceptor = inner.1.clone();
+                        let inner = inner.0;
+                        let method = ImportSubtitlesSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = if let Some(interceptor) = interceptor {
+                            tonic::server::Grpc::with_interceptor(codec, interceptor)
+                        } else {
+                            tonic::server::Grpc::new(codec)
+                        };
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/subtitles.VideoSubs/ListSourceCaptions" => {
+                    #[allow(non_camel_case_types)]
+                    struct ListSourceCaptionsSvc<T: VideoSubs>(pub Arc<T>);
+                    impl<T: VideoSubs> tonic::server::UnaryService<super::VideoId> for ListSourceCaptionsSvc<T> {
+                        type Response = super::SourceCaptionList;
+                        type Future = BoxFuture<tonic::Response<Self::Response>, tonic::Status>;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::VideoId>,
+                        ) -> Self::Future {
+                            let inner = self.0.clone();
+                            let fut = async move { (*inner).list_source_captions(request).await };
+                            Box::pin(fut)
+                        }
+                    }
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let interceptor = inner.1.clone();
+                        let inner = inner.0;
+                        let method = ListSourceCaptionsSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = if let Some(interceptor) = interceptor {
+                            tonic::server::Grpc::with_interceptor(codec, interceptor)
+                        } else {
+                            tonic::server::Grpc::new(codec)
+                        };
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/subtitles.VideoSubs/GetVideo" => {
+                    #[allow(non_camel_case_types)]
+                    struct GetVideoSvc<T: VideoSubs>(pub Arc<T>);
+                    impl<T: VideoSubs> tonic::server::UnaryService<super::VideoId> for GetVideoSvc<T> {
+                        type Response = super::Video;
+                        type Future = BoxFuture<tonic::Response<Self::Response>, tonic::Status>;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::VideoId>,
+                        ) -> Self::Future {
+                            let inner = self.0.clone();
+                            let fut = async move { (*inner).get_video(request).await };
+                            Box::pin(fut)
+                        }
+                    }
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let interceptor = inner.1.clone();
+                        let inner = inner.0;
+                        let method = GetVideoSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = if let Some(interceptor) = interceptor {
+                            tonic::server::Grpc::with_interceptor(codec, interceptor)
+                        } else {
+                            tonic::server::Grpc::new(codec)
+                        };
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/subtitles.VideoSubs/AddVideo" => {
+                    #[allow(non_camel_case_types)]
+                    struct AddVideoSvc<T: VideoSubs>(pub Arc<T>);
+                    impl<T: VideoSubs> tonic::server::UnaryService<super::AddVideoRequest> for AddVideoSvc<T> {
+                        type Response = super::Video;
+                        type Future = BoxFuture<tonic::Response<Self::Response>, tonic::Status>;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::AddVideoRequest>,
+                        ) -> Self::Future {
+                            let inner = self.0.clone();
+                            let fut = async move { (*inner).add_video(request).await };
+                            Box::pin(fut)
+                        }
+                    }
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let interceptor = inner.1.clone();
+                        let inner = inner.0;
+                        let method = AddVideoSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = if let Some(interceptor) = interceptor {
+                            tonic::server::Grpc::with_interceptor(codec, interceptor)
+                        } else {
+                            tonic::server::Grpc::new(codec)
+                        };
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
                 _ => Box::pin(async move {
                     Ok(http::Response::builder()
                         .status(200)
@@ -13,7 +13,183 @@ pub struct User {
     pub username: std::string::String,
     #[prost(string, tag = "3")]
     pub email: std::string::String,
+    /// Id of the current profile picture, empty if there is none.
+    #[prost(string, tag = "4")]
+    pub picture: std::string::String,
+    #[prost(string, tag = "5")]
+    pub display_name: std::string::String,
+    #[prost(string, tag = "6")]
+    pub bio: std::string::String,
+    #[prost(message, repeated, tag = "7")]
+    pub languages: ::std::vec::Vec<LanguageSkill>,
 }
+/// A language the user can work in, used to route captioning and translation work.
+#[derive(Clone, PartialEq, ::prost::Message, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LanguageSkill {
+    #[prost(string, tag = "1")]
+    pub language: std::string::String,
+    #[prost(bool, tag = "2")]
+    pub can_caption: bool,
+    #[prost(bool, tag = "3")]
+    pub can_translate: bool,
+}
+/// Replaces the editable parts of the current user's profile.
+#[derive(Clone, PartialEq, ::prost::Message, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UpdateProfileRequest {
+    #[prost(string, tag = "1")]
+    pub username: std::string::String,
+    #[prost(string, tag = "2")]
+    pub display_name: std::string::String,
+    #[prost(string, tag = "3")]
+    pub bio: std::string::String,
+    #[prost(message, repeated, tag = "4")]
+    pub languages: ::std::vec::Vec<LanguageSkill>,
+}
+#[derive(Clone, PartialEq, ::prost::Message, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProfilePictureRequest {
+    #[prost(string, tag = "1")]
+    pub user_id: std::string::String,
+    /// Size in pixels, rounded up to the closest stored size. Defaults to the largest one.
+    #[prost(uint32, tag = "2")]
+    pub size: u32,
+    #[prost(enumeration = "profile_picture_request::Format", tag = "3")]
+    pub format: i32,
+}
+pub mod profile_picture_request {
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
+    #[repr(i32)]
+    #[derive(Serialize, Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    pub enum Format {
+        Png = 0,
+        Webp = 1,
+    }
+}
+#[derive(Clone, PartialEq, ::prost::Message, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProfilePicture {
+    /// Empty if the user hasn't uploaded a picture.
+    #[prost(bytes, tag = "1")]
+    pub content: std::vec::Vec<u8>,
+    #[prost(string, tag = "2")]
+    pub content_type: std::string::String,
+    /// Changes whenever the picture does, usable as an ETag.
+    #[prost(string, tag = "3")]
+    pub version: std::string::String,
+    /// For rendering a default avatar.
+    #[prost(string, tag = "4")]
+    pub username: std::string::String,
+}
+#[derive(Clone, PartialEq, ::prost::Message, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UserStatsRequest {
+    #[prost(string, tag = "1")]
+    pub user_id: std::string::String,
+}
+/// Public profile of a contributor, the user's email is never included.
+#[derive(Clone, PartialEq, ::prost::Message, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UserStats {
+    #[prost(message, optional, tag = "1")]
+    pub user: ::std::option::Option<User>,
+    /// Number of saves that changed a track
+    #[prost(uint32, tag = "2")]
+    pub edits: u32,
+    /// Distinct video and language pairs edited
+    #[prost(uint32, tag = "3")]
+    pub tracks_touched: u32,
+    #[prost(uint32, tag = "4")]
+    pub entries_edited: u32,
+    #[prost(string, repeated, tag = "5")]
+    pub languages: ::std::vec::Vec<std::string::String>,
+    /// Unix timestamp in seconds, 0 if the user hasn't contributed yet
+    #[prost(int64, tag = "6")]
+    pub last_active: i64,
+}
+#[derive(Clone, PartialEq, ::prost::Message, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExportRequest {}
+/// A zip archive of everything stored about the current user.
+#[derive(Clone, PartialEq, ::prost::Message, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DataExport {
+    #[prost(bytes, tag = "1")]
+    pub archive: std::vec::Vec<u8>,
+    #[prost(string, tag = "2")]
+    pub file_name: std::string::String,
+}
+#[derive(Clone, PartialEq, ::prost::Message, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DeleteAccountRequest {
+    /// Has to match the current username, to guard against accidental deletion.
+    #[prost(string, tag = "1")]
+    pub confirm_username: std::string::String,
+}
+#[derive(Clone, PartialEq, ::prost::Message, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DeleteAccountResponse {}
+/// A personal token for scripts and CI, sent as a bearer token like the ones from the identity
+/// provider.
+#[derive(Clone, PartialEq, ::prost::Message, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ApiToken {
+    #[prost(string, tag = "1")]
+    pub id: std::string::String,
+    #[prost(string, tag = "2")]
+    pub name: std::string::String,
+    #[prost(string, repeated, tag = "3")]
+    pub scopes: ::std::vec::Vec<std::string::String>,
+    /// Unix timestamps in seconds
+    #[prost(int64, tag = "4")]
+    pub created: i64,
+    #[prost(int64, tag = "5")]
+    pub expires: i64,
+    /// 0 if the token hasn't been used yet
+    #[prost(int64, tag = "6")]
+    pub last_used: i64,
+}
+#[derive(Clone, PartialEq, ::prost::Message, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CreateApiTokenRequest {
+    #[prost(string, tag = "1")]
+    pub name: std::string::String,
+    /// Any of user.read, user.write, subtitles.read and subtitles.write
+    #[prost(string, repeated, tag = "2")]
+    pub scopes: ::std::vec::Vec<std::string::String>,
+    /// Defaults to 90, at most 365.
+    #[prost(uint32, tag = "3")]
+    pub expires_in_days: u32,
+}
+#[derive(Clone, PartialEq, ::prost::Message, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CreatedApiToken {
+    #[prost(message, optional, tag = "1")]
+    pub token: ::std::option::Option<ApiToken>,
+    /// Only stored hashed, so this is the only time it can be shown.
+    #[prost(string, tag = "2")]
+    pub secret: std::string::String,
+}
+#[derive(Clone, PartialEq, ::prost::Message, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ListApiTokensRequest {}
+#[derive(Clone, PartialEq, ::prost::Message, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ApiTokenList {
+    #[prost(message, repeated, tag = "1")]
+    pub tokens: ::std::vec::Vec<ApiToken>,
+}
+#[derive(Clone, PartialEq, ::prost::Message, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RevokeApiTokenRequest {
+    #[prost(string, tag = "1")]
+    pub id: std::string::String,
+}
+#[derive(Clone, PartialEq, ::prost::Message, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RevokeApiTokenResponse {}
 #[derive(Clone, PartialEq, ::prost::Message, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct SayRequest {
@@ -138,6 +314,118 @@ pub mod user_service_client {
             let path = http::uri::PathAndQuery::from_static("/user.UserService/GetUser");
             self.inner.unary(request.into_request(), path, codec).await
         }
+        pub async fn get_profile_picture(
+            &mut self,
+            request: impl tonic::IntoRequest<super::ProfilePictureRequest>,
+        ) -> Result<tonic::Response<super::ProfilePicture>, tonic::Status> {
+            self.inner.ready().await.map_err(|e| {
+                tonic::Status::new(
+                    tonic::Code::Unknown,
+                    format!("Service was not ready: {}", e.into()),
+                )
+            })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static("/user.UserService/GetProfilePicture");
+            self.inner.unary(request.into_request(), path, codec).await
+        }
+        pub async fn update_profile(
+            &mut self,
+            request: impl tonic::IntoRequest<super::UpdateProfileRequest>,
+        ) -> Result<tonic::Response<super::User>, tonic::Status> {
+            self.inner.ready().await.map_err(|e| {
+                tonic::Status::new(
+                    tonic::Code::Unknown,
+                    format!("Service was not ready: {}", e.into()),
+                )
+            })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static("/user.UserService/UpdateProfile");
+            self.inner.unary(request.into_request(), path, codec).await
+        }
+        pub async fn get_user_stats(
+            &mut self,
+            request: impl tonic::IntoRequest<super::UserStatsRequest>,
+        ) -> Result<tonic::Response<super::UserStats>, tonic::Status> {
+            self.inner.ready().await.map_err(|e| {
+                tonic::Status::new(
+                    tonic::Code::Unknown,
+                    format!("Service was not ready: {}", e.into()),
+                )
+            })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static("/user.UserService/GetUserStats");
+            self.inner.unary(request.into_request(), path, codec).await
+        }
+        pub async fn export_my_data(
+            &mut self,
+            request: impl tonic::IntoRequest<super::ExportRequest>,
+        ) -> Result<tonic::Response<super::DataExport>, tonic::Status> {
+            self.inner.ready().await.map_err(|e| {
+                tonic::Status::new(
+                    tonic::Code::Unknown,
+                    format!("Service was not ready: {}", e.into()),
+                )
+            })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static("/user.UserService/ExportMyData");
+            self.inner.unary(request.into_request(), path, codec).await
+        }
+        pub async fn delete_account(
+            &mut self,
+            request: impl tonic::IntoRequest<super::DeleteAccountRequest>,
+        ) -> Result<tonic::Response<super::DeleteAccountResponse>, tonic::Status> {
+            self.inner.ready().await.map_err(|e| {
+                tonic::Status::new(
+                    tonic::Code::Unknown,
+                    format!("Service was not ready: {}", e.into()),
+                )
+            })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static("/user.UserService/DeleteAccount");
+            self.inner.unary(request.into_request(), path, codec).await
+        }
+        pub async fn create_api_token(
+            &mut self,
+            request: impl tonic::IntoRequest<super::CreateApiTokenRequest>,
+        ) -> Result<tonic::Response<super::CreatedApiToken>, tonic::Status> {
+            self.inner.ready().await.map_err(|e| {
+                tonic::Status::new(
+                    tonic::Code::Unknown,
+                    format!("Service was not ready: {}", e.into()),
+                )
+            })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static("/user.UserService/CreateApiToken");
+            self.inner.unary(request.into_request(), path, codec).await
+        }
+        pub async fn list_api_tokens(
+            &mut self,
+            request: impl tonic::IntoRequest<super::ListApiTokensRequest>,
+        ) -> Result<tonic::Response<super::ApiTokenList>, tonic::Status> {
+            self.inner.ready().await.map_err(|e| {
+                tonic::Status::new(
+                    tonic::Code::Unknown,
+                    format!("Service was not ready: {}", e.into()),
+                )
+            })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static("/user.UserService/ListApiTokens");
+            self.inner.unary(request.into_request(), path, codec).await
+        }
+        pub async fn revoke_api_token(
+            &mut self,
+            request: impl tonic::IntoRequest<super::RevokeApiTokenRequest>,
+        ) -> Result<tonic::Response<super::RevokeApiTokenResponse>, tonic::Status> {
+            self.inner.ready().await.map_err(|e| {
+                tonic::Status::new(
+                    tonic::Code::Unknown,
+                    format!("Service was not ready: {}", e.into()),
+                )
+            })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static("/user.UserService/RevokeApiToken");
+            self.inner.unary(request.into_request(), path, codec).await
+        }
     }
     impl<T: Clone> Clone for UserServiceClient<T> {
         fn clone(&self) -> Self {
@@ -171,6 +459,38 @@ pub mod user_service_server {
             &self,
             request: tonic::Request<super::UserIdentity>,
         ) -> Result<tonic::Response<super::User>, tonic::Status>;
+        async fn get_profile_picture(
+            &self,
+            request: tonic::Request<super::ProfilePictureRequest>,
+        ) -> Result<tonic::Response<super::ProfilePicture>, tonic::Status>;
+        async fn update_profile(
+            &self,
+            request: tonic::Request<super::UpdateProfileRequest>,
+        ) -> Result<tonic::Response<super::User>, tonic::Status>;
+        async fn get_user_stats(
+            &self,
+            request: tonic::Request<super::UserStatsRequest>,
+        ) -> Result<tonic::Response<super::UserStats>, tonic::Status>;
+        async fn export_my_data(
+            &self,
+            request: tonic::Request<super::ExportRequest>,
+        ) -> Result<tonic::Response<super::DataExport>, tonic::Status>;
+        async fn delete_account(
+            &self,
+            request: tonic::Request<super::DeleteAccountRequest>,
+        ) -> Result<tonic::Response<super::DeleteAccountResponse>, tonic::Status>;
+        async fn create_api_token(
+            &self,
+            request: tonic::Request<super::CreateApiTokenRequest>,
+        ) -> Result<tonic::Response<super::CreatedApiToken>, tonic::Status>;
+        async fn list_api_tokens(
+            &self,
+            request: tonic::Request<super::ListApiTokensRequest>,
+        ) -> Result<tonic::Response<super::ApiTokenList>, tonic::Status>;
+        async fn revoke_api_token(
+            &self,
+            request: tonic::Request<super::RevokeApiTokenRequest>,
+        ) -> Result<tonic::Response<super::RevokeApiTokenResponse>, tonic::Status>;
     }
     #[derive(Debug)]
     pub struct UserServiceServer<T: UserService> {
@@ -299,6 +619,266 @@ pub mod user_service_server {
                     };
                     Box::pin(fut)
                 }
+                "/user.UserService/GetProfilePicture" => {
+                    #[allow(non_camel_case_types)]
+                    struct GetProfilePictureSvc<T: UserService>(pub Arc<T>);
+                    impl<T: UserService> tonic::server::UnaryService<super::ProfilePictureRequest>
+                        for GetProfilePictureSvc<T>
+                    {
+                        type Response = super::ProfilePicture;
+                        type Future = BoxFuture<tonic::Response<Self::Response>, tonic::Status>;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::ProfilePictureRequest>,
+                        ) -> Self::Future {
+                            let inner = self.0.clone();
+                            let fut = async move { (*inner).get_profile_picture(request).await };
+                            Box::pin(fut)
+                        }
+                    }
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let interceptor = inner.1.clone();
+                        let inner = inner.0;
+                        let method = GetProfilePictureSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = if let Some(interceptor) = interceptor {
+                            tonic::server::Grpc::with_interceptor(codec, interceptor)
+                        } else {
+                            tonic::server::Grpc::new(codec)
+                        };
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/user.UserService/UpdateProfile" => {
+                    #[allow(non_camel_case_types)]
+                    struct UpdateProfileSvc<T: UserService>(pub Arc<T>);
+                    impl<T: UserService> tonic::server::UnaryService<super::UpdateProfileRequest>
+                        for UpdateProfileSvc<T>
+                    {
+                        type Response = super::User;
+                        type Future = BoxFuture<tonic::Response<Self::Response>, tonic::Status>;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::UpdateProfileRequest>,
+                        ) -> Self::Future {
+                            let inner = self.0.clone();
+                            let fut = async move { (*inner).update_profile(request).await };
+                            Box::pin(fut)
+                        }
+                    }
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let interceptor = inner.1.clone();
+                        let inner = inner.0;
+                        let method = UpdateProfileSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = if let Some(interceptor) = interceptor {
+                            tonic::server::Grpc::with_interceptor(codec, interceptor)
+                        } else {
+                            tonic::server::Grpc::new(codec)
+                        };
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/user.UserService/GetUserStats" => {
+                    #[allow(non_camel_case_types)]
+                    struct GetUserStatsSvc<T: UserService>(pub Arc<T>);
+                    impl<T: UserService> tonic::server::UnaryService<super::UserStatsRequest> for GetUserStatsSvc<T> {
+                        type Response = super::UserStats;
+                        type Future = BoxFuture<tonic::Response<Self::Response>, tonic::Status>;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::UserStatsRequest>,
+                        ) -> Self::Future {
+                            let inner = self.0.clone();
+                            let fut = async move { (*inner).get_user_stats(request).await };
+                            Box::pin(fut)
+                        }
+                    }
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let interceptor = inner.1.clone();
+                        let inner = inner.0;
+                        let method = GetUserStatsSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = if let Some(interceptor) = interceptor {
+                            tonic::server::Grpc::with_interceptor(codec, interceptor)
+                        } else {
+                            tonic::server::Grpc::new(codec)
+                        };
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/user.UserService/ExportMyData" => {
+                    #[allow(non_camel_case_types)]
+                    struct ExportMyDataSvc<T: UserService>(pub Arc<T>);
+                    impl<T: UserService> tonic::server::UnaryService<super::ExportRequest> for ExportMyDataSvc<T> {
+                        type Response = super::DataExport;
+                        type Future = BoxFuture<tonic::Response<Self::Response>, tonic::Status>;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::ExportRequest>,
+                        ) -> Self::Future {
+                            let inner = self.0.clone();
+                            let fut = async move { (*inner).export_my_data(request).await };
+                            Box::pin(fut)
+                        }
+                    }
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let interceptor = inner.1.clone();
+                        let inner = inner.0;
+                        let method = ExportMyDataSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = if let Some(interceptor) = interceptor {
+                            tonic::server::Grpc::with_interceptor(codec, interceptor)
+                        } else {
+                            tonic::server::Grpc::new(codec)
+                        };
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/user.UserService/DeleteAccount" => {
+                    #[allow(non_camel_case_types)]
+                    struct DeleteAccountSvc<T: UserService>(pub Arc<T>);
+                    impl<T: UserService> tonic::server::UnaryService<super::DeleteAccountRequest>
+                        for DeleteAccountSvc<T>
+                    {
+                        type Response = super::DeleteAccountResponse;
+                        type Future = BoxFuture<tonic::Response<Self::Response>, tonic::Status>;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::DeleteAccountRequest>,
+                        ) -> Self::Future {
+                            let inner = self.0.clone();
+                            let fut = async move { (*inner).delete_account(request).await };
+                            Box::pin(fut)
+                        }
+                    }
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let interceptor = inner.1.clone();
+                        let inner = inner.0;
+                        let method = DeleteAccountSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = if let Some(interceptor) = interceptor {
+                            tonic::server::Grpc::with_interceptor(codec, interceptor)
+                        } else {
+                            tonic::server::Grpc::new(codec)
+                        };
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/user.UserService/CreateApiToken" => {
+                    #[allow(non_camel_case_types)]
+                    struct CreateApiTokenSvc<T: UserService>(pub Arc<T>);
+                    impl<T: UserService> tonic::server::UnaryService<super::CreateApiTokenRequest>
+                        for CreateApiTokenSvc<T>
+                    {
+                        type Response = super::CreatedApiToken;
+                        type Future = BoxFuture<tonic::Response<Self::Response>, tonic::Status>;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::CreateApiTokenRequest>,
+                        ) -> Self::Future {
+                            let inner = self.0.clone();
+                            let fut = async move { (*inner).create_api_token(request).await };
+                            Box::pin(fut)
+                        }
+                    }
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let interceptor = inner.1.clone();
+                        let inner = inner.0;
+                        let method = CreateApiTokenSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = if let Some(interceptor) = interceptor {
+                            tonic::server::Grpc::with_interceptor(codec, interceptor)
+                        } else {
+                            tonic::server::Grpc::new(codec)
+                        };
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/user.UserService/ListApiTokens" => {
+                    #[allow(non_camel_case_types)]
+                    struct ListApiTokensSvc<T: UserService>(pub Arc<T>);
+                    impl<T: UserService> tonic::server::UnaryService<super::ListApiTokensRequest>
+                        for ListApiTokensSvc<T>
+                    {
+                        type Response = super::ApiTokenList;
+                        type Future = BoxFuture<tonic::Response<Self::Response>, tonic::Status>;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::ListApiTokensRequest>,
+                        ) -> Self::Future {
+                            let inner = self.0.clone();
+                            let fut = async move { (*inner).list_api_tokens(request).await };
+                            Box::pin(fut)
+                        }
+                    }
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let interceptor = inner.1.clone();
+                        let inner = inner.0;
+                        let method = ListApiTokensSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = if let Some(interceptor) = interceptor {
+                            tonic::server::Grpc::with_interceptor(codec, interceptor)
+                        } else {
+                            tonic::server::Grpc::new(codec)
+                        };
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/user.UserService/RevokeApiToken" => {
+                    #[allow(non_camel_case_types)]
+                    struct RevokeApiTokenSvc<T: UserService>(pub Arc<T>);
+                    impl<T: UserService> tonic::server::UnaryService<super::RevokeApiTokenRequest>
+                        for RevokeApiTokenSvc<T>
+                    {
+                        type Response = super::RevokeApiTokenResponse;
+                        type Future = BoxFuture<tonic::Response<Self::Response>, tonic::Status>;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::RevokeApiTokenRequest>,
+                        ) -> Self::Future {
+                            let inner = self.0.clone();
+                            let fut = async move { (*inner).revoke_api_token(request).await };
+                            Box::pin(fut)
+                        }
+                    }
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let interceptor = inner.1.clone();
+                        let inner = inner.0;
+                        let method = RevokeApiTokenSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = if let Some(interceptor) = interceptor {
+                            tonic::server::Grpc::with_interceptor(codec, interceptor)
+                        } else {
+                            tonic::server::Grpc::new(codec)
+                        };
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
                 _ => Box::pin(async move {
                     Ok(http::Response::builder()
                         .status(200)
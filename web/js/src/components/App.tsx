@@ -8,14 +8,24 @@ import Header from "./Header"
 
 import Player from "./Player"
 import CaptionList from "./CaptionList"
-
-import type { Caption, CaptionData, VideoInfo } from "../types"
+import SourcePicker from "./SourcePicker"
+import SpeakerList from "./SpeakerList"
+
+import type {
+  ApiError,
+  Caption,
+  CaptionData,
+  PlayerHandle,
+  SourceCaption,
+  Speaker,
+  VideoInfo,
+} from "../types"
 import { initialCaptionState, timestampify, NotyfContext } from "../utils"
 
 let TOKEN = `${window.VIDEO_ID}-${window.SUBTITLE_LANG}`
 
 export default function App() {
-  let playerRef = useRef<HTMLDivElement | null>(null)
+  let playerRef = useRef<PlayerHandle | null>(null)
   let message = useContext(NotyfContext)
 
   // Editor State
@@ -33,34 +43,55 @@ export default function App() {
   let [captions, setCaptions] = useState<Caption[]>([])
   let [activeCaption, setActiveCaption] = useState<Caption>(initialCaptionState)
 
+  // Speakers that captions can be attributed to
+  let [speakers, setSpeakers] = useState<Speaker[]>([])
+
+  // Caption tracks that an empty track can be seeded from
+  let [sources, setSources] = useState<SourceCaption[]>([])
+
   /**
    * Fetch video information and captions from the API
    *
    * @param {string} id
    * @param {string} language
+   * @param {string} source Caption track to seed a new track from
    */
-  async function fetchCaptions(id: string, language: string): Promise<void> {
+  async function fetchCaptions(
+    id: string,
+    language: string,
+    source?: string
+  ): Promise<void> {
     try {
-      let response: Response = await fetch(`/subtitles/${id}?lang=${language}`)
+      let query = source ? `lang=${language}&source=${source}` : `lang=${language}`
+      let response: Response = await fetch(`/subtitles/${id}?${query}`)
+
+      if (!response.ok) {
+        let error: ApiError = await response.json()
+        return startEmpty(id, language, error)
+      }
+
       let data: CaptionData = await response.json()
 
-      let { entries, ...videoData } = data
+      let { entries, speakers = [], ...videoData } = data
 
       /**
        * If no caption entries are returned from the API, populate entries
-       * with a dummy caption to help users get started
+       * with a dummy caption to help users get started and offer the
+       * available caption tracks as a starting point
        */
       if (entries.length === 0) {
         entries.push({ startSeconds: 0, endSeconds: 0, text: "" })
+        fetchSources(id)
       }
 
       /**
        * Transform the caption entries and add display related metadata
        */
+      let format = videoData.isVideoLong ? "long" : "short"
       let fetchedCaptions: Caption[] = entries.map(caption => ({
         id: nanoid(),
-        startTimestamp: timestampify(caption.startSeconds, "short"),
-        endTimestamp: timestampify(caption.endSeconds, "short"),
+        startTimestamp: timestampify(caption.startSeconds, format),
+        endTimestamp: timestampify(caption.endSeconds, format),
         ...caption,
       }))
 
@@ -73,6 +104,7 @@ export default function App() {
       )
 
       setCaptions(fetchedCaptions)
+      setSpeakers(speakers)
       setLoading(false)
     } catch (error) {
       setLoading(false)
@@ -82,6 +114,61 @@ export default function App() {
     }
   }
 
+  /**
+   * Explain why the track couldn't be seeded and start out with
+   * a blank caption instead
+   *
+   * @param {string} id
+   * @param {string} language
+   * @param {ApiError} error
+   */
+  function startEmpty(id: string, language: string, error: ApiError): void {
+    switch (error.reason) {
+      case "NO_TRACKS":
+        message.success("This video has no captions yet, starting from scratch.")
+        break
+      case "LANGUAGE_MISSING":
+        message.success(
+          "There are no captions in this language yet, pick a track to start from or start from scratch."
+        )
+        fetchSources(id)
+        break
+      default:
+        message.error(`Couldn't load existing captions: ${error.message}`)
+    }
+
+    setVideoInfo({ videoId: id, language })
+    setCaptions([{ ...initialCaptionState, id: nanoid() }])
+    setLoading(false)
+  }
+
+  /**
+   * Fetch the caption tracks the video already has on its platform
+   *
+   * @param {string} id
+   */
+  async function fetchSources(id: string): Promise<void> {
+    try {
+      let response: Response = await fetch(`/subtitles/sources/${id}`)
+      let data: { captions?: SourceCaption[] } = await response.json()
+
+      setSources(data.captions || [])
+    } catch (error) {
+      console.error("Error fetching caption sources:", error)
+    }
+  }
+
+  /**
+   * Seed the editor from the chosen caption track
+   *
+   * @param {string} source
+   */
+  function selectSource(source: string): void {
+    setSources([])
+    setLoading(true)
+    fetchCaptions(window.VIDEO_ID, window.SUBTITLE_LANG, source)
+  }
+
   /**
    * Save changes to the API
    */
@@ -92,17 +179,19 @@ export default function App() {
        * necessary data to the API
        */
       let data: CaptionData = {
-        entries: captions.map(({ startSeconds, endSeconds, text }) => ({
-          startSeconds,
-          endSeconds,
-          text,
-        })),
+        entries: captions.map(
+          ({ id, startTimestamp, endTimestamp, ...caption }) => caption
+        ),
+        speakers,
         ...videoInfo,
       }
 
       let saveRequest: Response = await fetch("/subtitles/", {
         method: "POST",
-        headers: { "Content-Type": "application/json" },
+        headers: {
+          "Content-Type": "application/json",
+          "X-CSRF-Token": window.CSRF_TOKEN,
+        },
         body: JSON.stringify(data),
       })
 
@@ -111,12 +200,15 @@ export default function App() {
        * the editor as not holding any unsaved changes
        */
       if (saveRequest.ok) {
+        let { coverage }: { coverage?: number } = await saveRequest.json()
         message.success("Changes successfully saved!")
 
+        setVideoInfo({ ...videoInfo, coverage })
         localStorage.removeItem(`captions-${TOKEN}`)
         setEditorDirty(false)
       } else {
-        throw new Error("Unable to save captions")
+        let error: ApiError = await saveRequest.json()
+        message.error(`Unable to save changes: ${error.message}`)
       }
     } catch (error) {
       message.error("Unable to save changes. Please try again later.")
@@ -124,12 +216,48 @@ export default function App() {
     }
   }
 
+  /**
+   * Download the saved captions in the given format
+   *
+   * @param {string} format
+   */
+  function exportCaptions(format: string): void {
+    let { videoId, language } = videoInfo
+    window.location.href = `/subtitles/download/${videoId}?lang=${language}&format=${format}`
+  }
+
   function syncCaptionStorage(): void {
     if (videoInfo.videoId !== "") {
       localStorage.setItem(`captions-${TOKEN}`, JSON.stringify(captions))
+      localStorage.setItem(`speakers-${TOKEN}`, JSON.stringify(speakers))
     }
   }
 
+  /**
+   * Replace the speaker registry and flag the editor as dirty
+   *
+   * @param {Speaker[]} updatedSpeakers
+   */
+  function updateSpeakers(updatedSpeakers: Speaker[]): void {
+    setSpeakers(updatedSpeakers)
+    localStorage.setItem(`speakers-${TOKEN}`, JSON.stringify(updatedSpeakers))
+    setEditorDirty(true)
+  }
+
+  /**
+   * Remove a speaker and unassign it from all of its captions
+   *
+   * @param {string} id
+   */
+  function removeSpeaker(id: string): void {
+    updateSpeakers(speakers.filter(speaker => speaker.id !== id))
+    setCaptions(
+      captions.map(caption =>
+        caption.speaker === id ? { ...caption, speaker: "" } : caption
+      )
+    )
+  }
+
   useEffect(() => {
     let hasDirtyChanges: boolean = JSON.parse(
       localStorage.getItem(`isEditorDirty-${TOKEN}`)
@@ -143,8 +271,10 @@ export default function App() {
       if (hydrateConfirmation) {
         let localCaptionState = localStorage.getItem(`captions-${TOKEN}`)
         let localVideoInfoState = localStorage.getItem(`videoInfo-${TOKEN}`)
+        let localSpeakerState = localStorage.getItem(`speakers-${TOKEN}`)
 
         setCaptions(JSON.parse(localCaptionState))
+        setSpeakers(JSON.parse(localSpeakerState) || [])
         setVideoInfo(JSON.parse(localVideoInfoState))
         setLoading(false)
 
@@ -173,16 +303,32 @@ export default function App() {
 
   return (
     <div class="app">
-      <Header videoTitle={videoInfo.videoTitle} saveCaptions={saveCaptions} />
+      <Header
+        videoTitle={videoInfo.videoTitle}
+        coverage={videoInfo.durationSeconds ? videoInfo.coverage : undefined}
+        saveCaptions={saveCaptions}
+        exportCaptions={exportCaptions}
+      />
+
+      {sources.length > 0 && (
+        <SourcePicker sources={sources} selectSource={selectSource} />
+      )}
+
+      <SpeakerList
+        speakers={speakers}
+        updateSpeakers={updateSpeakers}
+        removeSpeaker={removeSpeaker}
+      />
 
       <div class="editor">
         <CaptionList
           captions={captions}
+          speakers={speakers}
           setCaptions={setCaptions}
           activeCaption={activeCaption}
           setActiveCaption={setActiveCaption}
           playerRef={playerRef}
-          isLongVideo={false}
+          isVideoLong={videoInfo.isVideoLong}
           isEditorDirty={isEditorDirty}
           setEditorDirty={setEditorDirty}
           syncCaptionStorage={syncCaptionStorage}
@@ -194,7 +340,8 @@ export default function App() {
           activeCaption={activeCaption}
           setActiveCaption={setActiveCaption}
           playerRef={playerRef}
-          videoId={videoInfo.videoId}
+          source={window.VIDEO_SOURCE || "youtube"}
+          sourceId={window.VIDEO_SOURCE_ID || videoInfo.videoId}
         />
       </div>
     </div>
@@ -1,9 +1,10 @@
 import { h } from "preact"
 
-import type { Caption, CaptionItemCallbacks } from "../types"
+import type { Caption, CaptionItemCallbacks, Speaker } from "../types"
 
 interface CaptionProps extends Caption, CaptionItemCallbacks {
   isActive: boolean
+  speakers: Speaker[]
 }
 
 export default function CaptionItem(props: CaptionProps) {
@@ -12,7 +13,12 @@ export default function CaptionItem(props: CaptionProps) {
     startTimestamp,
     endTimestamp,
     text,
+    speaker = "",
+    position = 0,
+    alignment = 0,
+    notes = "",
     isActive,
+    speakers,
     addCaption,
     captionSelected,
     updateCaptionField,
@@ -44,15 +50,72 @@ export default function CaptionItem(props: CaptionProps) {
         />
       </div>
 
-      <textarea
-        class="caption-textbox"
-        title="Caption text"
-        value={text}
-        rows={3}
-        onChange={event =>
-          updateCaptionField(id, "text", event.currentTarget.value)
-        }
-      />
+      <div class="caption-body">
+        <textarea
+          class="caption-textbox"
+          title="Caption text"
+          value={text}
+          rows={3}
+          onChange={event =>
+            updateCaptionField(id, "text", event.currentTarget.value)
+          }
+        />
+
+        <div class="caption-metadata">
+          <span
+            class="speaker-color"
+            style={{
+              background: speakers.find(s => s.id === speaker)?.color || "transparent",
+            }}
+          />
+          <select
+            title="Speaker"
+            value={speaker}
+            onChange={event =>
+              updateCaptionField(id, "speaker", event.currentTarget.value)
+            }
+          >
+            <option value="">No speaker</option>
+            {speakers.map(s => (
+              <option key={s.id} value={s.id}>
+                {s.name}
+              </option>
+            ))}
+          </select>
+          <select
+            title="Position"
+            value={position}
+            onChange={event =>
+              updateCaptionField(id, "position", event.currentTarget.value)
+            }
+          >
+            <option value={0}>Bottom</option>
+            <option value={1}>Top</option>
+          </select>
+          <select
+            title="Alignment"
+            value={alignment}
+            onChange={event =>
+              updateCaptionField(id, "alignment", event.currentTarget.value)
+            }
+          >
+            <option value={0}>Center</option>
+            <option value={1}>Left</option>
+            <option value={2}>Right</option>
+          </select>
+        </div>
+
+        <input
+          type="text"
+          class="caption-notes"
+          title="Notes for other editors, not exported"
+          placeholder="Notes"
+          value={notes}
+          onChange={event =>
+            updateCaptionField(id, "notes", event.currentTarget.value)
+          }
+        />
+      </div>
 
       <div class="caption-options">
         <button
@@ -10,6 +10,8 @@ import type {
   CaptionState,
   EditorState,
   EditableCaptionField,
+  PlayerHandle,
+  Speaker,
   VideoInfo,
 } from "../types"
 
@@ -19,7 +21,8 @@ interface CaptionListProps
   extends CaptionState,
     EditorState,
     Pick<VideoInfo, "isVideoLong"> {
-  playerRef: Ref<HTMLDivElement>
+  playerRef: Ref<PlayerHandle>
+  speakers: Speaker[]
 }
 
 export default function CaptionList(props: CaptionListProps) {
@@ -27,6 +30,7 @@ export default function CaptionList(props: CaptionListProps) {
     playerRef,
     captions,
     setCaptions,
+    speakers,
     isVideoLong,
     activeCaption,
     setActiveCaption,
@@ -80,6 +84,15 @@ export default function CaptionList(props: CaptionListProps) {
           case "endTimestamp":
             updatedCaption["endSeconds"] = secondify(content)
             break
+          case "text":
+            // Word timings no longer match edited text
+            updatedCaption["words"] = undefined
+            break
+          case "position":
+          case "alignment":
+            // Select values are strings, the API expects the enum's number
+            updatedCaption[field] = Number(content) as any
+            break
         }
 
         // Update the currently active caption while it's being edited
@@ -149,6 +162,7 @@ export default function CaptionList(props: CaptionListProps) {
           addCaption={addCaption}
           deleteCaption={deleteCaption}
           isActive={activeCaption.id === caption.id}
+          speakers={speakers}
           {...caption}
         />
       ))}
@@ -2,7 +2,10 @@ import { h } from "preact"
 
 interface HeaderProps {
   videoTitle?: string
+  // Fraction of the video with captions, if the video's length is known
+  coverage?: number
   saveCaptions(): void
+  exportCaptions(format: string): void
 }
 
 export default function Header(props: HeaderProps) {
@@ -15,11 +18,27 @@ export default function Header(props: HeaderProps) {
         <span class="video-title">
           {props.videoTitle ? props.videoTitle : "Subtitle Editor"}
         </span>
+        {props.coverage !== undefined && (
+          <span class="coverage" title="Share of the video with captions">
+            {Math.round(props.coverage * 100)}% captioned
+          </span>
+        )}
       </div>
       <div class="actions">
         <button onClick={() => props.saveCaptions()}>Save</button>
         <button>Import</button>
-        <button style={{ marginRight: 0 }}>Export</button>
+        <select
+          class="export"
+          value=""
+          onChange={event => props.exportCaptions(event.currentTarget.value)}
+        >
+          <option value="" disabled>
+            Export
+          </option>
+          <option value="srt">SubRip (.srt)</option>
+          <option value="vtt">WebVTT (.vtt)</option>
+          <option value="ass">Advanced SubStation (.ass)</option>
+        </select>
       </div>
     </div>
   )
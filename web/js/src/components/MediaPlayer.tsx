@@ -0,0 +1,38 @@
+import { h, Component } from "preact"
+
+import type { PlayerControls } from "../types"
+
+interface MediaPlayerProps {
+  class?: string
+  src: string
+  onTimeUpdate(currentTime: number): void
+}
+
+/**
+ * Player for media files we host ourselves, using a plain video element
+ */
+export default class MediaPlayer extends Component<MediaPlayerProps> {
+  elem: HTMLVideoElement | null = null
+
+  // Same interface as the YouTube and Vimeo players so the caption list can seek
+  player: PlayerControls = {
+    seek: (seconds: number) => {
+      if (this.elem) this.elem.currentTime = seconds
+    },
+    pause: () => this.elem?.pause(),
+  }
+
+  render(props: MediaPlayerProps) {
+    return (
+      <div class={props.class}>
+        <video
+          ref={elem => (this.elem = elem)}
+          src={props.src}
+          controls
+          preload="metadata"
+          onTimeUpdate={event => props.onTimeUpdate(event.currentTarget.currentTime)}
+        />
+      </div>
+    )
+  }
+}
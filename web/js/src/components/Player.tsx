@@ -3,12 +3,16 @@ import { useState } from "preact/hooks"
 
 // @ts-ignore
 import YouTubePlayer from "./YouTubePlayer"
+import VimeoPlayer from "./VimeoPlayer"
+import MediaPlayer from "./MediaPlayer"
 
 import type { Ref } from "preact/hooks"
-import type { VideoInfo, Caption, CaptionState } from "../types"
+import type { Caption, CaptionState, PlayerHandle, VideoSource } from "../types"
 
-interface PlayerProps extends CaptionState, Pick<VideoInfo, "videoId"> {
-  playerRef: Ref<HTMLDivElement>
+interface PlayerProps extends CaptionState {
+  playerRef: Ref<PlayerHandle>
+  source: VideoSource
+  sourceId: string
 }
 
 import "../styles/player.css"
@@ -16,7 +20,8 @@ import "../styles/player.css"
 export default function Player(props: PlayerProps) {
   let {
     playerRef,
-    videoId,
+    source,
+    sourceId,
     captions,
     activeCaption,
     setActiveCaption,
@@ -47,12 +52,28 @@ export default function Player(props: PlayerProps) {
 
   return (
     <div class="player">
-      <YouTubePlayer
-        class="player-iframe"
-        ref={playerRef}
-        videoId={videoId}
-        onTimeUpdate={(currentTime: number) => updateActiveCaption(currentTime)}
-      />
+      {source === "vimeo" ? (
+        <VimeoPlayer
+          class="player-iframe"
+          ref={playerRef}
+          videoId={sourceId}
+          onTimeUpdate={updateActiveCaption}
+        />
+      ) : source === "url" ? (
+        <MediaPlayer
+          class="player-media"
+          ref={playerRef}
+          src={sourceId}
+          onTimeUpdate={updateActiveCaption}
+        />
+      ) : (
+        <YouTubePlayer
+          class="player-iframe"
+          ref={playerRef}
+          videoId={sourceId}
+          onTimeUpdate={(currentTime: number) => updateActiveCaption(currentTime)}
+        />
+      )}
 
       {hasActiveCaption && (
         <div class="active-caption">{activeCaption.text}</div>
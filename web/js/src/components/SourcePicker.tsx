@@ -0,0 +1,32 @@
+import { h } from "preact"
+
+import type { SourceCaption } from "../types"
+
+interface SourcePickerProps {
+  sources: SourceCaption[]
+  selectSource(id: string): void
+}
+
+export default function SourcePicker(props: SourcePickerProps) {
+  let { sources, selectSource } = props
+
+  return (
+    <div class="source-picker">
+      <span>Start from existing captions:</span>
+      <select
+        value=""
+        onChange={event => selectSource(event.currentTarget.value)}
+      >
+        <option value="" disabled>
+          Choose a caption track
+        </option>
+        {sources.map(source => (
+          <option key={source.id} value={source.id}>
+            {source.name || source.language}
+            {source.autoGenerated ? " (auto-generated)" : ""}
+          </option>
+        ))}
+      </select>
+    </div>
+  )
+}
@@ -0,0 +1,68 @@
+import { h } from "preact"
+import { nanoid } from "nanoid"
+
+import type { Speaker } from "../types"
+
+const DEFAULT_COLORS = ["#e6194b", "#3cb44b", "#4363d8", "#f58231", "#911eb4"]
+
+interface SpeakerListProps {
+  speakers: Speaker[]
+  updateSpeakers(speakers: Speaker[]): void
+  removeSpeaker(id: string): void
+}
+
+export default function SpeakerList(props: SpeakerListProps) {
+  let { speakers, updateSpeakers, removeSpeaker } = props
+
+  function addSpeaker(): void {
+    let color = DEFAULT_COLORS[speakers.length % DEFAULT_COLORS.length]
+    updateSpeakers([
+      ...speakers,
+      { id: nanoid(), name: `Speaker ${speakers.length + 1}`, color },
+    ])
+  }
+
+  function updateSpeaker(id: string, field: "name" | "color", value: string): void {
+    updateSpeakers(
+      speakers.map(speaker =>
+        speaker.id === id ? { ...speaker, [field]: value } : speaker
+      )
+    )
+  }
+
+  return (
+    <div class="speaker-list">
+      <span>Speakers:</span>
+      {speakers.map(speaker => (
+        <span key={speaker.id} class="speaker">
+          <input
+            type="color"
+            title="Speaker color"
+            value={speaker.color}
+            onChange={event =>
+              updateSpeaker(speaker.id, "color", event.currentTarget.value)
+            }
+          />
+          <input
+            type="text"
+            title="Speaker name"
+            value={speaker.name}
+            onChange={event =>
+              updateSpeaker(speaker.id, "name", event.currentTarget.value)
+            }
+          />
+          <button
+            class="caption-button delete"
+            title="Remove this speaker"
+            onClick={() => removeSpeaker(speaker.id)}
+          >
+            &times;
+          </button>
+        </span>
+      ))}
+      <button class="caption-button" title="Add speaker" onClick={addSpeaker}>
+        +
+      </button>
+    </div>
+  )
+}
@@ -0,0 +1,54 @@
+import { h, Component } from "preact"
+
+import type { PlayerControls } from "../types"
+
+interface VimeoPlayerProps {
+  class?: string
+  videoId: string
+  onTimeUpdate(currentTime: number): void
+}
+
+declare global {
+  interface Window {
+    // Loaded from player.vimeo.com by the edit template
+    Vimeo: any
+  }
+}
+
+/**
+ * Wraps the Vimeo player SDK
+ */
+export default class VimeoPlayer extends Component<VimeoPlayerProps> {
+  elem: HTMLDivElement | null = null
+  vimeo: any = null
+
+  // Same interface as the YouTube player so the caption list can seek
+  player: PlayerControls = {
+    seek: (seconds: number) => this.vimeo?.setCurrentTime(seconds),
+    pause: () => this.vimeo?.pause(),
+  }
+
+  componentDidMount() {
+    this.vimeo = new window.Vimeo.Player(this.elem, {
+      id: Number(this.props.videoId),
+      responsive: true,
+    })
+    this.vimeo.on("timeupdate", ({ seconds }: { seconds: number }) =>
+      this.props.onTimeUpdate(seconds)
+    )
+  }
+
+  componentWillUnmount() {
+    this.vimeo?.destroy()
+    this.vimeo = null
+  }
+
+  shouldComponentUpdate() {
+    // The SDK owns the iframe
+    return false
+  }
+
+  render(props: VimeoPlayerProps) {
+    return <div class={props.class} ref={elem => (this.elem = elem)} />
+  }
+}
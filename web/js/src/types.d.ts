@@ -4,15 +4,62 @@ declare global {
   interface Window {
     VIDEO_ID: string
     SUBTITLE_LANG: string
+    CSRF_TOKEN: string
+    VIDEO_SOURCE: VideoSource
+    VIDEO_SOURCE_ID: string
   }
 }
 
-type EditableCaptionField = "id" | "startTimestamp" | "endTimestamp" | "text"
+/** Where a video is played from, `sourceId` is the YouTube/Vimeo id or the media URL */
+export type VideoSource = "youtube" | "vimeo" | "url"
+
+/** What the caption list needs from any of the players */
+export interface PlayerControls {
+  seek(seconds: number): void
+  pause(): void
+}
+
+/** Player components expose their controls on `player` */
+export interface PlayerHandle {
+  player: PlayerControls
+}
+
+type EditableCaptionField =
+  | "id"
+  | "startTimestamp"
+  | "endTimestamp"
+  | "text"
+  | "speaker"
+  | "position"
+  | "alignment"
+  | "notes"
+
+export interface Word {
+  startSeconds: number
+  text: string
+}
+
+/** `Subtitles.Position` in the API: bottom, top */
+export type Position = 0 | 1
+
+/** `Subtitles.Alignment` in the API: center, left, right */
+export type Alignment = 0 | 1 | 2
+
+export interface Speaker {
+  id: string
+  name: string
+  color: string
+}
 
 export interface BaseCaption {
   startSeconds: number
   endSeconds: number
   text: string
+  words?: Word[]
+  speaker?: string
+  position?: Position
+  alignment?: Alignment
+  notes?: string
 }
 
 export interface Caption extends BaseCaption {
@@ -23,6 +70,7 @@ export interface Caption extends BaseCaption {
 
 export interface CaptionData extends VideoInfo {
   entries: BaseCaption[]
+  speakers?: Speaker[]
 }
 
 export interface CaptionState {
@@ -56,4 +104,20 @@ export interface VideoInfo {
   uploaderId?: string
   uploaderName?: string
   isVideoLong?: boolean
+  durationSeconds?: number
+  coverage?: number
+}
+
+export interface SourceCaption {
+  id: string
+  language: string
+  name: string
+  autoGenerated: boolean
+  translatable: boolean
+}
+
+export interface ApiError {
+  message: string
+  reason?: string
+  metadata: { [key: string]: string }
 }
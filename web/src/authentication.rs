@@ -1,15 +1,25 @@
-use crate::{settings::Settings, ApiConn, Auth};
+use crate::{
+    error::{csrf_error, ApiResult},
+    sessions::{self, CsrfForm, CsrfToken, Session, SessionStore},
+    settings::{Flow, Settings},
+    ApiConn, Auth
+};
 use api_types::user::{user_service_client::UserServiceClient, User, UserIdentity};
 use chrono::{DateTime, NaiveDateTime, Utc};
-use openid::{Bearer, Options, Token, DiscoveredClient, StandardClaims};
+use openid::{
+    error::{ClientError, OAuth2ErrorCode},
+    Bearer, Options, Token, DiscoveredClient, StandardClaims
+};
 use rocket::{
-    futures::executor,
-    http::{uri::Origin, Cookie, CookieJar},
+    http::{uri::Origin, Cookie, CookieJar, SameSite},
     outcome::IntoOutcome,
-    request::{FromRequest, Outcome},
+    request::{Form, FromRequest, Outcome},
     response::{status::Unauthorized, Redirect},
     Request, State
 };
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use url::Url;
 use std::convert::Infallible;
 use tonic::{
     metadata::{Ascii, MetadataValue},
@@ -20,10 +30,6 @@ use parking_lot::RwLock;
 use std::ops::Deref;
 use api_types::subtitles::video_subs_client::VideoSubsClient;
 
-const ACCESS_TOKEN_NAME: &str = "__auth_access__";
-const ID_TOKEN_NAME: &str = "__auth_identity__";
-const REFRESH_TOKEN_NAME: &str = "__auth_refresh__";
-
 pub struct CurrentUser(User);
 
 impl Deref for CurrentUser {
@@ -36,25 +42,6 @@ impl Deref for CurrentUser {
 
 pub type AuthClient = DiscoveredClient;
 
-fn refresh_if_expired(
-    request: &Request<'_>,
-    mut token: Token,
-    auth: &AuthClient
-) -> Option<Token> {
-    if token.bearer.expired() {
-        let cookies = request.cookies();
-        let bearer = executor::block_on(auth.ensure_token(token.bearer)).ok()?;
-        for cookie in auth_cookies(&bearer) {
-            cookies.add_private(cookie);
-        }
-        token = bearer.into();
-        if let Some(id_token) = token.id_token.as_mut() {
-            auth.decode_token(id_token).ok()?;
-        }
-    }
-    Some(token)
-}
-
 fn validate(token: Token, auth: &AuthClient) -> Option<Token> {
     if let Some(id_token) = token.id_token.as_ref() {
         auth.validate_token(id_token, None, None).ok()?;
@@ -63,29 +50,21 @@ fn validate(token: Token, auth: &AuthClient) -> Option<Token> {
 }
 
 fn scopes(settings: &Settings) -> Option<String> {
-    Some(format!(
-        "email offline_access {}/User",
-        settings.authentication.api_url
-    ))
+    let authentication = &settings.authentication;
+    let full_access = match authentication.full_access_scope.as_str() {
+        "" => format!("{}/User", authentication.api_url),
+        scope => scope.to_string()
+    };
+    Some(format!("email offline_access {}", full_access))
 }
 
-fn token_from_cookies(
-    cookies: &CookieJar<'_>,
-    settings: &Settings,
-    auth: &AuthClient
-) -> Option<Token> {
-    let access = cookies.get_private(ACCESS_TOKEN_NAME)?.value().to_string();
-    let id = cookies
-        .get_private(ID_TOKEN_NAME)
-        .map(|cookie| cookie.value().to_string());
-    let refresh = cookies.get_private(REFRESH_TOKEN_NAME)?.value().to_string();
-
+fn token_from_session(session: Session, settings: &Settings, auth: &AuthClient) -> Option<Token> {
     let mut token: Token = Bearer {
-        access_token: access,
+        access_token: session.access_token,
         scope: scopes(settings),
-        refresh_token: Some(refresh),
+        refresh_token: session.refresh_token,
         expires: None,
-        id_token: id
+        id_token: session.id_token
     }
     .into();
     if let Some(token) = token.id_token.as_mut() {
@@ -101,6 +80,60 @@ fn token_from_cookies(
     Some(token)
 }
 
+/// Ends a session whose tokens can't be used anymore, so the user is sent to sign in again.
+fn end_session(session_id: &str, sessions: &SessionStore, cookies: &CookieJar<'_>) {
+    sessions.remove(session_id);
+    sessions::remove_session_cookie(cookies);
+}
+
+/// The token of a session, refreshed first if it expired. Sessions whose refresh token was
+/// rejected are ended, other errors only fail this request so the next one tries again.
+async fn session_token(
+    session_id: &str,
+    auth: &AuthClient,
+    settings: &Settings,
+    sessions: &SessionStore,
+    cookies: &CookieJar<'_>
+) -> Option<Token> {
+    let session = sessions.get(session_id)?;
+    sessions::add_session_cookie(cookies, sessions, session_id.to_string());
+    let refreshing = session.refreshing.clone();
+    let token = token_from_session(session, settings, auth)?;
+    if !token.bearer.expired() {
+        return validate(token, auth);
+    }
+
+    // Only one request per session refreshes, the others wait for it and use the new token
+    let _refreshing = refreshing.lock().await;
+    let token = token_from_session(sessions.get(session_id)?, settings, auth)?;
+    if !token.bearer.expired() {
+        return validate(token, auth);
+    }
+    if token.bearer.refresh_token.is_none() {
+        end_session(session_id, sessions, cookies);
+        return None;
+    }
+    match auth.ensure_token(token.bearer).await {
+        Ok(bearer) => {
+            sessions.update_tokens(session_id, &bearer);
+            let mut token: Token = bearer.into();
+            if let Some(id_token) = token.id_token.as_mut() {
+                auth.decode_token(id_token).ok()?;
+            }
+            validate(token, auth)
+        }
+        // The refresh token expired or was revoked
+        Err(ClientError::OAuth2(err)) if matches!(err.error, OAuth2ErrorCode::InvalidGrant) => {
+            end_session(session_id, sessions, cookies);
+            None
+        }
+        Err(err) => {
+            eprintln!("Couldn't refresh the tokens of a session: {:?}", err);
+            None
+        }
+    }
+}
+
 fn token_claims(token: &Token) -> Option<&StandardClaims> {
     token.id_token.as_ref()?.payload().ok()
 }
@@ -116,6 +149,15 @@ impl UserCache {
         }
     }
 
+    /// Replaces the cached user after their profile changed.
+    pub fn update(&self, user: User) {
+        self.cache.write().insert(user.id.clone(), user);
+    }
+
+    pub fn remove(&self, id: &str) {
+        self.cache.write().remove(id);
+    }
+
     pub async fn get(&self, id: &str, api: &AuthenticatedApiConn<'_>) -> Option<User> {
         let mut existing = {
             let cache = self.cache.read();
@@ -147,16 +189,21 @@ impl<'a, 'r> FromRequest<'a, 'r> for AuthToken {
     type Error = Infallible;
 
     async fn from_request(request: &'a Request<'r>) -> Outcome<Self, Self::Error> {
-        request
+        let cookies = request.cookies();
+        let state = request
             .managed_state::<AuthClient>()
             .zip(request.managed_state::<Settings>())
-            .and_then(|(auth, settings)| {
-                token_from_cookies(request.cookies(), settings, auth)
-                    .and_then(|token| refresh_if_expired(request, token, auth))
-                    .and_then(|token| validate(token, auth))
-            })
-            .map(|token| AuthToken(token))
-            .or_forward(())
+            .zip(request.managed_state::<SessionStore>())
+            .map(Flatten::flatten)
+            .zip(sessions::session_id(cookies));
+
+        let token = match state {
+            Some(((auth, settings, sessions), session_id)) => {
+                session_token(&session_id, auth, settings, sessions, cookies).await
+            }
+            None => None
+        };
+        token.map(AuthToken).or_forward(())
     }
 }
 
@@ -207,7 +254,7 @@ fn bearer(user: &AuthToken) -> MetadataValue<Ascii> {
     let mut result = String::with_capacity(token.len() + 7);
     result.push_str("Bearer ");
     result.push_str(token);
-    MetadataValue::from_str(&token).unwrap()
+    MetadataValue::from_str(&result).unwrap()
 }
 
 pub struct AuthenticatedApiConn<'r> {
@@ -263,77 +310,274 @@ impl<'a, 'r> FromRequest<'a, 'r> for AuthenticatedApiConn<'r> {
     }
 }
 
-fn auth_cookies(token: &Bearer) -> Vec<Cookie<'static>> {
-    let access = Cookie::new(ACCESS_TOKEN_NAME, token.access_token.clone());
-    let refresh = Cookie::new(REFRESH_TOKEN_NAME, token.refresh_token.clone().unwrap());
-    if let Some(id_token) = &token.id_token {
-        vec![
-            access,
-            Cookie::new(ID_TOKEN_NAME, id_token.clone()),
-            refresh,
-        ]
+const LOGIN_COOKIE: &str = "__login__";
+const REDIRECT_COOKIE: &str = "redirect_to";
+
+/// What `/login` generated for the identity provider, kept in a private cookie until it sends
+/// the browser back. The state ties the callback to this browser, the nonce ties the ID token to
+/// this login and the verifier ties the code to this login (PKCE).
+#[derive(Serialize, Deserialize)]
+struct LoginAttempt {
+    state: String,
+    nonce: String,
+    verifier: String,
+    redirect_to: Option<String>
+}
+
+/// Only paths on this site are followed after signing in, anything else could send the user to
+/// another site right after they trusted the login page.
+fn safe_redirect(target: &str) -> Option<&str> {
+    let same_origin = target.starts_with('/')
+        && !target.starts_with("//")
+        && !target.starts_with("/\\")
+        && !target.chars().any(char::is_control);
+    Some(target).filter(|_| same_origin)
+}
+
+fn code_challenge(verifier: &str) -> String {
+    base64::encode_config(Sha256::digest(verifier.as_bytes()), base64::URL_SAFE_NO_PAD)
+}
+
+/// Starts the policy of a flow, or sends the browser to the flow's page. `None` if it's disabled.
+fn start_flow(
+    auth: &AuthClient,
+    settings: &Settings,
+    cookies: &CookieJar<'_>,
+    flow: Flow<'_>,
+    redirect_to: Option<String>
+) -> Option<Redirect> {
+    if !flow.is_enabled() {
+        None
+    } else if !flow.url.is_empty() {
+        Some(Redirect::found(flow.url.to_string()))
     } else {
-        vec![access, refresh]
+        Some(start_login(auth, settings, cookies, Some(flow.policy), redirect_to))
+    }
+}
+
+/// Where a login or flow was meant to end up, the start page if it didn't say.
+fn finish_redirect(redirect_to: Option<&str>) -> Redirect {
+    match redirect_to.and_then(safe_redirect) {
+        Some(target) => Redirect::found(target.to_string()),
+        None => Redirect::found(uri!(super::index))
     }
 }
 
-#[get("/login/oauth2/code/oidc?<code>")]
+#[get("/login/oauth2/code/oidc?<code>&<state>")]
 pub async fn login(
     auth: Auth<'_>,
     code: String,
+    state: String,
+    sessions: State<'_, SessionStore>,
     cookies: &CookieJar<'_>
 ) -> Result<Redirect, Unauthorized<String>> {
-    match request_token(auth, code).await {
-        Ok(None) => Err(Unauthorized(None)),
-        Err(err) => Err(Unauthorized(Some(format!("{:?}", err)))),
-        Ok(Some(token)) => {
-            for cookie in auth_cookies(&token.bearer) {
-                cookies.add_private(cookie);
-            }
-            let redirect_cookie = cookies.get("redirect_to");
-            if let Some(cookie) = redirect_cookie {
-                let value = cookie.value().to_string();
-                cookies.remove(cookie.clone());
-                Ok(Redirect::found(value))
-            } else {
-                Ok(Redirect::found(uri!(super::index)))
-            }
-        }
+    let attempt = cookies
+        .get_private(LOGIN_COOKIE)
+        .and_then(|cookie| serde_json::from_str::<LoginAttempt>(cookie.value()).ok());
+    cookies.remove_private(Cookie::named(LOGIN_COOKIE));
+    let attempt = match attempt {
+        Some(attempt) if sessions::constant_time_eq(&state, &attempt.state) => attempt,
+        _ => return Err(Unauthorized(Some("Login expired or was started elsewhere".to_string())))
+    };
+
+    let token = request_token(&auth, code, &attempt).await.map_err(|err| Unauthorized(Some(err)))?;
+    let user_id = token_claims(&token).map(|claims| claims.sub.clone()).unwrap_or_default();
+    // A new session id, so one planted in this browser before signing in is worthless
+    if let Some(previous) = sessions::session_id(cookies) {
+        sessions.remove(&previous);
     }
+    let session_id = sessions.create(Session::new(user_id, &token.bearer));
+    sessions::add_session_cookie(cookies, &sessions, session_id);
+    Ok(finish_redirect(attempt.redirect_to.as_deref()))
 }
 
-async fn request_token(
+/// The identity provider sends errors to the callback instead of a code. The forgot password
+/// error starts the password reset, keeping where the user wanted to go, and cancelling a flow
+/// goes back there.
+#[get("/login/oauth2/code/oidc?<error>&<error_description>&<state>", rank = 2)]
+pub fn login_error(
     auth: Auth<'_>,
-    code: String
-) -> Result<Option<Token>, openid::error::Error> {
-    let mut token: Token = auth.request_token(&code).await?.into();
-    if let Some(mut id_token) = token.id_token.as_mut() {
-        auth.decode_token(&mut id_token)?;
-        auth.validate_token(&id_token, None, None)?;
-        //eprintln!("token: {:#?}", id_token);
-    } else {
-        return Ok(None);
+    error: String,
+    error_description: Option<String>,
+    state: Option<String>,
+    settings: State<'_, Settings>,
+    cookies: &CookieJar<'_>
+) -> Result<Redirect, Unauthorized<String>> {
+    let attempt = cookies
+        .get_private(LOGIN_COOKIE)
+        .and_then(|cookie| serde_json::from_str::<LoginAttempt>(cookie.value()).ok())
+        .filter(|attempt| {
+            let state = state.as_deref().unwrap_or_default();
+            sessions::constant_time_eq(state, &attempt.state)
+        });
+    cookies.remove_private(Cookie::named(LOGIN_COOKIE));
+
+    let description = error_description.unwrap_or_default();
+    let is_error = |code: &str| !code.is_empty() && description.starts_with(code);
+    let redirect_to = attempt.and_then(|attempt| attempt.redirect_to);
+    if is_error(&settings.authentication.forgot_password_error) {
+        let reset = settings.authentication.reset_password();
+        if let Some(redirect) = start_flow(&auth, &settings, cookies, reset, redirect_to) {
+            return Ok(redirect);
+        }
+    } else if is_error(&settings.authentication.cancelled_error) {
+        return Ok(finish_redirect(redirect_to.as_deref()));
     }
+    Err(Unauthorized(Some(format!("{}: {}", error, description))))
+}
 
-    Ok(Some(token))
+/// Exchanges the code for tokens. Done by hand because the client doesn't send a code verifier.
+async fn request_token(auth: &AuthClient, code: String, attempt: &LoginAttempt) -> Result<Token, String> {
+    let redirect_uri = auth.redirect_uri.as_deref().ok_or("No redirect URI configured")?;
+    let params = [
+        ("grant_type", "authorization_code"),
+        ("code", code.as_str()),
+        ("redirect_uri", redirect_uri),
+        ("code_verifier", attempt.verifier.as_str())
+    ];
+    let bearer: Bearer = auth
+        .http_client
+        .post(auth.config().token_endpoint.clone())
+        .basic_auth(&auth.client_id, Some(&auth.client_secret))
+        .form(&params)
+        .send()
+        .await
+        .and_then(|response| response.error_for_status())
+        .map_err(|err| err.to_string())?
+        .json()
+        .await
+        .map_err(|err| err.to_string())?;
+
+    let mut token: Token = bearer.into();
+    let id_token = token.id_token.as_mut().ok_or("No ID token in the response")?;
+    auth.decode_token(id_token).map_err(|err| format!("{:?}", err))?;
+    auth.validate_token(id_token, Some(&attempt.nonce), None)
+        .map_err(|err| format!("{:?}", err))?;
+    Ok(token)
 }
 
 #[get("/login")]
-pub async fn authorize(auth: Auth<'_>, settings: State<'_, Settings>) -> Redirect {
-    let auth_url = auth.auth_url(&Options {
-        scope: Some(format!(
-            "email offline_access {}/User",
-            settings.authentication.api_url
-        )),
+pub async fn authorize(auth: Auth<'_>, settings: State<'_, Settings>, cookies: &CookieJar<'_>) -> Redirect {
+    let redirect_to = cookies.get(REDIRECT_COOKIE).map(|cookie| cookie.value().to_string());
+    cookies.remove(Cookie::named(REDIRECT_COOKIE));
+    start_login(&auth, &settings, cookies, None, redirect_to)
+}
+
+/// Lets the user change what the identity provider knows about them, like their email.
+#[get("/profile/edit-identity")]
+pub fn edit_identity(auth: Auth<'_>, settings: State<'_, Settings>, cookies: &CookieJar<'_>) -> Option<Redirect> {
+    let flow = settings.authentication.edit_profile();
+    start_flow(&auth, &settings, cookies, flow, Some("/profile".to_string()))
+}
+
+#[get("/password/reset")]
+pub fn reset_password(auth: Auth<'_>, settings: State<'_, Settings>, cookies: &CookieJar<'_>) -> Option<Redirect> {
+    let flow = settings.authentication.reset_password();
+    start_flow(&auth, &settings, cookies, flow, None)
+}
+
+/// Sends the browser to the identity provider, running `policy` instead of the default sign in
+/// if set. It comes back to [`login`] with the tokens.
+fn start_login(
+    auth: &AuthClient,
+    settings: &Settings,
+    cookies: &CookieJar<'_>,
+    policy: Option<&str>,
+    redirect_to: Option<String>
+) -> Redirect {
+    let attempt = LoginAttempt {
+        state: sessions::random_token(),
+        nonce: sessions::random_token(),
+        verifier: sessions::random_token(),
+        redirect_to
+    };
+
+    let mut auth_url = auth.auth_url(&Options {
+        scope: scopes(settings),
+        state: Some(attempt.state.clone()),
+        nonce: Some(attempt.nonce.clone()),
         ..Default::default()
     });
+    {
+        let mut query = auth_url.query_pairs_mut();
+        query
+            .append_pair("code_challenge", &code_challenge(&attempt.verifier))
+            .append_pair("code_challenge_method", "S256");
+        if let Some(policy) = policy {
+            query.append_pair("p", policy);
+        }
+    }
 
-    eprintln!("authorize: {}", auth_url);
-
+    // Lax so it's sent along when the identity provider redirects back
+    let attempt = serde_json::to_string(&attempt).expect("Login attempts are always serializable");
+    cookies.add_private(Cookie::build(LOGIN_COOKIE, attempt).same_site(SameSite::Lax).finish());
     Redirect::found(auth_url.to_string())
 }
 
+/// Sends the browser to the identity provider to end the session there too, if it supports
+/// that. It's sent back to the start page afterwards.
+fn end_session_redirect(auth: &AuthClient, session: Option<Session>) -> Redirect {
+    let mut url = match &auth.config().end_session_endpoint {
+        Some(endpoint) => endpoint.clone(),
+        None => return Redirect::to("/")
+    };
+    {
+        let mut query = url.query_pairs_mut();
+        if let Some(id_token) = session.and_then(|session| session.id_token) {
+            query.append_pair("id_token_hint", &id_token);
+        }
+        let home = auth.redirect_uri.as_ref()
+            .and_then(|uri| Url::parse(uri).ok())
+            .and_then(|uri| uri.join("/").ok());
+        if let Some(home) = home {
+            query.append_pair("post_logout_redirect_uri", home.as_str());
+        }
+    }
+    Redirect::found(url.to_string())
+}
+
+/// Without a session there's nothing to protect, signing out again is harmless.
+fn check_logout_csrf(csrf: Option<CsrfToken>, form: &CsrfForm) -> ApiResult<()> {
+    match csrf {
+        Some(csrf) if !csrf.matches(&form.csrf_token) => Err(csrf_error()),
+        _ => Ok(())
+    }
+}
+
+/// Ends the session of this browser.
+#[post("/logout", data = "<form>")]
+pub fn logout(
+    auth: Auth<'_>,
+    form: Form<CsrfForm>,
+    csrf: Option<CsrfToken>,
+    sessions: State<'_, SessionStore>,
+    cookies: &CookieJar<'_>
+) -> ApiResult<Redirect> {
+    check_logout_csrf(csrf, &form)?;
+    let session = sessions::session_id(cookies).and_then(|id| sessions.remove(&id));
+    sessions::remove_session_cookie(cookies);
+    Ok(end_session_redirect(&auth, session))
+}
+
+/// Ends every session of the current user, e.g. after losing a device.
+#[post("/logout/everywhere", data = "<form>")]
+pub fn logout_everywhere(
+    auth: Auth<'_>,
+    form: Form<CsrfForm>,
+    csrf: Option<CsrfToken>,
+    sessions: State<'_, SessionStore>,
+    cookies: &CookieJar<'_>
+) -> ApiResult<Redirect> {
+    check_logout_csrf(csrf, &form)?;
+    let session = sessions::session_id(cookies).and_then(|id| sessions.get(&id));
+    if let Some(session) = &session {
+        sessions.remove_user(&session.user_id);
+    }
+    sessions::remove_session_cookie(cookies);
+    Ok(end_session_redirect(&auth, session))
+}
+
 pub fn unauthorized_redirect(current_uri: Origin, cookies: &CookieJar<'_>) -> Redirect {
-    cookies.add(Cookie::new("redirect_to", current_uri.to_string()));
+    cookies.add(Cookie::new(REDIRECT_COOKIE, current_uri.to_string()));
     Redirect::to(uri!(authorize))
 }
@@ -0,0 +1,52 @@
+use api_types::errors::ErrorInfo;
+use rocket::{http::Status, response::status::Custom};
+use rocket_contrib::json::Json;
+use serde::Serialize;
+use std::collections::HashMap;
+use tonic::Code;
+
+/// JSON body of a failed API call, so the frontend can tell failures apart.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ApiError {
+    pub message: String,
+    pub reason: Option<String>,
+    pub metadata: HashMap<String, String>
+}
+
+pub type ApiResult<T> = Result<T, Custom<Json<ApiError>>>;
+
+fn http_status(code: Code) -> Status {
+    match code {
+        Code::InvalidArgument | Code::FailedPrecondition | Code::OutOfRange => Status::BadRequest,
+        Code::Unauthenticated => Status::Unauthorized,
+        Code::PermissionDenied => Status::Forbidden,
+        Code::NotFound => Status::NotFound,
+        Code::AlreadyExists | Code::Aborted => Status::Conflict,
+        Code::ResourceExhausted => Status::TooManyRequests,
+        Code::Unimplemented => Status::NotImplemented,
+        Code::Unavailable => Status::ServiceUnavailable,
+        Code::DeadlineExceeded => Status::GatewayTimeout,
+        _ => Status::InternalServerError
+    }
+}
+
+/// Response to a form that didn't send the session's CSRF token.
+pub fn csrf_error() -> Custom<Json<ApiError>> {
+    let error = ApiError {
+        message: "Missing or invalid CSRF token, reload the page and try again".to_string(),
+        reason: Some("CSRF_TOKEN_MISMATCH".to_string()),
+        metadata: HashMap::new()
+    };
+    Custom(Status::Forbidden, Json(error))
+}
+
+pub fn api_error(status: tonic::Status) -> Custom<Json<ApiError>> {
+    let info = ErrorInfo::from_status(&status);
+    let error = ApiError {
+        message: status.message().to_string(),
+        reason: info.as_ref().map(|info| info.reason.clone()),
+        metadata: info.map(|info| info.metadata).unwrap_or_default()
+    };
+    Custom(http_status(status.code()), Json(error))
+}
@@ -20,15 +20,20 @@ use std::{error::Error, io, path::PathBuf, env};
 use tonic::transport::Channel;
 pub use api_types::user::User;
 use crate::authentication::{UserCache, unauthorized_redirect};
-use api_types::subtitles::video_subs_client::VideoSubsClient;
+use crate::sessions::{CsrfToken, SessionStore};
+use api_types::subtitles::{video::Source, video_subs_client::VideoSubsClient, VideoId};
+use crate::error::{api_error, ApiResult};
 use rocket::http::{CookieJar};
 use rocket::response::Redirect;
 
 mod authentication;
+mod error;
 mod profile;
+mod sessions;
 mod settings;
 mod templates;
 mod subtitles;
+mod videos;
 
 type Template = Html<Vec<u8>>;
 pub type API<'a> = State<'a, ApiConn>;
@@ -63,8 +68,22 @@ fn asset(path: PathBuf) -> Result<&'static [u8], NotFound<()>> {
 }
 
 #[get("/edit/<video_id>?<lang>")]
-fn edit(video_id: String, lang: String, _conn: AuthenticatedApiConn<'_>) -> Template {
-    template(|w| edit_html(w, &video_id, &lang))
+async fn edit(
+    video_id: String,
+    lang: String,
+    api: AuthenticatedApiConn<'_>,
+    csrf: CsrfToken
+) -> ApiResult<Template> {
+    let video = api.subtitles().get_video(VideoId { video_id: video_id.clone() })
+        .await
+        .map_err(api_error)?
+        .into_inner();
+    let source = match video.source() {
+        Source::YouTube => "youtube",
+        Source::Vimeo => "vimeo",
+        Source::Url => "url"
+    };
+    Ok(template(|w| edit_html(w, &video_id, &lang, source, &video.source_id, &csrf.0)))
 }
 
 #[get("/edit/<video_id>?<lang>", rank = 2)]
@@ -104,7 +123,8 @@ async fn main() -> Result<(), Box<dyn Error>> {
                     client_secret,
                     issuer,
                     ..
-                }
+                },
+            ..
         } = &settings;
 
         AuthClient::discover(
@@ -120,6 +140,7 @@ async fn main() -> Result<(), Box<dyn Error>> {
     let channel = Channel::from_static("http://[::1]:50051").connect().await?;
 
     let user_cache = UserCache::new();
+    let sessions = SessionStore::load(&settings.sessions)?;
 
     rocket::ignite()
         .attach(SpaceHelmet::default())
@@ -127,6 +148,7 @@ async fn main() -> Result<(), Box<dyn Error>> {
         .manage(ApiConn(channel))
         .manage(settings)
         .manage(user_cache)
+        .manage(sessions)
         .mount(
             "/",
             routes![
@@ -134,18 +156,36 @@ async fn main() -> Result<(), Box<dyn Error>> {
                 index2,
                 asset,
                 authentication::login,
+                authentication::login_error,
                 authentication::authorize,
+                authentication::edit_identity,
+                authentication::reset_password,
+                authentication::logout,
+                authentication::logout_everywhere,
                 profile::profile,
                 profile::profile_unauthorized,
+                profile::update_profile,
+                profile::user_page,
+                profile::export_data,
+                profile::delete_account,
+                profile::api_tokens,
+                profile::create_api_token,
+                profile::revoke_api_token,
+                profile::profile_picture,
                 edit,
                 edit_redirect
             ]
         )
         .mount("/subtitles", routes![
             subtitles::get_subtitles,
+            subtitles::list_source_captions,
             subtitles::set_subtitles,
             subtitles::download_subtitles
         ])
+        .mount("/videos", routes![
+            videos::get_video,
+            videos::add_video
+        ])
         .mount("/js", StaticFiles::from("./js"))
         .mount("/asset", StaticFiles::from("./assets"))
         .launch()
@@ -1,18 +1,427 @@
 use crate::{
-    authentication::unauthorized_redirect,
+    authentication::{unauthorized_redirect, UserCache},
+    error::{api_error, csrf_error, ApiResult},
+    sessions::{self, CsrfForm, CsrfToken, SessionStore},
+    settings::Settings,
+    subtitles::File,
     template,
-    templates::profile_html,
-    Template
+    templates::{profile_html, tokens_html, user_html},
+    AuthAPI, Template, User, API
 };
-use rocket::{http::CookieJar, response::Redirect};
+use api_types::user::{
+    profile_picture_request::Format, ApiToken, CreateApiTokenRequest, DeleteAccountRequest,
+    ExportRequest, LanguageSkill, ListApiTokensRequest, ProfilePictureRequest,
+    RevokeApiTokenRequest, UpdateProfileRequest, UserStatsRequest
+};
+use chrono::NaiveDateTime;
+use rocket::{
+    http::{ContentType, CookieJar, Header, Status},
+    request::{Form, FromRequest, Outcome},
+    response::{self, Redirect, Responder},
+    Request, Response, State
+};
+use std::{collections::BTreeMap, convert::Infallible};
 use crate::authentication::CurrentUser;
 
+/// Pictures are addressed by version, so a changed picture gets a new URL anyway.
+const PICTURE_MAX_AGE: u32 = 86_400;
+/// The default avatar changes once a picture is uploaded, so don't keep it around for long.
+const DEFAULT_AVATAR_MAX_AGE: u32 = 300;
+
+#[derive(FromForm)]
+pub struct ProfileForm {
+    username: String,
+    display_name: String,
+    bio: String,
+    /// Comma separated language codes
+    caption_languages: String,
+    translate_languages: String,
+    csrf_token: String
+}
+
+#[derive(FromForm)]
+pub struct DeleteForm {
+    confirm_username: String,
+    csrf_token: String
+}
+
+/// One checkbox per scope, unchecked ones aren't submitted.
+#[derive(FromForm)]
+pub struct TokenForm {
+    name: String,
+    subtitles_read: bool,
+    subtitles_write: bool,
+    user_read: bool,
+    user_write: bool,
+    expires_in_days: u32,
+    csrf_token: String
+}
+
+/// An API token formatted for the token list.
+pub struct TokenRow {
+    pub id: String,
+    pub name: String,
+    pub scopes: String,
+    pub created: String,
+    pub expires: String,
+    pub last_used: String
+}
+
+impl From<ApiToken> for TokenRow {
+    fn from(token: ApiToken) -> Self {
+        TokenRow {
+            scopes: token.scopes.join(", "),
+            created: date(token.created),
+            expires: date(token.expires),
+            last_used: date(token.last_used),
+            id: token.id,
+            name: token.name
+        }
+    }
+}
+
+/// Day of a unix timestamp, timestamps of 0 mean it didn't happen yet.
+fn date(timestamp: i64) -> String {
+    match timestamp {
+        0 => "Never".to_string(),
+        timestamp => NaiveDateTime::from_timestamp(timestamp, 0).format("%Y-%m-%d").to_string()
+    }
+}
+
+fn language_list(user: &User, skill: impl Fn(&LanguageSkill) -> bool) -> String {
+    user.languages.iter()
+        .filter(|language| skill(language))
+        .map(|language| &*language.language)
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Merges the two lists so each language appears once with both of its skills.
+fn language_skills(caption: &str, translate: &str) -> Vec<LanguageSkill> {
+    let codes = |list: &str| {
+        list.split(',')
+            .map(str::trim)
+            .filter(|code| !code.is_empty())
+            .map(str::to_string)
+            .collect::<Vec<_>>()
+    };
+    let mut skills = BTreeMap::new();
+    for language in codes(caption) {
+        skills.entry(language.clone())
+            .or_insert_with(|| LanguageSkill { language, ..Default::default() })
+            .can_caption = true;
+    }
+    for language in codes(translate) {
+        skills.entry(language.clone())
+            .or_insert_with(|| LanguageSkill { language, ..Default::default() })
+            .can_translate = true;
+    }
+    skills.into_iter().map(|(_, skill)| skill).collect()
+}
+
+/// Shown when a form comes back without the session's CSRF token. The page it's shown on has
+/// the right one, so submitting again works if the user really meant to.
+const CSRF_MESSAGE: &str = "The form expired, please submit it again";
+
+fn profile_page(user: &User, csrf: &CsrfToken, settings: &Settings, error: Option<&str>) -> Template {
+    let picture_url = format!("/users/{}/picture?size=128&v={}", user.id, user.picture);
+    let caption_languages = language_list(user, |skill| skill.can_caption);
+    let translate_languages = language_list(user, |skill| skill.can_translate);
+    let can_edit_identity = settings.authentication.edit_profile().is_enabled();
+    let can_reset_password = settings.authentication.reset_password().is_enabled();
+    template(|w| profile_html(
+        w,
+        user,
+        &picture_url,
+        &caption_languages,
+        &translate_languages,
+        can_edit_identity,
+        can_reset_password,
+        &csrf.0,
+        error
+    ))
+}
+
 #[get("/profile")]
-pub async fn profile(user: CurrentUser) -> Template {
-    template(|w| profile_html(w, &user, ""))
+pub async fn profile(user: CurrentUser, csrf: CsrfToken, settings: State<'_, Settings>) -> Template {
+    profile_page(&user, &csrf, &settings, None)
+}
+
+#[post("/profile", data = "<form>")]
+pub async fn update_profile(
+    user: CurrentUser,
+    csrf: CsrfToken,
+    form: Form<ProfileForm>,
+    api: AuthAPI<'_>,
+    cache: State<'_, UserCache>,
+    settings: State<'_, Settings>
+) -> Result<Redirect, Template> {
+    let form = form.into_inner();
+    if !csrf.matches(&form.csrf_token) {
+        return Err(profile_page(&user, &csrf, &settings, Some(CSRF_MESSAGE)));
+    }
+    let languages = language_skills(&form.caption_languages, &form.translate_languages);
+    let request = UpdateProfileRequest {
+        username: form.username,
+        display_name: form.display_name,
+        bio: form.bio,
+        languages
+    };
+
+    match api.user().update_profile(request.clone()).await {
+        Ok(response) => {
+            cache.update(response.into_inner());
+            Ok(Redirect::to(uri!(profile)))
+        }
+        // Show the form again with what was entered so nothing has to be retyped
+        Err(status) => {
+            let entered = User {
+                username: request.username,
+                display_name: request.display_name,
+                bio: request.bio,
+                languages: request.languages,
+                ..(*user).clone()
+            };
+            Err(profile_page(&entered, &csrf, &settings, Some(status.message())))
+        }
+    }
+}
+
+#[get("/profile/export")]
+pub async fn export_data(api: AuthAPI<'_>) -> ApiResult<File<Vec<u8>>> {
+    let export = api.user().export_my_data(ExportRequest {})
+        .await
+        .map_err(api_error)?
+        .into_inner();
+    Ok(File::new(&export.file_name, export.archive))
+}
+
+#[post("/profile/delete", data = "<form>")]
+pub async fn delete_account(
+    user: CurrentUser,
+    csrf: CsrfToken,
+    form: Form<DeleteForm>,
+    api: AuthAPI<'_>,
+    cache: State<'_, UserCache>,
+    sessions: State<'_, SessionStore>,
+    settings: State<'_, Settings>,
+    cookies: &CookieJar<'_>
+) -> Result<Redirect, Template> {
+    let form = form.into_inner();
+    if !csrf.matches(&form.csrf_token) {
+        return Err(profile_page(&user, &csrf, &settings, Some(CSRF_MESSAGE)));
+    }
+    let request = DeleteAccountRequest { confirm_username: form.confirm_username };
+    match api.user().delete_account(request).await {
+        Ok(_) => {
+            cache.remove(&user.id);
+            sessions.remove_user(&user.id);
+            sessions::remove_session_cookie(cookies);
+            Ok(Redirect::to("/"))
+        }
+        Err(status) => Err(profile_page(&user, &csrf, &settings, Some(status.message())))
+    }
+}
+
+async fn tokens_page(
+    api: &AuthAPI<'_>,
+    csrf: &CsrfToken,
+    secret: Option<&str>,
+    error: Option<&str>
+) -> ApiResult<Template> {
+    let tokens: Vec<TokenRow> = api.user().list_api_tokens(ListApiTokensRequest {})
+        .await
+        .map_err(api_error)?
+        .into_inner()
+        .tokens
+        .into_iter()
+        .map(Into::into)
+        .collect();
+    Ok(template(|w| tokens_html(w, &tokens, &csrf.0, secret, error)))
+}
+
+#[get("/profile/tokens")]
+pub async fn api_tokens(api: AuthAPI<'_>, csrf: CsrfToken) -> ApiResult<Template> {
+    tokens_page(&api, &csrf, None, None).await
+}
+
+/// Shows the list again with the new token, which can't be retrieved later.
+#[post("/profile/tokens", data = "<form>")]
+pub async fn create_api_token(form: Form<TokenForm>, api: AuthAPI<'_>, csrf: CsrfToken) -> ApiResult<Template> {
+    let form = form.into_inner();
+    if !csrf.matches(&form.csrf_token) {
+        return tokens_page(&api, &csrf, None, Some(CSRF_MESSAGE)).await;
+    }
+    let scopes = [
+        (form.subtitles_read, "subtitles.read"),
+        (form.subtitles_write, "subtitles.write"),
+        (form.user_read, "user.read"),
+        (form.user_write, "user.write")
+    ];
+    let request = CreateApiTokenRequest {
+        name: form.name,
+        scopes: scopes.iter()
+            .filter(|(checked, _)| *checked)
+            .map(|(_, scope)| scope.to_string())
+            .collect(),
+        expires_in_days: form.expires_in_days
+    };
+
+    match api.user().create_api_token(request).await {
+        Ok(created) => tokens_page(&api, &csrf, Some(&created.into_inner().secret), None).await,
+        Err(status) => tokens_page(&api, &csrf, None, Some(status.message())).await
+    }
+}
+
+#[post("/profile/tokens/<id>/revoke", data = "<form>")]
+pub async fn revoke_api_token(
+    id: String,
+    form: Form<CsrfForm>,
+    api: AuthAPI<'_>,
+    csrf: CsrfToken
+) -> ApiResult<Redirect> {
+    if !csrf.matches(&form.csrf_token) {
+        return Err(csrf_error());
+    }
+    api.user().revoke_api_token(RevokeApiTokenRequest { id })
+        .await
+        .map_err(api_error)?;
+    Ok(Redirect::to(uri!(api_tokens)))
+}
+
+/// Public page of a contributor with what they've worked on.
+#[get("/users/<id>")]
+pub async fn user_page(id: String, api: API<'_>) -> ApiResult<Template> {
+    let stats = api.user().get_user_stats(UserStatsRequest { user_id: id })
+        .await
+        .map_err(api_error)?
+        .into_inner();
+    let user = stats.user.clone().unwrap_or_default();
+
+    let name = if user.display_name.is_empty() { &user.username } else { &user.display_name };
+    let picture_url = format!("/users/{}/picture?size=128&v={}", user.id, user.picture);
+    let languages = stats.languages.join(", ");
+    let last_active = date(stats.last_active);
+    Ok(template(|w| user_html(w, &user, name, &stats, &picture_url, &languages, &last_active)))
 }
 
 #[get("/profile", rank = 2)]
 pub async fn profile_unauthorized(cookies: &CookieJar<'_>) -> Redirect {
     unauthorized_redirect(uri!(profile_unauthorized), cookies)
 }
+
+/// The `If-None-Match` header, if the client sent one.
+pub struct IfNoneMatch(Option<String>);
+
+#[async_trait]
+impl<'a, 'r> FromRequest<'a, 'r> for IfNoneMatch {
+    type Error = Infallible;
+
+    async fn from_request(request: &'a Request<'r>) -> Outcome<Self, Self::Error> {
+        Outcome::Success(IfNoneMatch(
+            request.headers().get_one("If-None-Match").map(str::to_string)
+        ))
+    }
+}
+
+/// Whether the client accepts WebP images, which all current browsers advertise.
+pub struct AcceptsWebp(bool);
+
+#[async_trait]
+impl<'a, 'r> FromRequest<'a, 'r> for AcceptsWebp {
+    type Error = Infallible;
+
+    async fn from_request(request: &'a Request<'r>) -> Outcome<Self, Self::Error> {
+        let accepts = request.headers()
+            .get("Accept")
+            .any(|accept| accept.contains("image/webp"));
+        Outcome::Success(AcceptsWebp(accepts))
+    }
+}
+
+pub enum Picture {
+    Image { content: Vec<u8>, content_type: ContentType, etag: String },
+    Default(String),
+    NotModified
+}
+
+impl<'r, 'o: 'r> Responder<'r, 'o> for Picture {
+    fn respond_to(self, request: &'r Request<'_>) -> response::Result<'o> {
+        match self {
+            Picture::Image { content, content_type, etag } => Response::build()
+                .merge(content.respond_to(request)?)
+                .header(content_type)
+                .header(cache_control(PICTURE_MAX_AGE))
+                .header(Header::new("ETag", etag))
+                .header(Header::new("Vary", "Accept"))
+                .ok(),
+            Picture::Default(svg) => Response::build()
+                .merge(svg.respond_to(request)?)
+                .header(ContentType::SVG)
+                .header(cache_control(DEFAULT_AVATAR_MAX_AGE))
+                .ok(),
+            Picture::NotModified => Response::build()
+                .status(Status::NotModified)
+                .ok()
+        }
+    }
+}
+
+fn cache_control(max_age: u32) -> Header<'static> {
+    Header::new("Cache-Control", format!("public, max-age={}", max_age))
+}
+
+/// Initials on a background colour derived from the user id, so it stays the same across
+/// renames.
+fn default_avatar(user_id: &str, username: &str) -> String {
+    let initials: String = username
+        .split_whitespace()
+        .filter_map(|word| word.chars().find(|c| c.is_alphanumeric()))
+        .flat_map(char::to_uppercase)
+        .take(2)
+        .collect();
+    let hue = user_id
+        .bytes()
+        .fold(0u32, |hash, byte| hash.wrapping_mul(31).wrapping_add(byte as u32))
+        % 360;
+
+    format!(
+        concat!(
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="256" height="256" viewBox="0 0 256 256">"#,
+            r#"<rect width="256" height="256" fill="hsl({}, 45%, 55%)"/>"#,
+            r#"<text x="50%" y="50%" dy=".35em" text-anchor="middle" fill="#fff" "#,
+            r#"font-family="sans-serif" font-size="112">{}</text></svg>"#
+        ),
+        hue, initials
+    )
+}
+
+#[get("/users/<id>/picture?<size>")]
+pub async fn profile_picture(
+    id: String,
+    size: Option<u32>,
+    accepts_webp: AcceptsWebp,
+    if_none_match: IfNoneMatch,
+    api: API<'_>
+) -> ApiResult<Picture> {
+    let format = if accepts_webp.0 { Format::Webp } else { Format::Png };
+    let picture = api.user().get_profile_picture(ProfilePictureRequest {
+        user_id: id.clone(),
+        size: size.unwrap_or_default(),
+        format: format as i32
+    })
+        .await
+        .map_err(api_error)?
+        .into_inner();
+
+    if picture.content.is_empty() {
+        return Ok(Picture::Default(default_avatar(&id, &picture.username)));
+    }
+
+    let etag = format!("\"{}\"", picture.version);
+    if if_none_match.0.as_deref() == Some(&*etag) {
+        return Ok(Picture::NotModified);
+    }
+    let content_type = ContentType::parse_flexible(&picture.content_type).unwrap_or(ContentType::PNG);
+    Ok(Picture::Image { content: picture.content, content_type, etag })
+}
@@ -0,0 +1,332 @@
+//! Server side sessions. The browser only gets an opaque id in a private cookie, the tokens stay
+//! here so sessions can be ended without the browser's cooperation.
+//!
+//! Each session also has a CSRF token. Pages that send JSON requests changing data get it from
+//! [`CsrfToken`] and send it back in the `X-CSRF-Token` header, which [`CsrfChecked`] verifies.
+//! Forms send it in a hidden `csrf_token` field instead, see [`CsrfForm`].
+
+use crate::settings::Sessions;
+use chrono::Utc;
+use openid::Bearer;
+use parking_lot::{Mutex as SyncMutex, RwLock};
+use rocket::{
+    http::{Cookie, CookieJar, SameSite, Status},
+    request::{FromRequest, Outcome},
+    Request
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    convert::Infallible,
+    fs,
+    io::{self, Write},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc
+    }
+};
+use tokio::sync::{Mutex, MutexGuard};
+use uuid::Uuid;
+
+const SESSION_COOKIE: &str = "__session__";
+const CSRF_HEADER: &str = "X-CSRF-Token";
+
+/// An unguessable token for ids and secrets, two v4 UUIDs give 244 random bits.
+pub fn random_token() -> String {
+    format!("{}{}", Uuid::new_v4().to_simple(), Uuid::new_v4().to_simple())
+}
+
+/// Compares secrets without returning early, so timing doesn't reveal how much matched.
+pub fn constant_time_eq(a: &str, b: &str) -> bool {
+    a.len() == b.len() && a.bytes().zip(b.bytes()).fold(0, |diff, (a, b)| diff | (a ^ b)) == 0
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Session {
+    /// Subject of the ID token
+    pub user_id: String,
+    pub access_token: String,
+    pub id_token: Option<String>,
+    pub refresh_token: Option<String>,
+    /// Unix timestamps in seconds
+    pub created: i64,
+    pub last_seen: i64,
+    /// Sessions saved before CSRF tokens existed get a new one
+    #[serde(default = "random_token")]
+    pub csrf_token: String,
+    /// Held while the tokens are refreshed, shared by all copies of the session
+    #[serde(skip)]
+    pub refreshing: RefreshLock
+}
+
+#[derive(Clone)]
+pub struct RefreshLock(Arc<Mutex<()>>);
+
+impl RefreshLock {
+    pub async fn lock(&self) -> MutexGuard<'_, ()> {
+        self.0.lock().await
+    }
+}
+
+impl Default for RefreshLock {
+    fn default() -> Self {
+        RefreshLock(Arc::new(Mutex::new(())))
+    }
+}
+
+impl Session {
+    pub fn new(user_id: String, bearer: &Bearer) -> Self {
+        let now = Utc::now().timestamp();
+        Session {
+            user_id,
+            access_token: bearer.access_token.clone(),
+            id_token: bearer.id_token.clone(),
+            refresh_token: bearer.refresh_token.clone(),
+            created: now,
+            last_seen: now,
+            csrf_token: random_token(),
+            refreshing: RefreshLock::default()
+        }
+    }
+
+    /// Refresh responses don't always include a new ID or refresh token, the old ones stay valid
+    /// then.
+    fn update_tokens(&mut self, bearer: &Bearer) {
+        self.access_token = bearer.access_token.clone();
+        if bearer.id_token.is_some() {
+            self.id_token = bearer.id_token.clone();
+        }
+        if bearer.refresh_token.is_some() {
+            self.refresh_token = bearer.refresh_token.clone();
+        }
+    }
+}
+
+pub struct SessionStore {
+    sessions: RwLock<HashMap<String, Session>>,
+    file: Option<PathBuf>,
+    idle_seconds: i64,
+    /// Numbers snapshots in the order of the changes they contain
+    generation: AtomicU64,
+    /// Generation of the snapshot in the file, held while writing it
+    written: SyncMutex<u64>
+}
+
+/// The sessions serialized after a change, written to the file once the sessions are unlocked
+/// so requests don't wait on the disk.
+struct Snapshot {
+    generation: u64,
+    json: serde_json::Result<Vec<u8>>
+}
+
+/// The file holds refresh tokens, so only the owner may read it.
+#[cfg(unix)]
+fn create_private(path: &Path) -> io::Result<fs::File> {
+    use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+
+    let file = fs::OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .mode(0o600)
+        .open(path)?;
+    // The mode only applies to new files, older ones may still be readable by others
+    file.set_permissions(fs::Permissions::from_mode(0o600))?;
+    Ok(file)
+}
+
+#[cfg(not(unix))]
+fn create_private(path: &Path) -> io::Result<fs::File> {
+    fs::File::create(path)
+}
+
+impl SessionStore {
+    /// Loads the sessions saved by the last run, if they're persisted.
+    pub fn load(settings: &Sessions) -> io::Result<Self> {
+        let file = Some(PathBuf::from(&settings.file)).filter(|_| !settings.file.is_empty());
+        let sessions = match &file {
+            Some(path) if path.exists() => serde_json::from_slice(&fs::read(path)?)?,
+            _ => HashMap::new()
+        };
+        Ok(SessionStore {
+            sessions: RwLock::new(sessions),
+            file,
+            idle_seconds: settings.idle_days * 24 * 60 * 60,
+            generation: AtomicU64::new(0),
+            written: SyncMutex::new(0)
+        })
+    }
+
+    /// Serializes the sessions while the caller still holds the lock, so snapshots are numbered
+    /// in the order of the changes. `None` if sessions aren't persisted.
+    fn snapshot(&self, sessions: &HashMap<String, Session>) -> Option<Snapshot> {
+        self.file.as_ref()?;
+        Some(Snapshot {
+            generation: self.generation.fetch_add(1, Ordering::SeqCst) + 1,
+            json: serde_json::to_vec(sessions)
+        })
+    }
+
+    /// Writes a snapshot after the sessions were unlocked, unless a newer one got there first.
+    fn save(&self, snapshot: Option<Snapshot>) {
+        let (snapshot, path) = match (snapshot, &self.file) {
+            (Some(snapshot), Some(path)) => (snapshot, path),
+            _ => return
+        };
+        let mut written = self.written.lock();
+        if snapshot.generation <= *written {
+            return;
+        }
+        let result = snapshot.json
+            .map_err(io::Error::from)
+            .and_then(|json| create_private(path)?.write_all(&json));
+        match result {
+            Ok(()) => *written = snapshot.generation,
+            Err(err) => eprintln!("Couldn't save sessions to {}: {}", path.display(), err)
+        }
+    }
+
+    /// Starts a session and returns its id. Sessions that went idle are dropped on the way.
+    pub fn create(&self, session: Session) -> String {
+        let id = random_token();
+        let now = Utc::now().timestamp();
+        let snapshot = {
+            let mut sessions = self.sessions.write();
+            sessions.retain(|_, session| now - session.last_seen <= self.idle_seconds);
+            sessions.insert(id.clone(), session);
+            self.snapshot(&sessions)
+        };
+        self.save(snapshot);
+        id
+    }
+
+    /// The session with `id`, unless it was idle for too long. Being seen isn't saved right
+    /// away, only with the next change.
+    pub fn get(&self, id: &str) -> Option<Session> {
+        let now = Utc::now().timestamp();
+        let snapshot = {
+            let mut sessions = self.sessions.write();
+            let session = sessions.get_mut(id)?;
+            if now - session.last_seen <= self.idle_seconds {
+                session.last_seen = now;
+                return Some(session.clone());
+            }
+            sessions.remove(id);
+            self.snapshot(&sessions)
+        };
+        self.save(snapshot);
+        None
+    }
+
+    /// How long an unused session lasts, which is also how long its cookie is kept.
+    pub fn max_age(&self) -> time::Duration {
+        time::Duration::seconds(self.idle_seconds)
+    }
+
+    pub fn update_tokens(&self, id: &str, bearer: &Bearer) {
+        let snapshot = {
+            let mut sessions = self.sessions.write();
+            match sessions.get_mut(id) {
+                Some(session) => session.update_tokens(bearer),
+                None => return
+            }
+            self.snapshot(&sessions)
+        };
+        self.save(snapshot);
+    }
+
+    pub fn remove(&self, id: &str) -> Option<Session> {
+        let (session, snapshot) = {
+            let mut sessions = self.sessions.write();
+            let session = sessions.remove(id);
+            (session, self.snapshot(&sessions))
+        };
+        self.save(snapshot);
+        session
+    }
+
+    /// Ends every session of a user, returns how many there were.
+    pub fn remove_user(&self, user_id: &str) -> usize {
+        let (removed, snapshot) = {
+            let mut sessions = self.sessions.write();
+            let before = sessions.len();
+            sessions.retain(|_, session| session.user_id != user_id);
+            (before - sessions.len(), self.snapshot(&sessions))
+        };
+        self.save(snapshot);
+        removed
+    }
+}
+
+pub fn session_id(cookies: &CookieJar<'_>) -> Option<String> {
+    cookies.get_private(SESSION_COOKIE).map(|cookie| cookie.value().to_string())
+}
+
+/// Lax instead of the default strict, otherwise the cookie isn't sent with the redirect that
+/// follows signing in. Requests changing data are protected by CSRF tokens instead.
+///
+/// The cookie lives as long as an idle session instead of the default week, and is issued again
+/// whenever the session is used so it expires together with the session.
+pub fn add_session_cookie(cookies: &CookieJar<'_>, sessions: &SessionStore, id: String) {
+    let cookie = Cookie::build(SESSION_COOKIE, id)
+        .same_site(SameSite::Lax)
+        .max_age(sessions.max_age())
+        .finish();
+    cookies.add_private(cookie);
+}
+
+pub fn remove_session_cookie(cookies: &CookieJar<'_>) {
+    cookies.remove_private(Cookie::named(SESSION_COOKIE));
+}
+
+fn current_session(request: &Request<'_>) -> Option<Session> {
+    let id = session_id(request.cookies())?;
+    request.managed_state::<SessionStore>()?.get(&id)
+}
+
+/// The CSRF token of the current session, forwards if there is none.
+pub struct CsrfToken(pub String);
+
+impl CsrfToken {
+    /// Whether a form sent this token, the form field variant of [`CsrfChecked`].
+    pub fn matches(&self, sent: &str) -> bool {
+        constant_time_eq(sent, &self.0)
+    }
+}
+
+/// Body of forms that send nothing but the CSRF token, like sign out buttons. Forms with more
+/// fields have a `csrf_token` field of their own.
+#[derive(FromForm)]
+pub struct CsrfForm {
+    pub csrf_token: String
+}
+
+#[async_trait]
+impl<'a, 'r> FromRequest<'a, 'r> for CsrfToken {
+    type Error = Infallible;
+
+    async fn from_request(request: &'a Request<'r>) -> Outcome<Self, Self::Error> {
+        match current_session(request) {
+            Some(session) => Outcome::Success(CsrfToken(session.csrf_token)),
+            None => Outcome::Forward(())
+        }
+    }
+}
+
+/// Rejects requests without the session's CSRF token with `403 Forbidden`. Other sites can make
+/// the browser send cookies, but can't read the token to put it into the header.
+pub struct CsrfChecked;
+
+#[async_trait]
+impl<'a, 'r> FromRequest<'a, 'r> for CsrfChecked {
+    type Error = ();
+
+    async fn from_request(request: &'a Request<'r>) -> Outcome<Self, Self::Error> {
+        let sent = request.headers().get_one(CSRF_HEADER).unwrap_or_default();
+        match current_session(request) {
+            Some(session) if constant_time_eq(sent, &session.csrf_token) => Outcome::Success(CsrfChecked),
+            _ => Outcome::Failure((Status::Forbidden, ()))
+        }
+    }
+}
@@ -2,7 +2,9 @@ use serde::Deserialize;
 
 #[derive(Default, Deserialize)]
 pub struct Settings {
-    pub authentication: Authentication
+    pub authentication: Authentication,
+    #[serde(default)]
+    pub sessions: Sessions
 }
 
 #[derive(Default, Deserialize)]
@@ -11,8 +13,86 @@ pub struct Authentication {
     pub client_id: String,
     pub client_secret: String,
     pub api_url: String,
+    /// Scope requested for the API, `{api_url}/User` like Azure AD B2C names it if empty. The
+    /// API has to map it to full access in its claim mapping.
+    #[serde(default)]
+    pub full_access_scope: String,
 
     pub signin_policy: String,
+    #[serde(default)]
     pub edit_profile_policy: String,
-    pub reset_password_policy: String
+    #[serde(default)]
+    pub reset_password_policy: String,
+    /// Pages of providers without policies, used instead of the policies if set
+    #[serde(default)]
+    pub edit_profile_url: String,
+    #[serde(default)]
+    pub reset_password_url: String,
+    /// Error the identity provider sends back to the login callback when the user asks to reset
+    /// their password, which then starts `reset_password`
+    #[serde(default = "forgot_password_error")]
+    pub forgot_password_error: String,
+    /// Error the identity provider sends back when the user cancels a flow, which then returns
+    /// to where they came from
+    #[serde(default = "cancelled_error")]
+    pub cancelled_error: String
+}
+
+fn forgot_password_error() -> String {
+    // Azure AD B2C's "The user has forgotten their password"
+    "AADB2C90118".to_string()
+}
+
+fn cancelled_error() -> String {
+    // Azure AD B2C's "The user has cancelled entering self-asserted information"
+    "AADB2C90091".to_string()
+}
+
+impl Authentication {
+    pub fn edit_profile(&self) -> Flow<'_> {
+        Flow {
+            policy: &self.edit_profile_policy,
+            url: &self.edit_profile_url
+        }
+    }
+
+    pub fn reset_password(&self) -> Flow<'_> {
+        Flow {
+            policy: &self.reset_password_policy,
+            url: &self.reset_password_url
+        }
+    }
+}
+
+/// A flow the identity provider runs for the user, like changing their password. Azure AD B2C
+/// runs them as policies started like a sign in, other providers usually have a page for them.
+/// The flow is disabled if neither is set.
+pub struct Flow<'a> {
+    pub policy: &'a str,
+    pub url: &'a str
+}
+
+impl Flow<'_> {
+    pub fn is_enabled(&self) -> bool {
+        !self.policy.is_empty() || !self.url.is_empty()
+    }
+}
+
+/// Sessions only live in memory unless a file is set, so restarting signs everyone out.
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct Sessions {
+    /// JSON file sessions are saved to. It holds refresh tokens, so only its owner can read it.
+    pub file: String,
+    /// Sessions that weren't used for this long are ended, their cookies expire with them
+    pub idle_days: i64
+}
+
+impl Default for Sessions {
+    fn default() -> Self {
+        Sessions {
+            file: String::new(),
+            idle_days: 30
+        }
+    }
 }
@@ -1,7 +1,6 @@
-use crate::AuthAPI;
+use crate::{AuthAPI, error::{api_error, ApiResult}, sessions::CsrfChecked};
 use rocket_contrib::json::Json;
-use api_types::subtitles::{Subtitles, SubtitleId, DownloadRequest};
-use rocket::response::status::BadRequest;
+use api_types::subtitles::{Subtitles, SubtitleId, DownloadRequest, VideoId, SourceCaptionList, SetSubtitleResponse};
 use rocket::response::{Stream, Responder};
 use api_types::subtitles::download_request::Format;
 use rocket::futures::{TryStreamExt, io};
@@ -32,35 +31,55 @@ impl<R> File<R> {
     }
 }
 
-#[get("/<video_id>?<lang>")]
-pub async fn get_subtitles(video_id: String, lang: String, api: AuthAPI<'_>) -> Json<Subtitles> {
+#[get("/<video_id>?<lang>&<source>")]
+pub async fn get_subtitles(video_id: String, lang: String, source: Option<String>, api: AuthAPI<'_>) -> ApiResult<Json<Subtitles>> {
     println!("Getting subtitles");
     let response = api.subtitles().get_subtitles(SubtitleId {
         video_id,
-        language: lang
-    }).await.unwrap().into_inner();
-    Json(response)
+        language: lang,
+        source: source.unwrap_or_default()
+    }).await.map_err(api_error)?.into_inner();
+    Ok(Json(response))
+}
+
+#[get("/sources/<video_id>")]
+pub async fn list_source_captions(video_id: String, api: AuthAPI<'_>) -> ApiResult<Json<SourceCaptionList>> {
+    let response = api.subtitles().list_source_captions(VideoId { video_id })
+        .await
+        .map_err(api_error)?
+        .into_inner();
+    Ok(Json(response))
 }
 
 #[post("/", format = "json", data = "<body>")]
-pub async fn set_subtitles(api: AuthAPI<'_>, body: Json<Subtitles>) -> Result<(), BadRequest<String>> {
-    api.subtitles().set_subtitles(body.into_inner())
+pub async fn set_subtitles(
+    api: AuthAPI<'_>,
+    _csrf: CsrfChecked,
+    body: Json<Subtitles>
+) -> ApiResult<Json<SetSubtitleResponse>> {
+    let response = api.subtitles().set_subtitles(body.into_inner())
         .await
-        .map_err(|err| BadRequest(Some(err.message().to_string())))?;
-    Ok(())
+        .map_err(api_error)?
+        .into_inner();
+    Ok(Json(response))
 }
 
-#[get("/download/<video_id>?<lang>")]
-pub async fn download_subtitles(api: AuthAPI<'_>, video_id: String, lang: String) -> impl Responder<'_, '_> {
-    let file_name = format!("subs_{}_{}.srt", video_id, lang);
+#[get("/download/<video_id>?<lang>&<format>")]
+pub async fn download_subtitles(api: AuthAPI<'_>, video_id: String, lang: String, format: Option<String>) -> ApiResult<File<impl Responder<'_, '_>>> {
+    let (format, extension) = match format.as_deref() {
+        Some("vtt") => (Format::Vtt, "vtt"),
+        Some("ass") => (Format::Ass, "ass"),
+        _ => (Format::Srt, "srt")
+    };
+    let file_name = format!("subs_{}_{}.{}", video_id, lang, extension);
     let res = api.subtitles().download_subtitles(DownloadRequest {
         video_id,
         language: lang,
-        format: Format::Srt as i32
-    }).await.unwrap().into_inner().map_err(|err| {
+        format: format as i32
+    }).await.map_err(api_error)?.into_inner().map_err(|err| {
         io::Error::new(ErrorKind::Other, err.message())
     });
     let stream = Stream::chunked(res.into_async_read().compat(), 1024);
 
-    File::new(&file_name, stream)
+    Ok(File::new(&file_name, stream))
 }
\ No newline at end of file
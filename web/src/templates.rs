@@ -19,6 +19,18 @@ pub use self::template_profile_html::profile_html;
 #[deprecated(since="0.7.4", note="please use `profile_html` instead")]
 pub use self::profile_html as profile;
 
+mod template_tokens_html;
+pub use self::template_tokens_html::tokens_html;
+
+#[deprecated(since="0.7.4", note="please use `tokens_html` instead")]
+pub use self::tokens_html as tokens;
+
+mod template_user_html;
+pub use self::template_user_html::user_html;
+
+#[deprecated(since="0.7.4", note="please use `user_html` instead")]
+pub use self::user_html as user;
+
 /// This trait should be implemented for any value that can be the
 /// result of an expression in a template.
 ///
@@ -5,11 +5,55 @@ use std::io::{self, Write};
 use super::{Html,ToHtml};
 use crate::User;
 
-pub fn profile_html<W>(mut _ructe_out_: &mut W, user: &User, profile_picture: &str) -> io::Result<()> where W: ?Sized, for<'a> &'a mut W: Write {
-_ructe_out_.write_all(b"<html lang=\"en\">\r\n    <body>\r\n        <p>")?;
+pub fn profile_html<W>(mut _ructe_out_: &mut W, user: &User, profile_picture: &str, caption_languages: &str, translate_languages: &str, can_edit_identity: bool, can_reset_password: bool, csrf_token: &str, error: Option<&str>) -> io::Result<()> where W: ?Sized, for<'a> &'a mut W: Write {
+_ructe_out_.write_all(b"<html lang=\"en\">\r\n    <body>\r\n        ")?;
+if user.display_name.is_empty() {
+_ructe_out_.write_all(b"\r\n            <h1>")?;
+user.username.to_html(&mut _ructe_out_)?;
+_ructe_out_.write_all(b"</h1>\r\n        ")?;
+} else {
+_ructe_out_.write_all(b"\r\n            <h1>")?;
+user.display_name.to_html(&mut _ructe_out_)?;
+_ructe_out_.write_all(b"</h1>\r\n            <p>")?;
 user.username.to_html(&mut _ructe_out_)?;
 _ructe_out_.write_all(b"</p>\r\n        ")?;
+}
+_ructe_out_.write_all(b"\r\n        <img src=\"")?;
 profile_picture.to_html(&mut _ructe_out_)?;
-_ructe_out_.write_all(b"\r\n    </body>\r\n</html>")?;
+_ructe_out_.write_all(b"\" alt=\"Profile picture\" width=\"128\" height=\"128\">\r\n        <p>")?;
+user.bio.to_html(&mut _ructe_out_)?;
+_ructe_out_.write_all(b"</p>\r\n        <form method=\"post\" action=\"/profile\">\r\n            <input type=\"hidden\" name=\"csrf_token\" value=\"")?;
+csrf_token.to_html(&mut _ructe_out_)?;
+_ructe_out_.write_all(b"\">\r\n            ")?;
+if let Some(error) = error {
+_ructe_out_.write_all(b"\r\n                <p class=\"error\">")?;
+error.to_html(&mut _ructe_out_)?;
+_ructe_out_.write_all(b"</p>\r\n            ")?;
+}
+_ructe_out_.write_all(b"\r\n            <label>Username <input name=\"username\" value=\"")?;
+user.username.to_html(&mut _ructe_out_)?;
+_ructe_out_.write_all(b"\" required minlength=\"3\" maxlength=\"32\"></label>\r\n            <label>Display name <input name=\"display_name\" value=\"")?;
+user.display_name.to_html(&mut _ructe_out_)?;
+_ructe_out_.write_all(b"\" maxlength=\"64\"></label>\r\n            <label>Bio <textarea name=\"bio\" maxlength=\"1000\">")?;
+user.bio.to_html(&mut _ructe_out_)?;
+_ructe_out_.write_all(b"</textarea></label>\r\n            <label>Languages I can caption <input name=\"caption_languages\" value=\"")?;
+caption_languages.to_html(&mut _ructe_out_)?;
+_ructe_out_.write_all(b"\" placeholder=\"en, de\"></label>\r\n            <label>Languages I can translate <input name=\"translate_languages\" value=\"")?;
+translate_languages.to_html(&mut _ructe_out_)?;
+_ructe_out_.write_all(b"\" placeholder=\"en, pt-BR\"></label>\r\n            <button type=\"submit\">Save</button>\r\n        </form>\r\n        <form method=\"post\" action=\"/logout\"><input type=\"hidden\" name=\"csrf_token\" value=\"")?;
+csrf_token.to_html(&mut _ructe_out_)?;
+_ructe_out_.write_all(b"\"><button type=\"submit\">Sign out</button></form>\r\n        <form method=\"post\" action=\"/logout/everywhere\"><input type=\"hidden\" name=\"csrf_token\" value=\"")?;
+csrf_token.to_html(&mut _ructe_out_)?;
+_ructe_out_.write_all(b"\"><button type=\"submit\">Sign out of all devices</button></form>\r\n        <a href=\"/profile/tokens\">API tokens</a>\r\n        <a href=\"/profile/export\" download>Download my data</a>\r\n        ")?;
+if can_edit_identity {
+_ructe_out_.write_all(b"\r\n            <a href=\"/profile/edit-identity\">Edit sign-in details</a>\r\n        ")?;
+}
+_ructe_out_.write_all(b"\r\n        ")?;
+if can_reset_password {
+_ructe_out_.write_all(b"\r\n            <a href=\"/password/reset\">Change password</a>\r\n        ")?;
+}
+_ructe_out_.write_all(b"\r\n        <form method=\"post\" action=\"/profile/delete\">\r\n            <input type=\"hidden\" name=\"csrf_token\" value=\"")?;
+csrf_token.to_html(&mut _ructe_out_)?;
+_ructe_out_.write_all(b"\">\r\n            <p>Deleting your account removes your profile and picture. Your edits stay, but are no longer linked to you.</p>\r\n            <label>Type your username to confirm <input name=\"confirm_username\" required></label>\r\n            <button type=\"submit\">Delete account</button>\r\n        </form>\r\n    </body>\r\n</html>")?;
 Ok(())
 }
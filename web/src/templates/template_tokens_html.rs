@@ -0,0 +1,49 @@
+use std::io::{self, Write};
+#[allow(renamed_and_removed_lints)]
+#[cfg_attr(feature="cargo-clippy", allow(useless_attribute))]
+#[allow(unused)]
+use super::{Html,ToHtml};
+use crate::profile::TokenRow;
+
+pub fn tokens_html<W>(mut _ructe_out_: &mut W, tokens: &[TokenRow], csrf_token: &str, secret: Option<&str>, error: Option<&str>) -> io::Result<()> where W: ?Sized, for<'a> &'a mut W: Write {
+_ructe_out_.write_all(b"<html lang=\"en\">\r\n    <head><title>API tokens</title></head>\r\n    <body>\r\n        <h1>API tokens</h1>\r\n        <p>Tokens let scripts use the API on your behalf. Send them in an <code>Authorization: Bearer</code> header.</p>\r\n        ")?;
+if let Some(secret) = secret {
+_ructe_out_.write_all(b"\r\n            <p>Copy your new token now, it won't be shown again.</p>\r\n            <pre>")?;
+secret.to_html(&mut _ructe_out_)?;
+_ructe_out_.write_all(b"</pre>\r\n        ")?;
+}
+_ructe_out_.write_all(b"\r\n        ")?;
+if tokens.is_empty() {
+_ructe_out_.write_all(b"\r\n            <p>You don't have any tokens.</p>\r\n        ")?;
+} else {
+_ructe_out_.write_all(b"\r\n            <table>\r\n                <tr><th>Name</th><th>Scopes</th><th>Created</th><th>Expires</th><th>Last used</th><th></th></tr>\r\n                ")?;
+for token in tokens {
+_ructe_out_.write_all(b"\r\n                    <tr>\r\n                        <td>")?;
+token.name.to_html(&mut _ructe_out_)?;
+_ructe_out_.write_all(b"</td>\r\n                        <td>")?;
+token.scopes.to_html(&mut _ructe_out_)?;
+_ructe_out_.write_all(b"</td>\r\n                        <td>")?;
+token.created.to_html(&mut _ructe_out_)?;
+_ructe_out_.write_all(b"</td>\r\n                        <td>")?;
+token.expires.to_html(&mut _ructe_out_)?;
+_ructe_out_.write_all(b"</td>\r\n                        <td>")?;
+token.last_used.to_html(&mut _ructe_out_)?;
+_ructe_out_.write_all(b"</td>\r\n                        <td><form method=\"post\" action=\"/profile/tokens/")?;
+token.id.to_html(&mut _ructe_out_)?;
+_ructe_out_.write_all(b"/revoke\"><input type=\"hidden\" name=\"csrf_token\" value=\"")?;
+csrf_token.to_html(&mut _ructe_out_)?;
+_ructe_out_.write_all(b"\"><button type=\"submit\">Revoke</button></form></td>\r\n                    </tr>\r\n                ")?;
+}
+_ructe_out_.write_all(b"\r\n            </table>\r\n        ")?;
+}
+_ructe_out_.write_all(b"\r\n        <form method=\"post\" action=\"/profile/tokens\">\r\n            <input type=\"hidden\" name=\"csrf_token\" value=\"")?;
+csrf_token.to_html(&mut _ructe_out_)?;
+_ructe_out_.write_all(b"\">\r\n            ")?;
+if let Some(error) = error {
+_ructe_out_.write_all(b"\r\n                <p class=\"error\">")?;
+error.to_html(&mut _ructe_out_)?;
+_ructe_out_.write_all(b"</p>\r\n            ")?;
+}
+_ructe_out_.write_all(b"\r\n            <label>Name <input name=\"name\" required maxlength=\"64\" placeholder=\"CI\"></label>\r\n            <label><input type=\"checkbox\" name=\"subtitles_read\" checked> Read subtitles</label>\r\n            <label><input type=\"checkbox\" name=\"subtitles_write\"> Edit subtitles</label>\r\n            <label><input type=\"checkbox\" name=\"user_read\"> Read profile</label>\r\n            <label><input type=\"checkbox\" name=\"user_write\"> Edit profile</label>\r\n            <label>Expires after <input type=\"number\" name=\"expires_in_days\" value=\"90\" min=\"1\" max=\"365\"> days</label>\r\n            <button type=\"submit\">Create token</button>\r\n        </form>\r\n        <a href=\"/profile\">Back to profile</a>\r\n    </body>\r\n</html>")?;
+Ok(())
+}
@@ -0,0 +1,30 @@
+use std::io::{self, Write};
+#[allow(renamed_and_removed_lints)]
+#[cfg_attr(feature="cargo-clippy", allow(useless_attribute))]
+#[allow(unused)]
+use super::{Html,ToHtml};
+use api_types::user::UserStats;
+use crate::User;
+
+pub fn user_html<W>(mut _ructe_out_: &mut W, user: &User, name: &str, stats: &UserStats, profile_picture: &str, languages: &str, last_active: &str) -> io::Result<()> where W: ?Sized, for<'a> &'a mut W: Write {
+_ructe_out_.write_all(b"<html lang=\"en\">\r\n    <head><title>")?;
+name.to_html(&mut _ructe_out_)?;
+_ructe_out_.write_all(b"</title></head>\r\n    <body>\r\n        <img src=\"")?;
+profile_picture.to_html(&mut _ructe_out_)?;
+_ructe_out_.write_all(b"\" alt=\"Profile picture\" width=\"128\" height=\"128\">\r\n        <h1>")?;
+name.to_html(&mut _ructe_out_)?;
+_ructe_out_.write_all(b"</h1>\r\n        <p>")?;
+user.bio.to_html(&mut _ructe_out_)?;
+_ructe_out_.write_all(b"</p>\r\n        <dl>\r\n            <dt>Edits</dt><dd>")?;
+stats.edits.to_html(&mut _ructe_out_)?;
+_ructe_out_.write_all(b"</dd>\r\n            <dt>Tracks</dt><dd>")?;
+stats.tracks_touched.to_html(&mut _ructe_out_)?;
+_ructe_out_.write_all(b"</dd>\r\n            <dt>Entries edited</dt><dd>")?;
+stats.entries_edited.to_html(&mut _ructe_out_)?;
+_ructe_out_.write_all(b"</dd>\r\n            <dt>Languages</dt><dd>")?;
+languages.to_html(&mut _ructe_out_)?;
+_ructe_out_.write_all(b"</dd>\r\n            <dt>Last active</dt><dd>")?;
+last_active.to_html(&mut _ructe_out_)?;
+_ructe_out_.write_all(b"</dd>\r\n        </dl>\r\n    </body>\r\n</html>")?;
+Ok(())
+}
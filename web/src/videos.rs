@@ -0,0 +1,21 @@
+use crate::{AuthAPI, error::{api_error, ApiResult}, sessions::CsrfChecked};
+use api_types::subtitles::{AddVideoRequest, Video, VideoId};
+use rocket_contrib::json::Json;
+
+#[get("/<video_id>")]
+pub async fn get_video(video_id: String, api: AuthAPI<'_>) -> ApiResult<Json<Video>> {
+    let response = api.subtitles().get_video(VideoId { video_id })
+        .await
+        .map_err(api_error)?
+        .into_inner();
+    Ok(Json(response))
+}
+
+#[post("/", format = "json", data = "<body>")]
+pub async fn add_video(_csrf: CsrfChecked, body: Json<AddVideoRequest>, api: AuthAPI<'_>) -> ApiResult<Json<Video>> {
+    let response = api.subtitles().add_video(body.into_inner())
+        .await
+        .map_err(api_error)?
+        .into_inner();
+    Ok(Json(response))
+}